@@ -0,0 +1,319 @@
+//! Layered configuration. Layers are merged in order, later layers overriding earlier ones:
+//!
+//! 1. built-in defaults,
+//! 2. the configuration files (`config.toml` unless `--config` is given), each followed by its
+//!    profile file when a profile is selected, e.g. `config.prod.toml` for `--profile prod`,
+//! 3. `SOURCEWATCH_*` environment variables, where `__` separates nested keys and numbers index
+//!    arrays, e.g. `SOURCEWATCH_SERVER__BIND` or `SOURCEWATCH_DATABASES__POSTGRESQL__0__HOST`,
+//! 4. command-line flags.
+//!
+//! Tables are merged key by key; arrays and other values are replaced as a whole. Overrides are
+//! read as TOML literals, except for the settings in `STRING_SETTINGS`, e.g. a numeric password.
+use std::path::Path;
+use crate::config::config::{interpolate_value, redact_url, Config, ConfigError, DEFAULT_CONFIG_FILE, REDACTED};
+
+pub const ENV_PREFIX: &str = "SOURCEWATCH_";
+/// The top-level sections `SOURCEWATCH_*` variables can override.
+const SECTIONS: [&str; 3] = ["server", "databases", "logs"];
+/// The settings holding strings, as key paths without array indexes and with `*` for the type
+/// of source. Overrides of these are kept as the string they were given as.
+const STRING_SETTINGS: [&str; 20] = [
+    "server.bind", "server.log_level",
+    "databases.*.name", "databases.*.url", "databases.*.host", "databases.*.username", "databases.*.password",
+    "databases.*.password_file", "databases.*.password_command", "databases.*.database", "databases.*.path",
+    "databases.*.tls.ca_file", "databases.*.tls.cert_file", "databases.*.tls.key_file", "databases.*.tls.verify",
+    "databases.*.tls.server_name",
+    "logs.file.name", "logs.file.path", "logs.file.format", "logs.file.encoding",
+];
+
+pub const USAGE: &str = "\
+Usage: source_watch [COMMAND] [OPTIONS]
+
+Commands:
+  serve          Start the server (default)
+  print-config   Print the effective configuration with secrets redacted
+
+Options:
+  -c, --config <FILE>      Configuration file, can be repeated (default: config.toml)
+  -p, --profile <NAME>     Also load <file>.<NAME>.toml after each configuration file
+      --bind <ADDR>        Address to listen on
+      --log-level <LEVEL>  Default log filter
+      --set <KEY=VALUE>    Override any setting, e.g. --set databases.redis.0.port=6380
+  -h, --help               Print this help
+
+Environment:
+  SOURCEWATCH_CONFIG       Comma-separated configuration files
+  SOURCEWATCH_PROFILE      Profile to load
+  SOURCEWATCH_BIND, SOURCEWATCH_LOG_LEVEL
+  SOURCEWATCH_<KEY>        Override any setting, with __ between nested keys";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CliCommand {
+    #[default]
+    Serve,
+    PrintConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CliArgs {
+    pub command: CliCommand,
+    pub config_files: Vec<String>,
+    pub profile: Option<String>,
+    /// Dotted keys and their values, applied in order.
+    pub overrides: Vec<(String, String)>,
+    pub help: bool,
+}
+
+impl CliArgs {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<CliArgs, ConfigError> {
+        let mut cli = CliArgs::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+                _ => (arg.clone(), None),
+            };
+            let mut value = |name: &str| {
+                inline_value.clone().or_else(|| args.next())
+                    .ok_or_else(|| ConfigError::InvalidConfig(format!("{} requires a value", name)))
+            };
+
+            match flag.as_str() {
+                "-h" | "--help" => cli.help = true,
+                "-c" | "--config" => cli.config_files.push(value(&flag)?),
+                "-p" | "--profile" => cli.profile = Some(value(&flag)?),
+                "--bind" => cli.overrides.push(("server.bind".to_string(), value(&flag)?)),
+                "--log-level" => cli.overrides.push(("server.log_level".to_string(), value(&flag)?)),
+                "--set" => {
+                    let setting = value(&flag)?;
+                    let (key, value) = setting.split_once('=')
+                        .ok_or_else(|| ConfigError::InvalidConfig(format!("--set expects KEY=VALUE, got {}", setting)))?;
+                    cli.overrides.push((key.to_string(), value.to_string()));
+                }
+                "serve" => cli.command = CliCommand::Serve,
+                "print-config" => cli.command = CliCommand::PrintConfig,
+                _ => return Err(ConfigError::InvalidConfig(format!("unknown argument {}", arg))),
+            }
+        }
+        Ok(cli)
+    }
+}
+
+/// The merged configuration and the document it was built from.
+pub struct LayeredConfig {
+    pub config: Config,
+    /// The merged and interpolated document, including secrets.
+    pub document: toml::Value,
+    /// The files that were found and merged, in order.
+    pub files: Vec<String>,
+    /// Problems that did not stop loading, to be logged once logging is set up.
+    pub warnings: Vec<String>,
+}
+
+impl LayeredConfig {
+    /// Loads every layer. `env` holds the environment variables, which are used both for
+    /// `SOURCEWATCH_*` overrides and for `${VAR}` interpolation.
+    pub fn load(cli: &CliArgs, env: &[(String, String)]) -> Result<LayeredConfig, ConfigError> {
+        let lookup = |name: &str| env.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone());
+        let mut document = toml::Value::Table(toml::Table::new());
+        let mut files = Vec::new();
+
+        for (file, required) in candidate_files(cli, env) {
+            if !Path::new(&file).exists() {
+                if required {
+                    return Err(ConfigError::InvalidConfig(format!("configuration file {} not found", file)));
+                }
+                continue;
+            }
+            let content = std::fs::read_to_string(&file)?;
+            let layer: toml::Value = toml::from_str(&content)?;
+            merge_values(&mut document, layer);
+            files.push(file);
+        }
+
+        let mut warnings = Vec::new();
+        let mut overrides = Vec::new();
+        for (key, value) in env_overrides(env) {
+            if SECTIONS.contains(&key[0].as_str()) {
+                overrides.push((key, value));
+            } else {
+                warnings.push(format!("Ignoring {}{}: unknown setting {}", ENV_PREFIX, key.join("__").to_uppercase(), key.join(".")));
+            }
+        }
+        overrides.extend(cli.overrides.iter().map(|(key, value)| (key.split('.').map(String::from).collect(), value.clone())));
+        for (key, value) in &overrides {
+            set_path(&mut document, key, override_value(key, value))?;
+        }
+
+        interpolate_value(&mut document, &lookup)?;
+        let config = Config::from_document(document.clone())?;
+        Ok(LayeredConfig { config, document, files, warnings })
+    }
+
+    /// The effective configuration as TOML, with passwords and URL credentials replaced by `***`.
+    pub fn redacted(&self) -> String {
+        let mut document = self.document.clone();
+        redact_value(&mut document);
+        toml::to_string_pretty(&document).unwrap_or_else(|e| format!("# {}", e))
+    }
+}
+
+/// Reads an override with `parse_scalar`, except where the setting holds a string, e.g. a
+/// numeric password.
+fn override_value(path: &[String], value: &str) -> toml::Value {
+    match parse_scalar(value) {
+        toml::Value::String(value) => toml::Value::String(value),
+        _ if expects_string(path) => toml::Value::String(value.to_string()),
+        parsed => parsed,
+    }
+}
+
+/// Whether the setting at `path` is one of `STRING_SETTINGS`.
+fn expects_string(path: &[String]) -> bool {
+    let mut name: Vec<&str> = path.iter().map(String::as_str).filter(|segment| segment.parse::<usize>().is_err()).collect();
+    if name.first() == Some(&"databases") && name.len() > 1 {
+        name[1] = "*";
+    }
+    STRING_SETTINGS.contains(&name.join(".").as_str())
+}
+
+/// The files to load and whether each is required. Files named explicitly are required; the
+/// default `config.toml` and profile files are optional.
+pub fn candidate_files(cli: &CliArgs, env: &[(String, String)]) -> Vec<(String, bool)> {
+    let env_var = |name: &str| env.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone());
+    let explicit: Vec<String> = if !cli.config_files.is_empty() {
+        cli.config_files.clone()
+    } else {
+        env_var("SOURCEWATCH_CONFIG")
+            .map(|files| files.split(',').map(|file| file.trim().to_string()).filter(|file| !file.is_empty()).collect())
+            .unwrap_or_default()
+    };
+    let profile = cli.profile.clone().or_else(|| env_var("SOURCEWATCH_PROFILE")).filter(|profile| !profile.is_empty());
+
+    let bases = if explicit.is_empty() {
+        vec![(DEFAULT_CONFIG_FILE.to_string(), false)]
+    } else {
+        explicit.into_iter().map(|file| (file, true)).collect()
+    };
+
+    let mut files = Vec::new();
+    for (base, required) in bases {
+        let profile_file = profile.as_ref().map(|profile| profile_file(&base, profile));
+        files.push((base, required));
+        files.extend(profile_file.map(|file| (file, false)));
+    }
+    files
+}
+
+/// `config.toml` with profile `prod` is `config.prod.toml`.
+pub fn profile_file(base: &str, profile: &str) -> String {
+    match base.strip_suffix(".toml") {
+        Some(stem) => format!("{}.{}.toml", stem, profile),
+        None => format!("{}.{}", base, profile),
+    }
+}
+
+/// Merges `overlay` into `base`: tables key by key, anything else replaced.
+pub fn merge_values(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// The `SOURCEWATCH_*` overrides as key paths, e.g. `SOURCEWATCH_SERVER__LOG_LEVEL` is
+/// `["server", "log_level"]`. `SOURCEWATCH_BIND` and `SOURCEWATCH_LOG_LEVEL` are shorthands for
+/// the server settings, and `SOURCEWATCH_CONFIG` and `SOURCEWATCH_PROFILE` select files.
+pub fn env_overrides(env: &[(String, String)]) -> Vec<(Vec<String>, String)> {
+    let mut overrides: Vec<(Vec<String>, String)> = env.iter()
+        .filter_map(|(key, value)| {
+            let name = key.strip_prefix(ENV_PREFIX)?;
+            let path = match name {
+                "CONFIG" | "PROFILE" | "" => return None,
+                "BIND" => vec!["server".to_string(), "bind".to_string()],
+                "LOG_LEVEL" => vec!["server".to_string(), "log_level".to_string()],
+                name => name.split("__").map(|segment| segment.to_lowercase()).collect(),
+            };
+            Some((path, value.clone()))
+        })
+        .collect();
+    // The environment is unordered; apply shorter paths first so nested overrides win
+    overrides.sort_by(|a, b| a.0.len().cmp(&b.0.len()).then_with(|| a.0.cmp(&b.0)));
+    overrides
+}
+
+/// Reads an override as a TOML boolean, number or inline array/table, falling back to a string.
+pub fn parse_scalar(value: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", value)).ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()))
+}
+
+/// Sets the value at `path`, creating missing tables. Numeric segments index existing arrays;
+/// index 0 of a table is the table itself, as a single source may be written as a table.
+pub fn set_path(document: &mut toml::Value, path: &[String], value: toml::Value) -> Result<(), ConfigError> {
+    let Some((last, parents)) = path.split_last() else {
+        return Err(ConfigError::InvalidConfig("empty setting name".to_string()));
+    };
+
+    let mut current = document;
+    for segment in parents {
+        current = child(current, segment, path)?;
+    }
+    match current {
+        toml::Value::Table(table) => {
+            table.insert(last.clone(), value);
+        }
+        toml::Value::Array(values) => {
+            let slot = last.parse::<usize>().ok().and_then(|index| values.get_mut(index))
+                .ok_or_else(|| invalid_path(path))?;
+            *slot = value;
+        }
+        _ => return Err(invalid_path(path)),
+    }
+    Ok(())
+}
+
+fn child<'a>(value: &'a mut toml::Value, segment: &str, path: &[String]) -> Result<&'a mut toml::Value, ConfigError> {
+    match value {
+        toml::Value::Table(_) if segment == "0" => Ok(value),
+        toml::Value::Table(_) if segment.parse::<usize>().is_ok() => Err(invalid_path(path)),
+        toml::Value::Table(table) => Ok(table.entry(segment.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))),
+        toml::Value::Array(values) => segment.parse::<usize>().ok()
+            .and_then(|index| values.get_mut(index))
+            .ok_or_else(|| invalid_path(path)),
+        _ => Err(invalid_path(path)),
+    }
+}
+
+fn invalid_path(path: &[String]) -> ConfigError {
+    ConfigError::InvalidConfig(format!("cannot set {}", path.join(".")))
+}
+
+/// Replaces `password` values and the credentials in `url` values with `***`.
+fn redact_value(value: &mut toml::Value) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table.iter_mut() {
+                match (key.as_str(), &mut *value) {
+                    ("password", toml::Value::String(password)) => *password = REDACTED.to_string(),
+                    ("url", toml::Value::String(url)) => *url = redact_url(url),
+                    _ => redact_value(value),
+                }
+            }
+        }
+        toml::Value::Array(values) => values.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
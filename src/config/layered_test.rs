@@ -0,0 +1,153 @@
+#[cfg(test)]
+mod tests {
+    use crate::config::config::ConfigError;
+    use crate::config::layered::{env_overrides, parse_scalar, profile_file, CliArgs, CliCommand, LayeredConfig};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+
+    fn write_config(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, content).expect("Failed to write test config");
+        path.display().to_string()
+    }
+
+    #[test]
+    fn test_parse_cli_args() {
+        let cli = CliArgs::parse(args(&[
+            "print-config", "-c", "base.toml", "--config=extra.toml", "--profile", "prod",
+            "--bind", "127.0.0.1:7000", "--set", "databases.redis.0.port=6380",
+        ])).unwrap();
+
+        assert_eq!(cli.command, CliCommand::PrintConfig);
+        assert_eq!(cli.config_files, vec!["base.toml", "extra.toml"]);
+        assert_eq!(cli.profile.as_deref(), Some("prod"));
+        assert_eq!(cli.overrides, vec![
+            ("server.bind".to_string(), "127.0.0.1:7000".to_string()),
+            ("databases.redis.0.port".to_string(), "6380".to_string()),
+        ]);
+
+        assert!(matches!(CliArgs::parse(args(&["--bind"])), Err(ConfigError::InvalidConfig(_))));
+        assert!(matches!(CliArgs::parse(args(&["--verbose"])), Err(ConfigError::InvalidConfig(_))));
+        assert_eq!(profile_file("conf/config.toml", "prod"), "conf/config.prod.toml");
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let overrides = env_overrides(&env(&[
+            ("SOURCEWATCH_DATABASES__REDIS__0__PORT", "6380"),
+            ("SOURCEWATCH_LOG_LEVEL", "debug"),
+            ("SOURCEWATCH_PROFILE", "prod"),
+            ("PATH", "/usr/bin"),
+        ]));
+
+        assert_eq!(overrides, vec![
+            (vec!["server".to_string(), "log_level".to_string()], "debug".to_string()),
+            (vec!["databases".to_string(), "redis".to_string(), "0".to_string(), "port".to_string()], "6380".to_string()),
+        ]);
+        assert_eq!(parse_scalar("6380"), toml::Value::Integer(6380));
+        assert_eq!(parse_scalar("true"), toml::Value::Boolean(true));
+        assert_eq!(parse_scalar("0.0.0.0:6262"), toml::Value::String("0.0.0.0:6262".to_string()));
+    }
+
+    #[test]
+    fn test_layers_are_merged_in_order() {
+        let base = write_config("source_watch_layered.toml", r#"
+            [server]
+            log_level = "warn"
+
+            [[databases.redis]]
+            name = "cache"
+            host = "localhost"
+            port = 6379
+        "#);
+        write_config("source_watch_layered.prod.toml", r#"
+            [server]
+            bind = "0.0.0.0:8080"
+
+            [[databases.redis]]
+            name = "cache"
+            host = "cache.prod"
+            password = "${REDIS_PASSWORD}"
+        "#);
+
+        let cli = CliArgs::parse(args(&["--config", &base, "--profile", "prod", "--bind", "127.0.0.1:9000"])).unwrap();
+        let layered = LayeredConfig::load(&cli, &env(&[
+            ("SOURCEWATCH_DATABASES__REDIS__0__PORT", "6380"),
+            ("SOURCEWATCH_LOG_LEVEL", "debug"),
+            ("REDIS_PASSWORD", "s3cret"),
+        ])).expect("Failed to load layered config");
+
+        assert_eq!(layered.files.len(), 2, "The profile file should be loaded after the base file");
+        assert_eq!(layered.config.server.bind, "127.0.0.1:9000", "Flags should override files");
+        assert_eq!(layered.config.server.log_level, "debug", "The environment should override files");
+        let redis = &layered.config.databases.redis[0];
+        assert_eq!(redis.host.as_deref(), Some("cache.prod"));
+        assert_eq!(redis.port, Some(6380));
+        assert_eq!(redis.password.as_deref(), Some("s3cret"));
+
+        let printed = layered.redacted();
+        assert!(printed.contains("cache.prod"));
+        assert!(!printed.contains("s3cret"), "Secrets should be redacted: {}", printed);
+    }
+
+    #[test]
+    fn test_defaults_without_files() {
+        let missing = std::env::temp_dir().join("source_watch_missing.toml").display().to_string();
+        let cli = CliArgs::parse(args(&["--config", &missing])).unwrap();
+        assert!(matches!(LayeredConfig::load(&cli, &[]), Err(ConfigError::InvalidConfig(_))), "Explicit files are required");
+
+        let empty = write_config("source_watch_empty.toml", "");
+        let layered = LayeredConfig::load(&CliArgs::default(), &env(&[("SOURCEWATCH_CONFIG", &empty)])).unwrap();
+        assert_eq!(layered.config.server.bind, "0.0.0.0:6262");
+        assert_eq!(layered.config.server.log_level, "info");
+        assert!(layered.config.databases.source_names().is_empty());
+    }
+
+    #[test]
+    fn test_overrides_keep_strings_where_settings_expect_them() {
+        let base = write_config("source_watch_overrides.toml", r#"
+            [databases.redis]
+            name = "cache"
+            host = "localhost"
+        "#);
+        let cli = CliArgs::parse(args(&[
+            "--config", &base, "--set", "databases.redis.0.name=true", "--set", "databases.redis.0.tls.server_name=10.0",
+        ])).unwrap();
+        let layered = LayeredConfig::load(&cli, &env(&[
+            ("SOURCEWATCH_DATABASES__REDIS__0__PASSWORD", "123456"),
+            ("SOURCEWATCH_DATABASES__REDIS__0__PORT", "6380"),
+        ])).expect("Numeric and boolean strings should be accepted");
+
+        let redis = &layered.config.databases.redis[0];
+        assert_eq!(redis.password.as_deref(), Some("123456"));
+        assert_eq!(redis.name, "true");
+        assert_eq!(redis.options.tls.as_ref().unwrap().server_name.as_deref(), Some("10.0"), "Strings should be kept as given");
+        assert_eq!(redis.port, Some(6380), "Numbers should stay numbers where settings expect them");
+        assert!(layered.document["databases"]["redis"].get("0").is_none(), "Index 0 should address the single source table");
+
+        let cli = CliArgs::parse(args(&["--config", &base, "--set", "databases.redis.1.port=6380"])).unwrap();
+        assert!(matches!(LayeredConfig::load(&cli, &[]), Err(ConfigError::InvalidConfig(_))), "A single source has no second entry");
+        let cli = CliArgs::parse(args(&["--config", &base, "--set", "databases.redis.0.port=true"])).unwrap();
+        assert!(LayeredConfig::load(&cli, &[]).is_err(), "Values that are not strings should still be type checked");
+    }
+
+    #[test]
+    fn test_unknown_environment_settings_are_reported() {
+        let empty = write_config("source_watch_unknown_env.toml", "");
+        let layered = LayeredConfig::load(&CliArgs::default(), &env(&[
+            ("SOURCEWATCH_CONFIG", &empty),
+            ("SOURCEWATCH_TOKEN", "abc"),
+            ("SOURCEWATCH_SERVER__BIND", "127.0.0.1:7000"),
+        ])).expect("Unknown environment settings should not stop loading");
+
+        assert_eq!(layered.config.server.bind, "127.0.0.1:7000");
+        assert_eq!(layered.warnings.len(), 1);
+        assert!(layered.warnings[0].contains("SOURCEWATCH_TOKEN"), "{}", layered.warnings[0]);
+    }
+}
@@ -1,2 +1,5 @@
-mod config;
+#[allow(clippy::module_inception)]
+pub mod config;
 mod config_test;
+pub mod layered;
+mod layered_test;
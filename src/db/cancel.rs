@@ -0,0 +1,62 @@
+//! Server-side cancellation of queries nobody waits for anymore, e.g. because their deadline
+//! passed. While a query runs its pool holds a `CancelGuard`; if the query future is dropped
+//! before the guard is disarmed, the backend's cancel request is sent in the background.
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use futures::future::BoxFuture;
+use log::warn;
+use crate::utils::errors::DatabaseError;
+
+static NEXT_TAG: AtomicU64 = AtomicU64::new(0);
+
+/// A process-unique tag attached to a query, e.g. as a MongoDB `comment`, to find it on the server.
+pub fn query_tag() -> String {
+    format!("source_watch-{}-{}", std::process::id(), NEXT_TAG.fetch_add(1, Ordering::Relaxed))
+}
+
+pub struct CancelGuard {
+    cancel: Option<BoxFuture<'static, Result<(), DatabaseError>>>,
+}
+
+impl CancelGuard {
+    pub fn new(cancel: impl Future<Output = Result<(), DatabaseError>> + Send + 'static) -> Self {
+        CancelGuard { cancel: Some(Box::pin(cancel)) }
+    }
+
+    /// Marks the query as finished on the server.
+    pub fn disarm(mut self) {
+        self.cancel = None;
+    }
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        if let Some(cancel) = self.cancel.take() {
+            spawn_cancel(cancel);
+        }
+    }
+}
+
+/// Sends a cancel request in the background, logging failures.
+pub fn spawn_cancel(cancel: impl Future<Output = Result<(), DatabaseError>> + Send + 'static) {
+    match tokio::runtime::Handle::try_current() {
+        Ok(runtime) => {
+            runtime.spawn(async move {
+                if let Err(e) = cancel.await {
+                    warn!("Could not cancel query: {}", e);
+                }
+            });
+        }
+        Err(_) => warn!("Could not cancel query: no runtime"),
+    }
+}
+
+/// Runs `query`, dropping it, and so cancelling it, if it is still running after `timeout`.
+pub async fn with_timeout<T>(timeout: Option<Duration>, query: impl Future<Output = Result<T, DatabaseError>>) -> Result<T, DatabaseError> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, query).await
+            .unwrap_or_else(|_| Err(DatabaseError::Timeout(timeout))),
+        None => query.await,
+    }
+}
@@ -0,0 +1,40 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::cancel::{query_tag, with_timeout, CancelGuard};
+    use crate::utils::errors::DatabaseError;
+    use std::time::Duration;
+    use tokio::sync::oneshot;
+
+    /// A query that holds a guard until it completes after `duration`.
+    async fn query(duration: Duration, cancelled: oneshot::Sender<()>) -> Result<&'static str, DatabaseError> {
+        let guard = CancelGuard::new(async move {
+            let _ = cancelled.send(());
+            Ok(())
+        });
+        tokio::time::sleep(duration).await;
+        guard.disarm();
+        Ok("done")
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_queries_are_cancelled() {
+        let (cancelled_tx, cancelled_rx) = oneshot::channel();
+        let result = with_timeout(Some(Duration::from_millis(20)), query(Duration::from_secs(5), cancelled_tx)).await;
+        assert!(matches!(result, Err(DatabaseError::Timeout(timeout)) if timeout == Duration::from_millis(20)));
+        tokio::time::timeout(Duration::from_secs(1), cancelled_rx).await
+            .expect("The cancel request should be sent")
+            .expect("The cancel request should run");
+    }
+
+    #[tokio::test]
+    async fn test_finished_queries_are_not_cancelled() {
+        let (cancelled_tx, cancelled_rx) = oneshot::channel();
+        let result = with_timeout(Some(Duration::from_secs(5)), query(Duration::from_millis(1), cancelled_tx)).await;
+        assert_eq!(result.unwrap(), "done");
+        assert!(cancelled_rx.await.is_err(), "A disarmed guard should drop its cancel request");
+
+        let (cancelled_tx, _cancelled_rx) = oneshot::channel();
+        assert_eq!(with_timeout(None, query(Duration::from_millis(1), cancelled_tx)).await.unwrap(), "done");
+        assert_ne!(query_tag(), query_tag());
+    }
+}
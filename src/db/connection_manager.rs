@@ -1,31 +1,417 @@
+//! A registry of named database connections. Unlike `DatabaseManager`, which holds a single
+//! pool per `DatabaseType`, connections are keyed by a user-chosen name, so several databases
+//! of the same type (e.g. `prod-pg` and `staging-pg`) can be open at once.
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use crate::db::Database;
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::Utc;
+use futures::StreamExt;
+use log::warn;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use crate::db::{Database, DatabasePool};
+use crate::db::native_query::NativeQuery;
+use crate::db::query_builder::{DatabaseType, QueryBuilder};
+use crate::db::result_set::ResultSet;
+use crate::db::row_stream::RowStream;
+use crate::utils::errors::DatabaseError;
 
+/// How often a drain checks whether the queries in flight have finished.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A connection that can both be queried and introspected.
+pub trait DatabaseConnection: Database + DatabasePool {}
+
+impl<T: Database + DatabasePool> DatabaseConnection for T {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionStatus {
+    Connecting,
+    Connected,
+    /// The connection is open but not healthy, e.g. slow or partially failing.
+    Degraded,
+    Disconnecting,
+    Disconnected,
+    /// Connecting failed or the connection was lost; see `last_error`.
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConnectionInfo {
+    pub name: String,
+    pub database_type: DatabaseType,
+    pub status: ConnectionStatus,
+    pub last_error: Option<String>,
+    /// RFC 3339 timestamps of registration and of the last successful connect.
+    pub created_at: String,
+    pub connected_at: Option<String>,
+    /// Round trip of the last successful health check, and when the last check ran.
+    pub latency_ms: Option<u64>,
+    pub last_checked_at: Option<String>,
+}
+
+/// Counts the queries running on a handle, so that it is only closed once they are done.
+#[derive(Clone, Default)]
+pub struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    /// Counts a query until the returned guard is dropped.
+    pub fn start(&self) -> InFlightGuard {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(Arc::clone(&self.0))
+    }
+
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+pub struct InFlightGuard(Arc<AtomicUsize>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A registered handle that counts the queries running on it. This is the form in which
+/// connections are given to the `DatabaseManager`.
+#[derive(Clone)]
+pub struct TrackedConnection {
+    handle: Arc<dyn DatabaseConnection>,
+    in_flight: InFlight,
+}
+
+#[async_trait]
+impl DatabasePool for TrackedConnection {
+    async fn execute(&self, query: &str, params: Vec<Value>) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        let _guard = self.in_flight.start();
+        self.handle.execute(query, params).await
+    }
+
+    async fn execute_builder(&self, builder: &QueryBuilder) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        let _guard = self.in_flight.start();
+        self.handle.execute_builder(builder).await
+    }
+
+    async fn execute_native(&self, query: &NativeQuery) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        let _guard = self.in_flight.start();
+        self.handle.execute_native(query).await
+    }
+
+    async fn query_builder(&self, builder: &QueryBuilder) -> Result<ResultSet, DatabaseError> {
+        let _guard = self.in_flight.start();
+        self.handle.query_builder(builder).await
+    }
+
+    async fn query_native(&self, query: &NativeQuery) -> Result<ResultSet, DatabaseError> {
+        let _guard = self.in_flight.start();
+        self.handle.query_native(query).await
+    }
+
+    fn stream_native<'a>(&'a self, query: &'a NativeQuery, batch_size: usize) -> RowStream<'a> {
+        // Counted until the stream is dropped
+        let guard = self.in_flight.start();
+        self.handle.stream_native(query, batch_size).map(move |batch| {
+            let _ = &guard;
+            batch
+        }).boxed()
+    }
+}
+
+struct ConnectionEntry {
+    info: ConnectionInfo,
+    handle: Option<Arc<dyn DatabaseConnection>>,
+    in_flight: InFlight,
+    /// The handle is also used outside the registry, e.g. for pub/sub, so it is not
+    /// disconnected when it is replaced or removed.
+    shared: bool,
+}
+
+impl ConnectionEntry {
+    fn new(info: ConnectionInfo, handle: Option<Arc<dyn DatabaseConnection>>) -> Self {
+        ConnectionEntry { info, handle, in_flight: InFlight::default(), shared: false }
+    }
+}
+
+/// Waits up to `timeout` for the queries counted by `in_flight` to finish, then disconnects
+/// `handle` unless it is shared.
+async fn close_handle(handle: Arc<dyn DatabaseConnection>, in_flight: InFlight, shared: bool, timeout: Duration) -> Result<(), DatabaseError> {
+    let deadline = Instant::now() + timeout;
+    while in_flight.count() > 0 && Instant::now() < deadline {
+        tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+    }
+    if shared {
+        return Ok(());
+    }
+    handle.disconnect().await
+}
+
+/// Closes a handle that is no longer registered, in the background.
+fn close_in_background(name: &str, handle: Option<Arc<dyn DatabaseConnection>>, in_flight: InFlight, shared: bool, timeout: Duration) -> JoinHandle<()> {
+    let name = name.to_string();
+    tokio::spawn(async move {
+        if let Some(handle) = handle {
+            if let Err(e) = close_handle(handle, in_flight, shared, timeout).await {
+                warn!("Error while closing {}: {}", name, e);
+            }
+        }
+    })
+}
+
+#[derive(Default)]
 pub struct ConnectionManager {
-    connections: Mutex<HashMap<String, Box<dyn Database>>>,
+    connections: RwLock<HashMap<String, ConnectionEntry>>,
 }
 
 impl ConnectionManager {
     pub fn new() -> Self {
-        ConnectionManager {
-            connections: Mutex::new(HashMap::new()),
+        Self::default()
+    }
+
+    fn validate_name(name: &str) -> Result<(), DatabaseError> {
+        if name.trim().is_empty() {
+            return Err(DatabaseError::InvalidQuery("Connection names cannot be empty".to_string()));
+        }
+        Ok(())
+    }
+
+    fn already_exists(name: &str) -> DatabaseError {
+        DatabaseError::ConnectionError(format!("A connection named {} already exists", name))
+    }
+
+    fn not_found(name: &str) -> DatabaseError {
+        DatabaseError::DatabaseNotFound(format!("No connection named {}", name))
+    }
+
+    /// Registers an already open connection.
+    pub async fn add_connection(&self, name: &str, database_type: DatabaseType, handle: Arc<dyn DatabaseConnection>) -> Result<(), DatabaseError> {
+        Self::validate_name(name)?;
+        let mut connections = self.connections.write().await;
+        if connections.contains_key(name) {
+            return Err(Self::already_exists(name));
+        }
+
+        let now = Utc::now().to_rfc3339();
+        connections.insert(name.to_string(), ConnectionEntry::new(ConnectionInfo {
+            name: name.to_string(),
+            database_type,
+            status: ConnectionStatus::Connected,
+            last_error: None,
+            created_at: now.clone(),
+            connected_at: Some(now),
+            latency_ms: None,
+            last_checked_at: None,
+        }, Some(handle)));
+        Ok(())
+    }
+
+    /// Registers a connection that could not be opened, so it is listed with a `Failed` status.
+    pub async fn add_failed_connection(&self, name: &str, database_type: DatabaseType, error: String) -> Result<(), DatabaseError> {
+        Self::validate_name(name)?;
+        let mut connections = self.connections.write().await;
+        if connections.contains_key(name) {
+            return Err(Self::already_exists(name));
+        }
+
+        connections.insert(name.to_string(), ConnectionEntry::new(ConnectionInfo {
+            name: name.to_string(),
+            database_type,
+            status: ConnectionStatus::Failed,
+            last_error: Some(error),
+            created_at: Utc::now().to_rfc3339(),
+            connected_at: None,
+            latency_ms: None,
+            last_checked_at: None,
+        }, None));
+        Ok(())
+    }
+
+    /// Opens a connection of type `D` and registers it. The name is reserved while connecting;
+    /// if connecting fails the connection stays listed with a `Failed` status. A connection
+    /// removed while connecting is closed as soon as it opens.
+    pub async fn connect<D>(&self, name: &str, database_type: DatabaseType, connection_string: &str) -> Result<Arc<dyn DatabaseConnection>, DatabaseError>
+    where
+        D: Database + DatabasePool + 'static,
+    {
+        Self::validate_name(name)?;
+        {
+            let mut connections = self.connections.write().await;
+            match connections.get(name) {
+                Some(entry) if entry.handle.is_some() || entry.info.status == ConnectionStatus::Connecting => {
+                    return Err(Self::already_exists(name));
+                }
+                _ => {}
+            }
+            connections.insert(name.to_string(), ConnectionEntry::new(ConnectionInfo {
+                name: name.to_string(),
+                database_type,
+                status: ConnectionStatus::Connecting,
+                last_error: None,
+                created_at: Utc::now().to_rfc3339(),
+                connected_at: None,
+                latency_ms: None,
+                last_checked_at: None,
+            }, None));
+        }
+
+        let result = D::connect(connection_string).await
+            .map(|db| Arc::new(db) as Arc<dyn DatabaseConnection>);
+
+        let mut connections = self.connections.write().await;
+        let entry = connections.get_mut(name)
+            .filter(|entry| entry.info.status == ConnectionStatus::Connecting && entry.handle.is_none());
+        let Some(entry) = entry else {
+            // Removed while connecting: the registry no longer owns the handle, so close it here
+            drop(connections);
+            if let Ok(handle) = result {
+                handle.disconnect().await?;
+            }
+            return Err(Self::not_found(name));
+        };
+        match &result {
+            Ok(handle) => {
+                entry.handle = Some(Arc::clone(handle));
+                entry.info.status = ConnectionStatus::Connected;
+                entry.info.last_error = None;
+                entry.info.connected_at = Some(Utc::now().to_rfc3339());
+            }
+            Err(e) => {
+                entry.info.status = ConnectionStatus::Failed;
+                entry.info.last_error = Some(e.to_string());
+            }
+        }
+        result
+    }
+
+    /// Returns the handle of an open connection. Connections being drained are not handed out.
+    pub async fn get_connection(&self, name: &str) -> Option<Arc<dyn DatabaseConnection>> {
+        self.connections.read().await.get(name)
+            .filter(|entry| entry.info.status != ConnectionStatus::Disconnecting)
+            .and_then(|entry| entry.handle.clone())
+    }
+
+    /// Like `get_connection`, counting the queries run through the returned handle so that
+    /// replacing or removing the connection waits for them.
+    pub async fn tracked_connection(&self, name: &str) -> Option<TrackedConnection> {
+        self.connections.read().await.get(name)
+            .filter(|entry| entry.info.status != ConnectionStatus::Disconnecting)
+            .and_then(|entry| Some(TrackedConnection { handle: entry.handle.clone()?, in_flight: entry.in_flight.clone() }))
+    }
+
+    /// Marks the current handle of a connection as used outside the registry, e.g. by pub/sub
+    /// or monitoring, so it stays open when the connection is replaced or removed.
+    pub async fn share_handle(&self, name: &str) -> Result<(), DatabaseError> {
+        let mut connections = self.connections.write().await;
+        connections.get_mut(name).ok_or_else(|| Self::not_found(name))?.shared = true;
+        Ok(())
+    }
+
+    pub async fn connection_info(&self, name: &str) -> Option<ConnectionInfo> {
+        self.connections.read().await.get(name).map(|entry| entry.info.clone())
+    }
+
+    /// Lists all registered connections, sorted by name.
+    pub async fn list_connections(&self) -> Vec<ConnectionInfo> {
+        let mut connections: Vec<ConnectionInfo> = self.connections.read().await.values()
+            .map(|entry| entry.info.clone())
+            .collect();
+        connections.sort_by(|a, b| a.name.cmp(&b.name));
+        connections
+    }
+
+    /// Updates the status of a connection, e.g. from a health check.
+    pub async fn set_status(&self, name: &str, status: ConnectionStatus, last_error: Option<String>) -> Result<(), DatabaseError> {
+        let mut connections = self.connections.write().await;
+        let entry = connections.get_mut(name).ok_or_else(|| Self::not_found(name))?;
+        entry.info.status = status;
+        entry.info.last_error = last_error;
+        Ok(())
+    }
+
+    /// Records the result of a health check. Returns whether the status changed. Connections
+    /// that are connecting or being drained keep their status.
+    pub async fn record_health(&self, name: &str, status: ConnectionStatus, latency: Option<Duration>, last_error: Option<String>) -> Result<bool, DatabaseError> {
+        let mut connections = self.connections.write().await;
+        let entry = connections.get_mut(name).ok_or_else(|| Self::not_found(name))?;
+        if matches!(entry.info.status, ConnectionStatus::Connecting | ConnectionStatus::Disconnecting) {
+            return Ok(false);
+        }
+
+        let changed = entry.info.status != status;
+        entry.info.status = status;
+        entry.info.last_error = last_error;
+        entry.info.last_checked_at = Some(Utc::now().to_rfc3339());
+        if let Some(latency) = latency {
+            entry.info.latency_ms = Some(latency.as_millis() as u64);
         }
+        Ok(changed)
     }
 
-    pub async fn add_connection(&self, name: &str, db: Box<dyn Database>) {
-        let mut connections = self.connections.lock().await;
-        connections.insert(name.to_string(), db);
+    /// Swaps in a newly opened handle, e.g. after reconnecting a failed connection or after its
+    /// settings changed. The status is left to the next health check. The old handle is closed
+    /// in the background once its queries finish, or after `drain_timeout`; the returned task
+    /// completes when it is closed.
+    pub async fn replace_handle(&self, name: &str, handle: Arc<dyn DatabaseConnection>, drain_timeout: Duration) -> Result<JoinHandle<()>, DatabaseError> {
+        let mut connections = self.connections.write().await;
+        let entry = connections.get_mut(name)
+            .filter(|entry| entry.info.status != ConnectionStatus::Disconnecting)
+            .ok_or_else(|| Self::not_found(name))?;
+        let old = entry.handle.replace(handle);
+        let in_flight = std::mem::take(&mut entry.in_flight);
+        let shared = std::mem::take(&mut entry.shared);
+        entry.info.connected_at = Some(Utc::now().to_rfc3339());
+        Ok(close_in_background(name, old, in_flight, shared, drain_timeout))
     }
 
-    pub async fn get_connection(&self, name: &str) -> Option<Box<dyn Database>> {
-        let connections = self.connections.lock().await;
-        connections.get(name).cloned()
+    pub async fn rename_connection(&self, name: &str, new_name: &str) -> Result<(), DatabaseError> {
+        Self::validate_name(new_name)?;
+        let mut connections = self.connections.write().await;
+        if name == new_name {
+            return if connections.contains_key(name) { Ok(()) } else { Err(Self::not_found(name)) };
+        }
+        if connections.contains_key(new_name) {
+            return Err(Self::already_exists(new_name));
+        }
+        let mut entry = connections.remove(name).ok_or_else(|| Self::not_found(name))?;
+        entry.info.name = new_name.to_string();
+        connections.insert(new_name.to_string(), entry);
+        Ok(())
     }
 
-    pub async fn remove_connection(&self, name: &str) {
-        let mut connections = self.connections.lock().await;
-        connections.remove(name);
+    /// Disconnects and unregisters a connection. Queries already running on it are not waited for.
+    pub async fn remove_connection(&self, name: &str) -> Result<(), DatabaseError> {
+        self.drain_connection(name, Duration::ZERO).await
     }
-}
\ No newline at end of file
+
+    /// Stops handing out a connection, waits up to `timeout` for the queries running on it to
+    /// finish, then disconnects and unregisters it.
+    pub async fn drain_connection(&self, name: &str, timeout: Duration) -> Result<(), DatabaseError> {
+        let (handle, in_flight, shared) = {
+            let mut connections = self.connections.write().await;
+            let entry = connections.get_mut(name).ok_or_else(|| Self::not_found(name))?;
+            entry.info.status = ConnectionStatus::Disconnecting;
+            (entry.handle.clone(), entry.in_flight.clone(), entry.shared)
+        };
+
+        let result = match handle {
+            Some(handle) => close_handle(handle, in_flight, shared, timeout).await,
+            None => Ok(()),
+        };
+        self.connections.write().await.remove(name);
+        result
+    }
+
+    /// Unregisters a connection right away and closes it in the background once its queries
+    /// finish, or after `timeout`. The returned task completes when it is closed.
+    pub async fn retire_connection(&self, name: &str, timeout: Duration) -> Result<JoinHandle<()>, DatabaseError> {
+        let entry = self.connections.write().await.remove(name).ok_or_else(|| Self::not_found(name))?;
+        Ok(close_in_background(name, entry.handle, entry.in_flight, entry.shared, timeout))
+    }
+}
@@ -0,0 +1,191 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::connection_manager::{ConnectionManager, ConnectionStatus};
+    use crate::db::query_builder::DatabaseType;
+    use crate::db::{Database, DatabasePool};
+    use crate::utils::errors::DatabaseError;
+    use async_trait::async_trait;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// The urls of the fake databases that were disconnected.
+    static DISCONNECTED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct FakeDatabase {
+        url: String,
+    }
+
+    #[async_trait]
+    impl Database for FakeDatabase {
+        async fn connect(connection_string: &str) -> Result<Self, DatabaseError> {
+            if connection_string.starts_with("slow://") {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                Ok(FakeDatabase { url: connection_string.to_string() })
+            } else if connection_string.starts_with("fake://") {
+                Ok(FakeDatabase { url: connection_string.to_string() })
+            } else {
+                Err(DatabaseError::ConnectionError("unreachable".to_string()))
+            }
+        }
+
+        async fn disconnect(&self) -> Result<(), DatabaseError> {
+            DISCONNECTED.lock().unwrap().push(self.url.clone());
+            Ok(())
+        }
+
+        async fn execute_query(&self, _query: &str) -> Result<Vec<Value>, DatabaseError> {
+            Ok(vec![json!(self.url)])
+        }
+
+        async fn list_databases(&self) -> Result<Vec<String>, DatabaseError> {
+            Ok(vec!["main".to_string()])
+        }
+
+        async fn list_collections(&self, _database: &str) -> Result<Vec<String>, DatabaseError> {
+            Ok(Vec::new())
+        }
+
+        async fn get_schema(&self, _database: &str, _collection: &str) -> Result<Value, DatabaseError> {
+            Ok(Value::Null)
+        }
+    }
+
+    #[async_trait]
+    impl DatabasePool for FakeDatabase {
+        async fn execute(&self, _query: &str, _params: Vec<Value>) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+            if self.url.starts_with("slow://") {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+            }
+            Ok(vec![[("url".to_string(), json!(self.url))].into_iter().collect()])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_named_connections_of_the_same_type() {
+        let manager = ConnectionManager::new();
+        manager.connect::<FakeDatabase>("prod-pg", DatabaseType::PostgreSQL, "fake://prod").await.unwrap();
+        manager.connect::<FakeDatabase>("staging-pg", DatabaseType::PostgreSQL, "fake://staging").await.unwrap();
+
+        let staging = manager.get_connection("staging-pg").await.unwrap();
+        assert_eq!(staging.execute("", Vec::new()).await.unwrap()[0]["url"], json!("fake://staging"));
+        assert_eq!(staging.list_databases().await.unwrap(), vec!["main".to_string()]);
+
+        let names: Vec<String> = manager.list_connections().await.into_iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["prod-pg", "staging-pg"]);
+        assert!(manager.connect::<FakeDatabase>("prod-pg", DatabaseType::PostgreSQL, "fake://other").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failed_connection_is_listed_and_can_be_retried() {
+        let manager = ConnectionManager::new();
+        assert!(manager.connect::<FakeDatabase>("cache", DatabaseType::Redis, "bad://host").await.is_err());
+
+        let info = manager.connection_info("cache").await.unwrap();
+        assert_eq!(info.status, ConnectionStatus::Failed);
+        assert!(info.last_error.unwrap().contains("unreachable"));
+        assert!(manager.get_connection("cache").await.is_none());
+
+        manager.connect::<FakeDatabase>("cache", DatabaseType::Redis, "fake://cache").await.unwrap();
+        let info = manager.connection_info("cache").await.unwrap();
+        assert_eq!(info.status, ConnectionStatus::Connected);
+        assert!(info.connected_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_connection_removed_while_connecting_is_closed() {
+        let manager = Arc::new(ConnectionManager::new());
+        let connecting = tokio::spawn({
+            let manager = Arc::clone(&manager);
+            async move { manager.connect::<FakeDatabase>("orphan", DatabaseType::Redis, "slow://orphan").await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(manager.connection_info("orphan").await.unwrap().status, ConnectionStatus::Connecting);
+        manager.remove_connection("orphan").await.unwrap();
+
+        assert!(connecting.await.unwrap().is_err());
+        assert!(manager.connection_info("orphan").await.is_none());
+        assert!(DISCONNECTED.lock().unwrap().contains(&"slow://orphan".to_string()),
+            "The handle opened after the connection was removed should be disconnected");
+    }
+
+    #[tokio::test]
+    async fn test_rename_and_remove() {
+        let manager = ConnectionManager::new();
+        manager.add_connection("a", DatabaseType::MongoDB, Arc::new(FakeDatabase { url: "fake://a".to_string() })).await.unwrap();
+        manager.add_connection("b", DatabaseType::MongoDB, Arc::new(FakeDatabase { url: "fake://b".to_string() })).await.unwrap();
+
+        assert!(manager.rename_connection("a", "b").await.is_err());
+        manager.rename_connection("a", "c").await.unwrap();
+        assert!(manager.get_connection("a").await.is_none());
+        assert_eq!(manager.connection_info("c").await.unwrap().name, "c");
+
+        manager.remove_connection("c").await.unwrap();
+        assert!(manager.connection_info("c").await.is_none());
+        assert!(manager.remove_connection("c").await.is_err());
+        assert!(manager.add_connection(" ", DatabaseType::Redis, Arc::new(FakeDatabase { url: String::new() })).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_queries_in_flight() {
+        let manager = Arc::new(ConnectionManager::new());
+        manager.add_connection("pg", DatabaseType::PostgreSQL, Arc::new(FakeDatabase { url: "slow://pg".to_string() })).await.unwrap();
+        let tracked = manager.tracked_connection("pg").await.unwrap();
+        let query = tokio::spawn(async move { tracked.execute("", Vec::new()).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let draining = tokio::spawn({
+            let manager = Arc::clone(&manager);
+            async move { manager.drain_connection("pg", Duration::from_secs(5)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert!(!draining.is_finished(), "Draining should wait for the query in flight");
+        assert_eq!(manager.connection_info("pg").await.unwrap().status, ConnectionStatus::Disconnecting);
+        assert!(manager.get_connection("pg").await.is_none(), "Draining connections should not be handed out");
+
+        query.await.unwrap().unwrap();
+        draining.await.unwrap().unwrap();
+        assert!(manager.connection_info("pg").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_idle_handles_do_not_hold_up_a_drain() {
+        let manager = ConnectionManager::new();
+        manager.add_connection("cache", DatabaseType::Redis, Arc::new(FakeDatabase { url: "fake://idle".to_string() })).await.unwrap();
+        let _kept_for_pubsub = manager.get_connection("cache").await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(1), manager.drain_connection("cache", Duration::from_secs(30))).await
+            .expect("A handle that runs no query should not delay the drain")
+            .unwrap();
+        assert!(DISCONNECTED.lock().unwrap().contains(&"fake://idle".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_health_results_and_reconnect() {
+        let manager = ConnectionManager::new();
+        manager.add_connection("es", DatabaseType::Elasticsearch, Arc::new(FakeDatabase { url: "fake://old".to_string() })).await.unwrap();
+
+        assert!(!manager.record_health("es", ConnectionStatus::Connected, Some(Duration::from_millis(12)), None).await.unwrap());
+        assert!(manager.record_health("es", ConnectionStatus::Failed, None, Some("timed out".to_string())).await.unwrap());
+        let info = manager.connection_info("es").await.unwrap();
+        assert_eq!(info.latency_ms, Some(12), "The last known latency should be kept");
+        assert_eq!(info.last_error.as_deref(), Some("timed out"));
+        assert!(info.last_checked_at.is_some());
+
+        manager.share_handle("es").await.unwrap();
+        let closed = manager.replace_handle("es", Arc::new(FakeDatabase { url: "fake://new".to_string() }), Duration::from_secs(5)).await.unwrap();
+        closed.await.unwrap();
+        assert!(!DISCONNECTED.lock().unwrap().contains(&"fake://old".to_string()), "Shared handles should stay open");
+        let handle = manager.get_connection("es").await.unwrap();
+        assert_eq!(handle.execute("", Vec::new()).await.unwrap()[0]["url"], json!("fake://new"));
+        assert!(manager.record_health("es", ConnectionStatus::Connected, Some(Duration::from_millis(8)), None).await.unwrap());
+        assert_eq!(manager.connection_info("es").await.unwrap().last_error, None);
+        assert!(manager.replace_handle("missing", handle, Duration::ZERO).await.is_err());
+
+        manager.replace_handle("es", Arc::new(FakeDatabase { url: "fake://newer".to_string() }), Duration::ZERO).await.unwrap()
+            .await.unwrap();
+        assert!(DISCONNECTED.lock().unwrap().contains(&"fake://new".to_string()), "Replaced handles should be disconnected");
+    }
+}
@@ -0,0 +1,272 @@
+//! Bulk writes through the `_bulk` API and client side reindexing between indices.
+//! Operations are sent in batches; items rejected with `429 Too Many Requests` are retried
+//! with exponential backoff, other item failures are reported without failing the batch.
+use std::time::Duration;
+use elasticsearch::BulkParts;
+use elasticsearch::http::request::JsonBody;
+use futures::TryStreamExt;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use crate::db::elasticsearch_custom::{parse_refresh, read_response, transport_error, ElasticsearchPool};
+use crate::db::elasticsearch_results::hit_document;
+use crate::utils::errors::DatabaseError;
+
+pub const DEFAULT_BULK_BATCH_SIZE: usize = 500;
+const MAX_BULK_RETRIES: u32 = 5;
+const BULK_RETRY_DELAY: Duration = Duration::from_millis(500);
+const TOO_MANY_REQUESTS: u16 = 429;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAction {
+    Index,
+    Create,
+    Update,
+    Delete,
+}
+
+/// A single bulk item. `index` overrides the index passed to `bulk`; `document` is the source
+/// for `index`/`create`, the partial document for `update` and unused for `delete`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BulkOperation {
+    pub action: BulkAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub document: Value,
+}
+
+impl BulkOperation {
+    pub fn index(document: Value) -> Self {
+        BulkOperation { action: BulkAction::Index, index: None, id: None, document }
+    }
+
+    pub fn create(id: &str, document: Value) -> Self {
+        Self::index(document).with_action(BulkAction::Create).with_id(id)
+    }
+
+    pub fn update(id: &str, document: Value) -> Self {
+        Self::index(document).with_action(BulkAction::Update).with_id(id)
+    }
+
+    pub fn delete(id: &str) -> Self {
+        Self::index(Value::Null).with_action(BulkAction::Delete).with_id(id)
+    }
+
+    fn with_action(mut self, action: BulkAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    pub fn with_id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    pub fn with_index(mut self, index: &str) -> Self {
+        self.index = Some(index.to_string());
+        self
+    }
+
+    fn action_name(&self) -> &'static str {
+        match self.action {
+            BulkAction::Index => "index",
+            BulkAction::Create => "create",
+            BulkAction::Update => "update",
+            BulkAction::Delete => "delete",
+        }
+    }
+}
+
+/// A bulk item that failed. `position` is the item's position in the operations passed to `bulk`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BulkItemError {
+    pub position: usize,
+    pub action: BulkAction,
+    pub index: Option<String>,
+    pub id: Option<String>,
+    pub status: u16,
+    pub error_type: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BulkReport {
+    pub succeeded: usize,
+    pub failed: Vec<BulkItemError>,
+    /// The number of times throttled items were resent.
+    pub retries: u32,
+}
+
+impl BulkReport {
+    fn merge(&mut self, other: BulkReport, offset: usize) {
+        self.succeeded += other.succeeded;
+        self.retries += other.retries;
+        self.failed.extend(other.failed.into_iter().map(|mut e| {
+            e.position += offset;
+            e
+        }));
+    }
+}
+
+/// The NDJSON lines of a bulk request: an action line per operation, followed by a source line
+/// for everything but `delete`.
+pub fn bulk_lines(operations: &[&BulkOperation]) -> Vec<Value> {
+    let mut lines = Vec::with_capacity(operations.len() * 2);
+    for operation in operations {
+        let mut meta = Map::new();
+        if let Some(index) = &operation.index {
+            meta.insert("_index".to_string(), json!(index));
+        }
+        if let Some(id) = &operation.id {
+            meta.insert("_id".to_string(), json!(id));
+        }
+        lines.push(json!({ operation.action_name(): meta }));
+
+        match operation.action {
+            BulkAction::Index | BulkAction::Create => lines.push(operation.document.clone()),
+            BulkAction::Update => lines.push(json!({ "doc": operation.document })),
+            BulkAction::Delete => {}
+        }
+    }
+    lines
+}
+
+/// The outcome of each item of a bulk response, in request order: `None` for success.
+/// Error positions are relative to the request.
+pub fn parse_bulk_items(body: &Value, operations: &[&BulkOperation]) -> Vec<Option<BulkItemError>> {
+    let items = body["items"].as_array().cloned().unwrap_or_default();
+    operations.iter().enumerate().map(|(position, operation)| {
+        // Each item is keyed by its action, e.g. `{"index": {"_id": "1", "status": 201}}`
+        let item = items.get(position)
+            .and_then(Value::as_object)
+            .and_then(|item| item.values().next())
+            .cloned()
+            .unwrap_or(Value::Null);
+        let status = item["status"].as_u64().unwrap_or(0) as u16;
+        if item.get("error").is_none() && (200..300).contains(&status) {
+            return None;
+        }
+
+        let error = &item["error"];
+        Some(BulkItemError {
+            position,
+            action: operation.action,
+            index: item["_index"].as_str().map(String::from).or_else(|| operation.index.clone()),
+            id: item["_id"].as_str().map(String::from).or_else(|| operation.id.clone()),
+            status,
+            error_type: error["type"].as_str().unwrap_or("unknown").to_string(),
+            reason: error["reason"].as_str().map(String::from)
+                .unwrap_or_else(|| if item.is_null() { "Missing item in bulk response".to_string() } else { error.to_string() }),
+        })
+    }).collect()
+}
+
+impl ElasticsearchPool {
+    /// Writes `operations` to `index` in batches of `batch_size`, retrying throttled items.
+    /// Only request level failures are returned as errors; failed items are listed in the report.
+    pub async fn bulk(&self, index: &str, operations: &[BulkOperation], batch_size: usize) -> Result<BulkReport, DatabaseError> {
+        let mut report = BulkReport::default();
+        for (batch, chunk) in operations.chunks(batch_size.max(1)).enumerate() {
+            let outcome = self.bulk_batch(index, chunk).await?;
+            report.merge(outcome, batch * batch_size.max(1));
+        }
+        Ok(report)
+    }
+
+    async fn bulk_batch(&self, index: &str, operations: &[BulkOperation]) -> Result<BulkReport, DatabaseError> {
+        let mut report = BulkReport::default();
+        let mut pending: Vec<usize> = (0..operations.len()).collect();
+        let mut delay = BULK_RETRY_DELAY;
+
+        loop {
+            let batch: Vec<&BulkOperation> = pending.iter().map(|&i| &operations[i]).collect();
+            let lines: Vec<JsonBody<Value>> = bulk_lines(&batch).into_iter().map(JsonBody::new).collect();
+            let parts = if index.is_empty() { BulkParts::None } else { BulkParts::Index(index) };
+            let mut call = self.client().bulk(parts).body(lines);
+            if let Some(refresh) = self.refresh_policy() {
+                call = call.refresh(parse_refresh(refresh)?);
+            }
+            let response = call.send().await
+                .map_err(transport_error)?;
+
+            let throttled: Vec<usize> = if response.status_code().as_u16() == TOO_MANY_REQUESTS {
+                (0..pending.len()).collect()
+            } else {
+                let body = read_response(response).await?;
+                let mut throttled = Vec::new();
+                for (position, outcome) in parse_bulk_items(&body, &batch).into_iter().enumerate() {
+                    match outcome {
+                        None => report.succeeded += 1,
+                        Some(error) if error.status == TOO_MANY_REQUESTS => throttled.push(position),
+                        Some(error) => report.failed.push(BulkItemError { position: pending[position], ..error }),
+                    }
+                }
+                throttled
+            };
+
+            if throttled.is_empty() {
+                break;
+            }
+            if report.retries >= MAX_BULK_RETRIES {
+                report.failed.extend(throttled.iter().map(|&position| {
+                    let operation = &operations[pending[position]];
+                    BulkItemError {
+                        position: pending[position],
+                        action: operation.action,
+                        index: operation.index.clone().or_else(|| Some(index.to_string())),
+                        id: operation.id.clone(),
+                        status: TOO_MANY_REQUESTS,
+                        error_type: "too_many_requests".to_string(),
+                        reason: format!("Still throttled after {} retries", MAX_BULK_RETRIES),
+                    }
+                }));
+                break;
+            }
+
+            warn!("Bulk request throttled, retrying {} items in {:?}", throttled.len(), delay);
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+            report.retries += 1;
+            pending = throttled.into_iter().map(|position| pending[position]).collect();
+        }
+
+        report.failed.sort_by_key(|e| e.position);
+        Ok(report)
+    }
+
+    /// Copies the documents matching `query` from `source` into `dest`, keeping their ids.
+    /// A `null` query copies every document. `transform` can rewrite each document or return `None` to skip it; pass `Some` to copy
+    /// documents unchanged.
+    pub async fn reindex<F>(&self, source: &str, dest: &str, query: Value, batch_size: usize, mut transform: F) -> Result<BulkReport, DatabaseError>
+    where
+        F: FnMut(Map<String, Value>) -> Option<Map<String, Value>>,
+    {
+        let mut report = BulkReport::default();
+        let mut copied = 0;
+        let body = if query.is_null() { json!({}) } else { json!({ "query": query }) };
+        let mut pages = Box::pin(self.search_hits(source, body, batch_size));
+
+        while let Some(hits) = pages.try_next().await? {
+            let operations: Vec<BulkOperation> = hits.iter()
+                .map(hit_document)
+                .filter_map(|(id, document)| {
+                    let operation = BulkOperation::index(Value::Object(transform(document)?));
+                    Some(match id {
+                        Some(id) => operation.with_id(&id),
+                        None => operation,
+                    })
+                })
+                .collect();
+
+            let outcome = self.bulk(dest, &operations, batch_size).await?;
+            report.merge(outcome, copied);
+            copied += operations.len();
+        }
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::elasticsearch_bulk::{bulk_lines, parse_bulk_items, BulkAction, BulkOperation};
+    use crate::db::elasticsearch_results::hit_document;
+    use serde_json::json;
+
+    #[test]
+    fn test_bulk_lines() {
+        let operations = [
+            BulkOperation::index(json!({ "name": "a" })),
+            BulkOperation::create("2", json!({ "name": "b" })).with_index("other"),
+            BulkOperation::update("3", json!({ "name": "c" })),
+            BulkOperation::delete("4"),
+        ];
+        let operations: Vec<&BulkOperation> = operations.iter().collect();
+
+        assert_eq!(bulk_lines(&operations), vec![
+            json!({ "index": {} }),
+            json!({ "name": "a" }),
+            json!({ "create": { "_index": "other", "_id": "2" } }),
+            json!({ "name": "b" }),
+            json!({ "update": { "_id": "3" } }),
+            json!({ "doc": { "name": "c" } }),
+            json!({ "delete": { "_id": "4" } }),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_bulk_items_reports_failures_in_order() {
+        let operations = [
+            BulkOperation::index(json!({})),
+            BulkOperation::create("2", json!({})),
+            BulkOperation::delete("3"),
+        ];
+        let operations: Vec<&BulkOperation> = operations.iter().collect();
+        let body = json!({
+            "errors": true,
+            "items": [
+                { "index": { "_index": "logs", "_id": "x1", "status": 201, "result": "created" } },
+                { "create": { "_index": "logs", "_id": "2", "status": 409, "error": { "type": "version_conflict_engine_exception", "reason": "document already exists" } } },
+                { "delete": { "_index": "logs", "_id": "3", "status": 429, "error": { "type": "es_rejected_execution_exception", "reason": "rejected" } } }
+            ]
+        });
+
+        let items = parse_bulk_items(&body, &operations);
+        assert!(items[0].is_none());
+
+        let conflict = items[1].as_ref().unwrap();
+        assert_eq!(conflict.position, 1);
+        assert_eq!(conflict.action, BulkAction::Create);
+        assert_eq!(conflict.status, 409);
+        assert_eq!(conflict.error_type, "version_conflict_engine_exception");
+        assert_eq!(conflict.reason, "document already exists");
+
+        assert_eq!(items[2].as_ref().unwrap().status, 429);
+    }
+
+    #[test]
+    fn test_parse_bulk_items_missing_items_are_failures() {
+        let operations = [BulkOperation::delete("1")];
+        let operations: Vec<&BulkOperation> = operations.iter().collect();
+        let items = parse_bulk_items(&json!({ "items": [] }), &operations);
+        assert_eq!(items[0].as_ref().unwrap().reason, "Missing item in bulk response");
+        assert_eq!(items[0].as_ref().unwrap().id.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_hit_document_keeps_source_apart_from_metadata() {
+        let hit = json!({
+            "_id": "7", "_index": "logs", "_score": 1.0,
+            "_source": { "message": "hello", "_score": 42, "_index": "original" }
+        });
+
+        let (id, document) = hit_document(&hit);
+        assert_eq!(id.as_deref(), Some("7"));
+        assert_eq!(serde_json::Value::Object(document), json!({ "message": "hello", "_score": 42, "_index": "original" }),
+            "Source fields named like hit metadata should be kept");
+    }
+}
@@ -0,0 +1,224 @@
+//! Elasticsearch cluster monitoring: cluster health, shard allocation, node statistics and
+//! per-index health, parsed from `_cluster/health`, `_cat/shards`, `_nodes/stats` and `_cat/indices`.
+use elasticsearch::cat::{CatIndicesParts, CatShardsParts};
+use elasticsearch::cluster::ClusterHealthParts;
+use elasticsearch::nodes::NodesStatsParts;
+use elasticsearch::params::Bytes;
+use serde::Serialize;
+use serde_json::Value;
+use crate::db::elasticsearch_custom::{read_response, transport_error, ElasticsearchPool};
+use crate::utils::errors::DatabaseError;
+
+/// Node statistics requested from `_nodes/stats`.
+const NODE_STATS_METRICS: [&str; 4] = ["jvm", "os", "fs", "indices"];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClusterHealth {
+    pub cluster_name: String,
+    /// `green`, `yellow` or `red`.
+    pub status: String,
+    pub number_of_nodes: u64,
+    pub number_of_data_nodes: u64,
+    pub active_primary_shards: u64,
+    pub active_shards: u64,
+    pub relocating_shards: u64,
+    pub initializing_shards: u64,
+    pub unassigned_shards: u64,
+    pub active_shards_percent: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShardInfo {
+    pub index: String,
+    pub shard: u64,
+    pub primary: bool,
+    /// `STARTED`, `RELOCATING`, `INITIALIZING` or `UNASSIGNED`.
+    pub state: String,
+    pub docs: Option<u64>,
+    pub store_bytes: Option<u64>,
+    pub node: Option<String>,
+    pub unassigned_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeStats {
+    pub id: String,
+    pub name: String,
+    pub heap_used_bytes: u64,
+    pub heap_max_bytes: u64,
+    pub heap_used_percent: f64,
+    pub cpu_percent: Option<f64>,
+    pub load_average_1m: Option<f64>,
+    pub disk_total_bytes: Option<u64>,
+    pub disk_available_bytes: Option<u64>,
+    pub docs: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IndexInfo {
+    pub index: String,
+    pub health: String,
+    /// `open` or `close`.
+    pub status: String,
+    pub primaries: u64,
+    pub replicas: u64,
+    pub docs_count: Option<u64>,
+    pub store_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ElasticsearchClusterMetrics {
+    pub health: ClusterHealth,
+    pub nodes: Vec<NodeStats>,
+    pub indices: Vec<IndexInfo>,
+    /// Shards that are not `STARTED`.
+    pub unhealthy_shards: Vec<ShardInfo>,
+}
+
+impl ElasticsearchClusterMetrics {
+    /// Indices whose health is `yellow` or `red`.
+    pub fn unhealthy_indices(&self) -> Vec<&IndexInfo> {
+        self.indices.iter().filter(|index| index.health != "green").collect()
+    }
+
+    /// Nodes whose heap usage is at or above `percent`.
+    pub fn nodes_under_heap_pressure(&self, percent: f64) -> Vec<&NodeStats> {
+        self.nodes.iter().filter(|node| node.heap_used_percent >= percent).collect()
+    }
+}
+
+/// `_cat` APIs return numbers as strings.
+fn cat_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        value => value.as_u64(),
+    }
+}
+
+fn cat_string(value: &Value) -> Option<String> {
+    value.as_str().filter(|s| !s.is_empty()).map(String::from)
+}
+
+pub fn parse_cluster_health(body: &Value) -> ClusterHealth {
+    let count = |key: &str| body[key].as_u64().unwrap_or(0);
+    ClusterHealth {
+        cluster_name: body["cluster_name"].as_str().unwrap_or_default().to_string(),
+        status: body["status"].as_str().unwrap_or("unknown").to_string(),
+        number_of_nodes: count("number_of_nodes"),
+        number_of_data_nodes: count("number_of_data_nodes"),
+        active_primary_shards: count("active_primary_shards"),
+        active_shards: count("active_shards"),
+        relocating_shards: count("relocating_shards"),
+        initializing_shards: count("initializing_shards"),
+        unassigned_shards: count("unassigned_shards"),
+        active_shards_percent: body["active_shards_percent_as_number"].as_f64().unwrap_or(0.0),
+    }
+}
+
+/// Parses `_cat/shards?format=json&bytes=b`.
+pub fn parse_cat_shards(body: &Value) -> Vec<ShardInfo> {
+    body.as_array().map(|shards| shards.iter().map(|shard| ShardInfo {
+        index: shard["index"].as_str().unwrap_or_default().to_string(),
+        shard: cat_u64(&shard["shard"]).unwrap_or(0),
+        primary: shard["prirep"] == "p",
+        state: shard["state"].as_str().unwrap_or_default().to_string(),
+        docs: cat_u64(&shard["docs"]),
+        store_bytes: cat_u64(&shard["store"]),
+        node: cat_string(&shard["node"]),
+        unassigned_reason: cat_string(&shard["unassigned.reason"]),
+    }).collect()).unwrap_or_default()
+}
+
+/// Parses `_nodes/stats`, sorted by node name.
+pub fn parse_nodes_stats(body: &Value) -> Vec<NodeStats> {
+    let mut nodes: Vec<NodeStats> = body["nodes"].as_object().map(|nodes| nodes.iter().map(|(id, node)| {
+        let heap_used_bytes = node["jvm"]["mem"]["heap_used_in_bytes"].as_u64().unwrap_or(0);
+        let heap_max_bytes = node["jvm"]["mem"]["heap_max_in_bytes"].as_u64().unwrap_or(0);
+        let heap_used_percent = match node["jvm"]["mem"]["heap_used_percent"].as_f64() {
+            Some(percent) => percent,
+            None if heap_max_bytes > 0 => heap_used_bytes as f64 * 100.0 / heap_max_bytes as f64,
+            None => 0.0,
+        };
+        NodeStats {
+            id: id.clone(),
+            name: node["name"].as_str().unwrap_or(id).to_string(),
+            heap_used_bytes,
+            heap_max_bytes,
+            heap_used_percent,
+            cpu_percent: node["os"]["cpu"]["percent"].as_f64(),
+            load_average_1m: node["os"]["cpu"]["load_average"]["1m"].as_f64(),
+            disk_total_bytes: node["fs"]["total"]["total_in_bytes"].as_u64(),
+            disk_available_bytes: node["fs"]["total"]["available_in_bytes"].as_u64(),
+            docs: node["indices"]["docs"]["count"].as_u64(),
+        }
+    }).collect()).unwrap_or_default();
+    nodes.sort_by(|a, b| a.name.cmp(&b.name));
+    nodes
+}
+
+/// Parses `_cat/indices?format=json&bytes=b`, sorted by index name.
+pub fn parse_cat_indices(body: &Value) -> Vec<IndexInfo> {
+    let mut indices: Vec<IndexInfo> = body.as_array().map(|indices| indices.iter().map(|index| IndexInfo {
+        index: index["index"].as_str().unwrap_or_default().to_string(),
+        // Closed indices have no health
+        health: cat_string(&index["health"]).unwrap_or_else(|| "unknown".to_string()),
+        status: index["status"].as_str().unwrap_or_default().to_string(),
+        primaries: cat_u64(&index["pri"]).unwrap_or(0),
+        replicas: cat_u64(&index["rep"]).unwrap_or(0),
+        docs_count: cat_u64(&index["docs.count"]),
+        store_bytes: cat_u64(&index["store.size"]),
+    }).collect()).unwrap_or_default();
+    indices.sort_by(|a, b| a.index.cmp(&b.index));
+    indices
+}
+
+impl ElasticsearchPool {
+    pub async fn cluster_health(&self) -> Result<ClusterHealth, DatabaseError> {
+        let response = self.client().cluster().health(ClusterHealthParts::None).send().await
+            .map_err(transport_error)?;
+        Ok(parse_cluster_health(&read_response(response).await?))
+    }
+
+    pub async fn shards(&self) -> Result<Vec<ShardInfo>, DatabaseError> {
+        let response = self.client().cat().shards(CatShardsParts::None)
+            .format("json")
+            .bytes(Bytes::B)
+            .h(&["index", "shard", "prirep", "state", "docs", "store", "node", "unassigned.reason"])
+            .send()
+            .await
+            .map_err(transport_error)?;
+        Ok(parse_cat_shards(&read_response(response).await?))
+    }
+
+    pub async fn node_stats(&self) -> Result<Vec<NodeStats>, DatabaseError> {
+        let response = self.client().nodes().stats(NodesStatsParts::Metric(&NODE_STATS_METRICS)).send().await
+            .map_err(transport_error)?;
+        Ok(parse_nodes_stats(&read_response(response).await?))
+    }
+
+    pub async fn indices(&self) -> Result<Vec<IndexInfo>, DatabaseError> {
+        let response = self.client().cat().indices(CatIndicesParts::None)
+            .format("json")
+            .bytes(Bytes::B)
+            .send()
+            .await
+            .map_err(transport_error)?;
+        Ok(parse_cat_indices(&read_response(response).await?))
+    }
+
+    /// Collects cluster health, node stats, index health and the shards that are not started.
+    pub async fn cluster_metrics(&self) -> Result<ElasticsearchClusterMetrics, DatabaseError> {
+        let (health, nodes, indices, shards) = tokio::try_join!(
+            self.cluster_health(),
+            self.node_stats(),
+            self.indices(),
+            self.shards(),
+        )?;
+        Ok(ElasticsearchClusterMetrics {
+            health,
+            nodes,
+            indices,
+            unhealthy_shards: shards.into_iter().filter(|shard| shard.state != "STARTED").collect(),
+        })
+    }
+}
@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::elasticsearch_cluster::{
+        parse_cat_indices, parse_cat_shards, parse_cluster_health, parse_nodes_stats, ClusterHealth, ElasticsearchClusterMetrics,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_cluster_health() {
+        let health = parse_cluster_health(&json!({
+            "cluster_name": "prod", "status": "yellow", "number_of_nodes": 3, "number_of_data_nodes": 2,
+            "active_primary_shards": 10, "active_shards": 18, "relocating_shards": 0,
+            "initializing_shards": 1, "unassigned_shards": 2, "active_shards_percent_as_number": 85.7
+        }));
+        assert_eq!(health, ClusterHealth {
+            cluster_name: "prod".to_string(),
+            status: "yellow".to_string(),
+            number_of_nodes: 3,
+            number_of_data_nodes: 2,
+            active_primary_shards: 10,
+            active_shards: 18,
+            relocating_shards: 0,
+            initializing_shards: 1,
+            unassigned_shards: 2,
+            active_shards_percent: 85.7,
+        });
+    }
+
+    #[test]
+    fn test_parse_cat_shards() {
+        let shards = parse_cat_shards(&json!([
+            { "index": "logs", "shard": "0", "prirep": "p", "state": "STARTED", "docs": "120", "store": "4096", "node": "node-1", "unassigned.reason": null },
+            { "index": "logs", "shard": "0", "prirep": "r", "state": "UNASSIGNED", "docs": null, "store": null, "node": null, "unassigned.reason": "NODE_LEFT" }
+        ]));
+        assert_eq!(shards.len(), 2);
+        assert!(shards[0].primary);
+        assert_eq!(shards[0].docs, Some(120));
+        assert_eq!(shards[0].store_bytes, Some(4096));
+        assert!(!shards[1].primary);
+        assert_eq!(shards[1].node, None);
+        assert_eq!(shards[1].unassigned_reason.as_deref(), Some("NODE_LEFT"));
+    }
+
+    #[test]
+    fn test_parse_nodes_stats() {
+        let nodes = parse_nodes_stats(&json!({
+            "nodes": {
+                "b1": {
+                    "name": "node-b",
+                    "jvm": { "mem": { "heap_used_in_bytes": 750, "heap_max_in_bytes": 1000 } },
+                    "os": { "cpu": { "percent": 12, "load_average": { "1m": 0.5 } } },
+                    "fs": { "total": { "total_in_bytes": 10000, "available_in_bytes": 2500 } },
+                    "indices": { "docs": { "count": 42 } }
+                },
+                "a1": { "name": "node-a", "jvm": { "mem": { "heap_used_in_bytes": 10, "heap_max_in_bytes": 100, "heap_used_percent": 10 } } }
+            }
+        }));
+        assert_eq!(nodes[0].name, "node-a");
+        assert_eq!(nodes[0].heap_used_percent, 10.0);
+        assert_eq!(nodes[0].cpu_percent, None);
+        assert_eq!(nodes[1].id, "b1");
+        assert_eq!(nodes[1].heap_used_percent, 75.0);
+        assert_eq!(nodes[1].load_average_1m, Some(0.5));
+        assert_eq!(nodes[1].disk_available_bytes, Some(2500));
+        assert_eq!(nodes[1].docs, Some(42));
+    }
+
+    #[test]
+    fn test_unhealthy_indices_and_heap_pressure() {
+        let indices = parse_cat_indices(&json!([
+            { "health": "green", "status": "open", "index": "b", "pri": "1", "rep": "1", "docs.count": "10", "store.size": "2048" },
+            { "health": "red", "status": "open", "index": "a", "pri": "3", "rep": "1", "docs.count": "0", "store.size": "0" },
+            { "health": null, "status": "close", "index": "c", "pri": "1", "rep": "0" }
+        ]));
+        assert_eq!(indices[0].index, "a");
+        assert_eq!(indices[1].store_bytes, Some(2048));
+        assert_eq!(indices[2].health, "unknown");
+        assert_eq!(indices[2].docs_count, None);
+
+        let metrics = ElasticsearchClusterMetrics {
+            health: parse_cluster_health(&json!({ "status": "red" })),
+            nodes: parse_nodes_stats(&json!({ "nodes": { "n": { "name": "n", "jvm": { "mem": { "heap_used_percent": 91 } } } } })),
+            indices,
+            unhealthy_shards: Vec::new(),
+        };
+        let unhealthy: Vec<&str> = metrics.unhealthy_indices().iter().map(|i| i.index.as_str()).collect();
+        assert_eq!(unhealthy, vec!["a", "c"]);
+        assert_eq!(metrics.nodes_under_heap_pressure(85.0).len(), 1);
+        assert!(metrics.nodes_under_heap_pressure(95.0).is_empty());
+    }
+}
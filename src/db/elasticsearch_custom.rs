@@ -1,39 +1,120 @@
 use super::*;
-use elasticsearch::{Elasticsearch, http::transport::Transport, SearchParts, CreateParts, UpdateParts, DeleteParts};
+use elasticsearch::{Elasticsearch, SearchParts, CreateParts, UpdateParts, DeleteParts, IndexParts, UpdateByQueryParts, DeleteByQueryParts};
+use elasticsearch::auth::{ClientCertificate, Credentials};
 use elasticsearch::cat::CatIndicesParts;
-use elasticsearch::http::request::JsonBody;
+use elasticsearch::cert::{Certificate, CertificateValidation};
+use elasticsearch::http::headers::{HeaderName, HeaderValue};
+use elasticsearch::http::transport::{SingleNodeConnectionPool, TransportBuilder};
+use elasticsearch::http::Url;
+use elasticsearch::http::response::Response;
+use elasticsearch::params::Refresh;
+use elasticsearch::tasks::TasksCancelParts;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use crate::db::query_builder::{Operator, QueryBuilder, QueryOperation, OrderDirection};
+use log::warn;
+use tokio::sync::RwLock;
+use crate::db::elasticsearch_dsl::{build_query_clause, FieldMappings};
+use crate::db::elasticsearch_paging::can_stream;
+use crate::db::elasticsearch_results::{search_response_to_result, search_response_to_rows};
+use crate::config::config::{SourceOptions, TlsConfig, TlsVerify};
+use crate::db::cancel::{query_tag, CancelGuard};
+use crate::db::native_query::NativeQuery;
+use crate::db::result_set::ResultSet;
+use crate::db::row_stream::{collected, RowStream};
+use futures::StreamExt;
+use crate::db::query_builder::{Condition, Operator, QueryBuilder, QueryOperation, OrderDirection};
+use crate::db::tls::{tls_error, TlsFiles, TlsSupport};
 use crate::utils::errors::QueryBuilderError;
 
+/// The field that addresses a document by its id in builder fields and conditions.
+pub const ID_FIELD: &str = "_id";
+
+/// The Elasticsearch API an `ElasticsearchRequest` is routed to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ElasticsearchOperation {
+    #[default]
+    Search,
+    Index,
+    Create,
+    Update,
+    UpdateByQuery,
+    Delete,
+    DeleteByQuery,
+}
+
+/// The query produced by `build_query`: the target index and operation along with the body.
+/// `id` addresses a single document and `refresh` (`true`, `false` or `wait_for`) overrides
+/// the pool's refresh policy for writes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ElasticsearchRequest {
+    pub index: String,
+    #[serde(default)]
+    pub operation: ElasticsearchOperation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh: Option<String>,
+    #[serde(default)]
+    pub body: serde_json::Value,
+}
+
+impl ElasticsearchRequest {
+    fn new(builder: &QueryBuilder, operation: ElasticsearchOperation, id: Option<String>, body: serde_json::Value) -> Self {
+        ElasticsearchRequest {
+            index: builder.table.as_str().to_string(),
+            operation,
+            id,
+            refresh: None,
+            body,
+        }
+    }
+}
+
 pub fn build_query(builder: &QueryBuilder) -> Result<String, QueryBuilderError> {
-    let query = match builder.operation {
-        QueryOperation::Select => build_search_query(builder),
+    build_query_with_mappings(builder, &FieldMappings::default())
+}
+
+/// Builds the query using the index mappings to pick exact-match fields for `text` fields.
+pub fn build_query_with_mappings(builder: &QueryBuilder, mappings: &FieldMappings) -> Result<String, QueryBuilderError> {
+    let request = match builder.operation {
+        QueryOperation::Select => build_search_query(builder, mappings)
+            .map(|body| ElasticsearchRequest::new(builder, ElasticsearchOperation::Search, None, body)),
         QueryOperation::Insert => build_create_query(builder),
-        QueryOperation::Update => build_update_query(builder),
-        QueryOperation::Delete => build_delete_query(builder),
-        QueryOperation::Aggregate => build_aggregation_query(builder),
+        QueryOperation::Update => build_update_query(builder, mappings),
+        QueryOperation::Delete => build_delete_query(builder, mappings),
+        QueryOperation::Aggregate => build_aggregation_query(builder, mappings)
+            .map(|body| ElasticsearchRequest::new(builder, ElasticsearchOperation::Search, None, body)),
     }?;
 
-    Ok(serde_json::to_string(&query).map_err(|e| QueryBuilderError::InvalidQuery(e.to_string()))?)
+    if request.operation != ElasticsearchOperation::Search && request.index.is_empty() {
+        return Err(QueryBuilderError::MissingField("index".to_string()));
+    }
+
+    serde_json::to_string(&request).map_err(|e| QueryBuilderError::InvalidQuery(e.to_string()))
 }
 
-fn build_search_query(builder: &QueryBuilder) -> Result<serde_json::Value, QueryBuilderError> {
-    let mut query = json!({
-        "query": {
-            "bool": {
-                "must": []
-            }
+/// Returns the document id when the conditions consist of a single `_id` equality.
+fn document_id(conditions: &[Condition]) -> Option<String> {
+    match conditions {
+        [condition] if condition.field.as_str() == ID_FIELD && matches!(condition.operator, Operator::Eq) => {
+            Some(id_to_string(&condition.value))
         }
-    });
+        _ => None,
+    }
+}
 
-    for condition in &builder.conditions {
-        let mut term = json!({});
-        term[condition.field.as_str()] = json!({
-            operator_to_elasticsearch(&condition.operator): condition.value
-        });
-        query["query"]["bool"]["must"].as_array_mut().unwrap().push(term);
+fn id_to_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
     }
+}
+
+fn build_search_query(builder: &QueryBuilder, mappings: &FieldMappings) -> Result<serde_json::Value, QueryBuilderError> {
+    let mut query = json!({
+        "query": build_query_clause(&builder.conditions, mappings)
+    });
 
     if !builder.fields.is_empty() {
         query["_source"] = json!(builder.fields.iter().map(|f| f.as_str()).collect::<Vec<_>>());
@@ -42,7 +123,7 @@ fn build_search_query(builder: &QueryBuilder) -> Result<serde_json::Value, Query
     if !builder.order_by.is_empty() {
         query["sort"] = json!(builder.order_by.iter().map(|o| {
             json!({
-                o.field.as_str(): {
+                mappings.sort_field(o.field.as_str()): {
                     "order": if o.direction == OrderDirection::Asc { "asc" } else { "desc" }
                 }
             })
@@ -60,42 +141,80 @@ fn build_search_query(builder: &QueryBuilder) -> Result<serde_json::Value, Query
     Ok(query)
 }
 
-fn build_create_query(builder: &QueryBuilder) -> Result<serde_json::Value, QueryBuilderError> {
+/// Zips fields and values into a document, splitting out the `_id` field if present.
+fn build_document(builder: &QueryBuilder) -> Result<(Option<String>, serde_json::Map<String, serde_json::Value>), QueryBuilderError> {
     if builder.fields.is_empty() || builder.values.is_empty() {
         return Err(QueryBuilderError::MissingField("fields or values".to_string()));
     }
 
-    let doc: serde_json::Map<String, serde_json::Value> = builder.fields.iter()
-        .zip(builder.values.iter())
-        .map(|(f, v)| (f.as_str().to_string(), v.clone()))
-        .collect();
+    let mut id = None;
+    let mut doc = serde_json::Map::new();
+    for (field, value) in builder.fields.iter().zip(builder.values.iter()) {
+        if field.as_str() == ID_FIELD {
+            id = Some(id_to_string(value));
+        } else {
+            doc.insert(field.as_str().to_string(), value.clone());
+        }
+    }
 
-    Ok(json!(doc))
+    Ok((id, doc))
 }
 
-fn build_update_query(builder: &QueryBuilder) -> Result<serde_json::Value, QueryBuilderError> {
-    if builder.fields.is_empty() || builder.values.is_empty() {
-        return Err(QueryBuilderError::MissingField("fields or values".to_string()));
+fn build_create_query(builder: &QueryBuilder) -> Result<ElasticsearchRequest, QueryBuilderError> {
+    let (id, doc) = build_document(builder)?;
+    // With an id, `create` refuses to overwrite an existing document; without one `index`
+    // lets Elasticsearch generate it.
+    let operation = if id.is_some() { ElasticsearchOperation::Create } else { ElasticsearchOperation::Index };
+    Ok(ElasticsearchRequest::new(builder, operation, id, json!(doc)))
+}
+
+fn build_update_query(builder: &QueryBuilder, mappings: &FieldMappings) -> Result<ElasticsearchRequest, QueryBuilderError> {
+    let (_, doc) = build_document(builder)?;
+
+    if let Some(id) = document_id(&builder.conditions) {
+        return Ok(ElasticsearchRequest::new(builder, ElasticsearchOperation::Update, Some(id), json!({
+            "doc": doc
+        })));
     }
 
-    let doc: serde_json::Map<String, serde_json::Value> = builder.fields.iter()
-        .zip(builder.values.iter())
-        .map(|(f, v)| (f.as_str().to_string(), v.clone()))
-        .collect();
+    require_conditions(builder)?;
+    Ok(ElasticsearchRequest::new(builder, ElasticsearchOperation::UpdateByQuery, None, json!({
+        "query": build_query_clause(&builder.conditions, mappings),
+        "script": {
+            "lang": "painless",
+            "source": "for (entry in params.doc.entrySet()) { ctx._source[entry.getKey()] = entry.getValue(); }",
+            "params": { "doc": doc }
+        }
+    })))
+}
+
+fn build_delete_query(builder: &QueryBuilder, mappings: &FieldMappings) -> Result<ElasticsearchRequest, QueryBuilderError> {
+    if let Some(id) = document_id(&builder.conditions) {
+        // Deleting a single document doesn't require a body
+        return Ok(ElasticsearchRequest::new(builder, ElasticsearchOperation::Delete, Some(id), json!({})));
+    }
 
-    Ok(json!({
-        "doc": doc
-    }))
+    require_conditions(builder)?;
+    Ok(ElasticsearchRequest::new(builder, ElasticsearchOperation::DeleteByQuery, None, json!({
+        "query": build_query_clause(&builder.conditions, mappings)
+    })))
 }
 
-fn build_delete_query(_builder: &QueryBuilder) -> Result<serde_json::Value, QueryBuilderError> {
-    // Elasticsearch delete doesn't require a body, so we return an empty object
-    Ok(json!({}))
+/// Refuses to turn an update or delete without conditions into a `match_all` over the whole
+/// index, unless the builder asked for it with `all`.
+fn require_conditions(builder: &QueryBuilder) -> Result<(), QueryBuilderError> {
+    if builder.conditions.is_empty() && !builder.all {
+        return Err(QueryBuilderError::InvalidQuery(
+            "An update or delete without conditions would change every document; use `all` to allow it".to_string(),
+        ));
+    }
+    Ok(())
 }
 
-fn build_aggregation_query(builder: &QueryBuilder) -> Result<serde_json::Value, QueryBuilderError> {
+fn build_aggregation_query(builder: &QueryBuilder, mappings: &FieldMappings) -> Result<serde_json::Value, QueryBuilderError> {
     let mut query = json!({
         "size": 0,
+        "query": build_query_clause(&builder.conditions, mappings),
         "aggs": {}
     });
 
@@ -103,7 +222,7 @@ fn build_aggregation_query(builder: &QueryBuilder) -> Result<serde_json::Value,
         let agg_name = format!("agg_{}", i);
         query["aggs"][&agg_name] = json!({
             "terms": {
-                "field": field.as_str()
+                "field": mappings.sort_field(field.as_str())
             }
         });
     }
@@ -111,30 +230,316 @@ fn build_aggregation_query(builder: &QueryBuilder) -> Result<serde_json::Value,
     Ok(query)
 }
 
-fn operator_to_elasticsearch(operator: &Operator) -> &'static str {
-    match operator {
-        Operator::Eq => "term",
-        Operator::Ne => "must_not",
-        Operator::Gt => "gt",
-        Operator::Lt => "lt",
-        Operator::Gte => "gte",
-        Operator::Lte => "lte",
-        Operator::Like => "wildcard",
-        Operator::In => "terms",
-        Operator::NotIn => "must_not",
+/// Converts a refresh policy name into the value expected by single-document APIs.
+pub(crate) fn parse_refresh(refresh: &str) -> Result<Refresh, DatabaseError> {
+    match refresh {
+        "true" => Ok(Refresh::True),
+        "false" => Ok(Refresh::False),
+        "wait_for" => Ok(Refresh::WaitFor),
+        other => Err(DatabaseError::InvalidQuery(format!("Invalid refresh policy: {}", other))),
     }
 }
 
+/// Reads a response body, turning non-success statuses into errors carrying the reason and status.
+pub(crate) async fn read_response(response: Response) -> Result<serde_json::Value, DatabaseError> {
+    let status = response.status_code();
+    let body = response.json::<serde_json::Value>().await;
+
+    if !status.is_success() {
+        // Proxies in front of the cluster may answer without a JSON body
+        let reason = match &body {
+            Ok(body) => body["error"]["reason"].as_str()
+                .or_else(|| body["result"].as_str())
+                .map(String::from)
+                .unwrap_or_else(|| body["error"].to_string()),
+            Err(_) => status.canonical_reason().unwrap_or("Request failed").to_string(),
+        };
+        return Err(DatabaseError::elasticsearch(status.as_u16(), format!("{} ({})", reason, status)));
+    }
+
+    body.map_err(|e| DatabaseError::QueryError(e.to_string()))
+}
+
+/// Classifies an error sending a request. Responses with an error status are read by
+/// `read_response`, so these are transport errors: the cluster could not be reached or the
+/// connection broke.
+pub(crate) fn transport_error(error: elasticsearch::Error) -> DatabaseError {
+    match error.status_code() {
+        Some(status) => DatabaseError::elasticsearch(status.as_u16(), error.to_string()),
+        None => DatabaseError::ConnectionError(error.to_string()),
+    }
+}
+
+/// Summarizes a write response in a single row. `affected` is the number of documents that
+/// were created, updated or deleted.
+fn write_result_row(operation: ElasticsearchOperation, body: &serde_json::Value) -> HashMap<String, Value> {
+    let mut row: HashMap<String, Value> = ["_index", "_id", "_version", "result", "total", "created", "updated", "deleted", "noops", "version_conflicts", "took"]
+        .iter()
+        .filter_map(|key| body.get(*key).map(|v| (key.to_string(), v.clone())))
+        .collect();
+
+    let affected = match operation {
+        ElasticsearchOperation::UpdateByQuery => body["updated"].as_u64().unwrap_or(0),
+        ElasticsearchOperation::DeleteByQuery => body["deleted"].as_u64().unwrap_or(0),
+        _ => match body["result"].as_str() {
+            Some("created") | Some("updated") | Some("deleted") => 1,
+            _ => 0,
+        },
+    };
+
+    row.insert("operation".to_string(), json!(operation));
+    row.insert("affected".to_string(), json!(affected));
+    row
+}
+
+/// The request of a native query. Only searches may leave out the index.
+fn native_request(query: &NativeQuery) -> Result<&ElasticsearchRequest, DatabaseError> {
+    match query {
+        NativeQuery::Elasticsearch(request) => {
+            if request.operation != ElasticsearchOperation::Search && request.index.is_empty() {
+                return Err(DatabaseError::MissingField("index".to_string()));
+            }
+            Ok(request)
+        }
+        other => Err(other.unsupported()),
+    }
+}
+
+/// The client always sends the host as the server name and checks it whenever it verifies
+/// certificates. A configured CA is trusted in addition to the built-in roots.
+pub(crate) fn with_tls(builder: TransportBuilder, tls: &TlsConfig) -> Result<TransportBuilder, DatabaseError> {
+    TlsSupport { verify_ca_only: false, server_name: false }.check("Elasticsearch", tls)?;
+    let files = TlsFiles::load(tls)?;
+
+    let validation = match (tls.verify, &files.ca) {
+        (TlsVerify::Disabled, _) => CertificateValidation::None,
+        (_, Some(ca)) => CertificateValidation::Full(Certificate::from_pem(ca).map_err(tls_error)?),
+        (_, None) => CertificateValidation::Default,
+    };
+    let mut builder = builder.cert_validation(validation);
+    if let Some(identity) = files.identity_pem() {
+        builder = builder.auth(Credentials::Certificate(ClientCertificate::Pem(identity)));
+    }
+    Ok(builder)
+}
+
+/// Header whose value Elasticsearch copies into the tasks a request starts.
+const OPAQUE_ID_HEADER: &str = "x-opaque-id";
+
+/// The ids of the cancellable top-level tasks in a `_tasks?detailed` response that were started
+/// by requests tagged with `tag`.
+pub(crate) fn tagged_tasks(response: &serde_json::Value, tag: &str) -> Vec<String> {
+    let nodes = response["nodes"].as_object().into_iter().flat_map(|nodes| nodes.values());
+    nodes.filter_map(|node| node["tasks"].as_object())
+        .flat_map(|tasks| tasks.iter())
+        .filter(|(_, task)| {
+            task["cancellable"].as_bool() == Some(true)
+                && task.get("parent_task_id").is_none()
+                && task["headers"]["X-Opaque-Id"].as_str() == Some(tag)
+        })
+        .map(|(id, _)| id.clone())
+        .collect()
+}
+
+/// Cancels the tasks started by requests tagged with `tag`.
+async fn cancel_tagged(client: Elasticsearch, tag: String) -> Result<(), DatabaseError> {
+    let response = client.tasks().list().detailed(true).send().await
+        .map_err(transport_error)?;
+    let tasks = read_response(response).await?;
+    for task_id in tagged_tasks(&tasks, &tag) {
+        client.tasks().cancel(TasksCancelParts::TaskId(&task_id)).send().await
+            .map_err(transport_error)?;
+    }
+    Ok(())
+}
+
 pub struct ElasticsearchPool {
     client: Elasticsearch,
+    refresh: Option<String>,
+    mappings: RwLock<HashMap<String, FieldMappings>>,
 }
 
 impl ElasticsearchPool {
     pub async fn new(connection_string: &str) -> Result<Self, DatabaseError> {
-        let transport = elasticsearch::http::transport::Transport::single_node(connection_string)
-            .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
+        Self::open(connection_string, &SourceOptions::default()).await
+    }
+
+    /// Opens a client with the options of a configured source.
+    pub async fn open(connection_string: &str, options: &SourceOptions) -> Result<Self, DatabaseError> {
+        let url = Url::parse(connection_string).map_err(|e| DatabaseError::ConfigurationError(e.to_string()))?;
+        let mut builder = TransportBuilder::new(SingleNodeConnectionPool::new(url));
+        if let Some(timeout) = options.query_timeout() {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(tls) = options.tls.as_ref().filter(|tls| tls.enabled) {
+            builder = with_tls(builder, tls)?;
+        }
+        let transport = builder.build().map_err(|e| DatabaseError::ConfigurationError(e.to_string()))?;
         let client = Elasticsearch::new(transport);
-        Ok(ElasticsearchPool { client })
+        Ok(ElasticsearchPool { client, refresh: None, mappings: RwLock::new(HashMap::new()) })
+    }
+
+    pub(crate) fn client(&self) -> &Elasticsearch {
+        &self.client
+    }
+
+    pub(crate) fn refresh_policy(&self) -> Option<&str> {
+        self.refresh.as_deref()
+    }
+
+    /// Sets the refresh policy (`true`, `false` or `wait_for`) for writes that don't specify one.
+    pub fn with_refresh(mut self, refresh: &str) -> Self {
+        self.refresh = Some(refresh.to_string());
+        self
+    }
+
+    /// Returns the field mappings of an index (or index pattern), cached after the first lookup.
+    pub async fn field_mappings(&self, index: &str) -> Result<FieldMappings, DatabaseError> {
+        if let Some(mappings) = self.mappings.read().await.get(index) {
+            return Ok(mappings.clone());
+        }
+
+        let response = self.client
+            .indices()
+            .get_mapping(elasticsearch::indices::IndicesGetMappingParts::Index(&[index]))
+            .send()
+            .await
+            .map_err(transport_error)?;
+        let mappings = FieldMappings::from_mapping_response(&read_response(response).await?);
+
+        self.mappings.write().await.insert(index.to_string(), mappings.clone());
+        Ok(mappings)
+    }
+
+    /// Forgets cached mappings, e.g. after a mapping change.
+    pub async fn clear_mapping_cache(&self) {
+        self.mappings.write().await.clear();
+    }
+
+    /// Routes a request to the API matching its operation. Searches return one row per hit, or
+    /// one row per bucket when the search has aggregations; writes return a single row with the outcome and the number of affected documents.
+    pub async fn execute_request(&self, request: &ElasticsearchRequest) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        let response_body = self.send_request(request).await?;
+        match request.operation {
+            ElasticsearchOperation::Search => search_response_to_rows(&response_body)
+                .ok_or_else(|| DatabaseError::QueryError("Invalid response format".to_string())),
+            operation => Ok(vec![write_result_row(operation, &response_body)]),
+        }
+    }
+
+    /// Builds the request of a `QueryBuilder` query, using the mappings of its index to pick
+    /// exact-match fields.
+    async fn builder_request(&self, builder: &QueryBuilder) -> Result<ElasticsearchRequest, DatabaseError> {
+        let index = builder.table.as_str();
+        let mappings = if index.is_empty() {
+            FieldMappings::default()
+        } else {
+            self.field_mappings(index).await.unwrap_or_else(|e| {
+                warn!("Could not load mappings for {}, querying without them: {}", index, e);
+                FieldMappings::default()
+            })
+        };
+
+        let query = build_query_with_mappings(builder, &mappings)?;
+        serde_json::from_str(&query).map_err(|e| DatabaseError::InvalidQuery(e.to_string()))
+    }
+
+    /// Like `execute_request`, keeping the `_source` field order of hits. Writes report the
+    /// number of affected documents in `affected_rows`.
+    pub async fn query_request(&self, request: &ElasticsearchRequest) -> Result<ResultSet, DatabaseError> {
+        let response_body = self.send_request(request).await?;
+        match request.operation {
+            ElasticsearchOperation::Search => search_response_to_result(&response_body)
+                .ok_or_else(|| DatabaseError::QueryError("Invalid response format".to_string())),
+            operation => {
+                let row = write_result_row(operation, &response_body);
+                let affected_rows = row.get("affected").and_then(Value::as_u64);
+                Ok(ResultSet { affected_rows, ..ResultSet::from_maps(vec![row]) })
+            }
+        }
+    }
+
+    /// Sends a request to the API matching its operation and returns the response body.
+    async fn send_request(&self, request: &ElasticsearchRequest) -> Result<Value, DatabaseError> {
+        let refresh = request.refresh.as_deref().or(self.refresh.as_deref());
+        let index = request.index.as_str();
+        let indices = [index];
+        let body = request.body.clone();
+        let require_id = || request.id.as_deref()
+            .ok_or_else(|| DatabaseError::MissingField("id".to_string()));
+
+        // Searches and by-query writes run as tasks, which are tagged to be cancelled when the
+        // request is abandoned
+        let tag = query_tag();
+        let opaque_id = HeaderValue::from_str(&tag).map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        let opaque_id_header = HeaderName::from_static(OPAQUE_ID_HEADER);
+        let guard = matches!(
+            request.operation,
+            ElasticsearchOperation::Search | ElasticsearchOperation::UpdateByQuery | ElasticsearchOperation::DeleteByQuery
+        ).then(|| CancelGuard::new(cancel_tagged(self.client.clone(), tag)));
+
+        let response = match request.operation {
+            ElasticsearchOperation::Search => {
+                let parts = if index.is_empty() { SearchParts::None } else { SearchParts::Index(&indices) };
+                self.client.search(parts).header(opaque_id_header, opaque_id).body(body).send().await
+            }
+            ElasticsearchOperation::Index => {
+                let parts = match request.id.as_deref() {
+                    Some(id) => IndexParts::IndexId(index, id),
+                    None => IndexParts::Index(index),
+                };
+                let mut call = self.client.index(parts).body(body);
+                if let Some(refresh) = refresh {
+                    call = call.refresh(parse_refresh(refresh)?);
+                }
+                call.send().await
+            }
+            ElasticsearchOperation::Create => {
+                let mut call = self.client.create(CreateParts::IndexId(index, require_id()?)).body(body);
+                if let Some(refresh) = refresh {
+                    call = call.refresh(parse_refresh(refresh)?);
+                }
+                call.send().await
+            }
+            ElasticsearchOperation::Update => {
+                let mut call = self.client.update(UpdateParts::IndexId(index, require_id()?)).body(body);
+                if let Some(refresh) = refresh {
+                    call = call.refresh(parse_refresh(refresh)?);
+                }
+                call.send().await
+            }
+            ElasticsearchOperation::Delete => {
+                let mut call = self.client.delete(DeleteParts::IndexId(index, require_id()?));
+                if let Some(refresh) = refresh {
+                    call = call.refresh(parse_refresh(refresh)?);
+                }
+                call.send().await
+            }
+            // The by-query APIs only accept a boolean refresh, so `wait_for` refreshes as well.
+            ElasticsearchOperation::UpdateByQuery => {
+                let mut call = self.client.update_by_query(UpdateByQueryParts::Index(&indices))
+                    .header(opaque_id_header, opaque_id)
+                    .body(body);
+                if let Some(refresh) = refresh {
+                    call = call.refresh(refresh != "false");
+                }
+                call.send().await
+            }
+            ElasticsearchOperation::DeleteByQuery => {
+                let mut call = self.client.delete_by_query(DeleteByQueryParts::Index(&indices))
+                    .header(opaque_id_header, opaque_id)
+                    .body(body);
+                if let Some(refresh) = refresh {
+                    call = call.refresh(refresh != "false");
+                }
+                call.send().await
+            }
+        }.map_err(transport_error)?;
+        if let Some(guard) = guard {
+            guard.disarm();
+        }
+
+        read_response(response).await
     }
 }
 
@@ -144,33 +549,45 @@ impl DatabasePool for ElasticsearchPool {
         let query: serde_json::Value = serde_json::from_str(query)
             .map_err(|e| DatabaseError::InvalidQuery(e.to_string()))?;
 
-        let response = self.client
-            .search(SearchParts::None)
-            .body(query)
-            .send()
-            .await
-            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        // A bare query DSL body is searched across all indices
+        let request = match serde_json::from_value::<ElasticsearchRequest>(query.clone()) {
+            Ok(request) => request,
+            Err(_) => ElasticsearchRequest {
+                index: String::new(),
+                operation: ElasticsearchOperation::Search,
+                id: None,
+                refresh: None,
+                body: query,
+            },
+        };
+
+        self.execute_request(&request).await
+    }
 
-        let response_body = response.json::<serde_json::Value>().await
-            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+    async fn execute_builder(&self, builder: &QueryBuilder) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        self.execute_request(&self.builder_request(builder).await?).await
+    }
 
-        let hits = response_body["hits"]["hits"]
-            .as_array()
-            .ok_or_else(|| DatabaseError::QueryError("Invalid response format".to_string()))?;
-
-        Ok(hits
-            .iter()
-            .map(|hit| {
-                hit["_source"]
-                    .as_object()
-                    .map(|obj| {
-                        obj.iter()
-                            .map(|(k, v)| (k.clone(), v.clone()))
-                            .collect::<HashMap<String, Value>>()
-                    })
-                    .unwrap_or_default()
-            })
-            .collect())
+    async fn query_builder(&self, builder: &QueryBuilder) -> Result<ResultSet, DatabaseError> {
+        self.query_request(&self.builder_request(builder).await?).await
+    }
+
+    async fn execute_native(&self, query: &NativeQuery) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        self.execute_request(native_request(query)?).await
+    }
+
+    async fn query_native(&self, query: &NativeQuery) -> Result<ResultSet, DatabaseError> {
+        self.query_request(native_request(query)?).await
+    }
+
+    /// Searches that return every hit are paged through with a cursor, see `can_stream`.
+    fn stream_native<'a>(&'a self, query: &'a NativeQuery, batch_size: usize) -> RowStream<'a> {
+        match query {
+            NativeQuery::Elasticsearch(request) if can_stream(request) => {
+                self.search_results(&request.index, request.body.clone(), batch_size).boxed()
+            }
+            _ => collected(self.query_native(query), batch_size),
+        }
     }
 }
 
@@ -194,10 +611,9 @@ impl Database for ElasticsearchPool {
             .body(query)
             .send()
             .await
-            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+            .map_err(transport_error)?;
 
-        let response_body = response.json::<serde_json::Value>().await
-            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        let response_body = read_response(response).await?;
 
         Ok(vec![response_body])
     }
@@ -210,9 +626,9 @@ impl Database for ElasticsearchPool {
             .format("json")
             .send()
             .await
-            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+            .map_err(transport_error)?;
 
-        let indices: Vec<serde_json::Value> = response.json().await
+        let indices: Vec<serde_json::Value> = serde_json::from_value(read_response(response).await?)
             .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
 
         Ok(indices
@@ -228,10 +644,9 @@ impl Database for ElasticsearchPool {
             .get_mapping(elasticsearch::indices::IndicesGetMappingParts::Index(&[index]))
             .send()
             .await
-            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+            .map_err(transport_error)?;
 
-        let mappings: serde_json::Value = response.json().await
-            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        let mappings = read_response(response).await?;
 
         Ok(mappings[index]["mappings"]["properties"]
             .as_object()
@@ -245,11 +660,19 @@ impl Database for ElasticsearchPool {
             .get_mapping(elasticsearch::indices::IndicesGetMappingParts::Index(&[index]))
             .send()
             .await
-            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+            .map_err(transport_error)?;
 
-        let mappings: serde_json::Value = response.json().await
-            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        let mappings = read_response(response).await?;
 
         Ok(mappings[index]["mappings"].clone())
     }
+
+    async fn health_check(&self) -> Result<(), DatabaseError> {
+        let response = self.client.ping().send().await
+            .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
+        if !response.status_code().is_success() {
+            return Err(DatabaseError::ConnectionError(format!("Ping returned {}", response.status_code())));
+        }
+        Ok(())
+    }
 }
\ No newline at end of file
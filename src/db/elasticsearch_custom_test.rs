@@ -0,0 +1,114 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::elasticsearch_custom::{build_query, tagged_tasks, ElasticsearchOperation, ElasticsearchRequest};
+    use crate::db::query_builder::{DatabaseType, Field, Operator, QueryBuilder, QueryOperation, Table};
+    use crate::utils::errors::QueryBuilderError;
+    use serde_json::json;
+
+    fn build(builder: QueryBuilder) -> ElasticsearchRequest {
+        serde_json::from_str(&build_query(&builder).unwrap()).unwrap()
+    }
+
+    fn builder(operation: QueryOperation) -> QueryBuilder {
+        QueryBuilder::new(DatabaseType::Elasticsearch)
+            .table(Table::from("users"))
+            .operation(operation)
+    }
+
+    #[test]
+    fn test_search_targets_index() {
+        let request = build(builder(QueryOperation::Select).limit(5));
+        assert_eq!(request.index, "users");
+        assert_eq!(request.operation, ElasticsearchOperation::Search);
+        assert_eq!(request.body["size"], json!(5));
+    }
+
+    #[test]
+    fn test_insert_with_and_without_id() {
+        let request = build(builder(QueryOperation::Insert)
+            .field(Field::from("_id")).value(json!("u1"))
+            .field(Field::Name).value(json!("Ada")));
+        assert_eq!(request.operation, ElasticsearchOperation::Create);
+        assert_eq!(request.id.as_deref(), Some("u1"));
+        assert_eq!(request.body, json!({ "Name": "Ada" }));
+
+        let request = build(builder(QueryOperation::Insert).field(Field::Name).value(json!("Ada")));
+        assert_eq!(request.operation, ElasticsearchOperation::Index);
+        assert_eq!(request.id, None);
+    }
+
+    #[test]
+    fn test_update_routes_by_id_or_query() {
+        let request = build(builder(QueryOperation::Update)
+            .field(Field::Name).value(json!("Ada"))
+            .condition(Field::from("_id"), Operator::Eq, json!(7)));
+        assert_eq!(request.operation, ElasticsearchOperation::Update);
+        assert_eq!(request.id.as_deref(), Some("7"));
+        assert_eq!(request.body, json!({ "doc": { "Name": "Ada" } }));
+
+        let request = build(builder(QueryOperation::Update)
+            .field(Field::Name).value(json!("Ada"))
+            .condition(Field::Age, Operator::Gt, json!(30)));
+        assert_eq!(request.operation, ElasticsearchOperation::UpdateByQuery);
+        assert_eq!(request.body["script"]["params"]["doc"], json!({ "Name": "Ada" }));
+    }
+
+    #[test]
+    fn test_delete_routes_by_id_or_query() {
+        let request = build(builder(QueryOperation::Delete).condition(Field::from("_id"), Operator::Eq, json!("u1")));
+        assert_eq!(request.operation, ElasticsearchOperation::Delete);
+
+        let request = build(builder(QueryOperation::Delete).condition(Field::Age, Operator::Gt, json!(30)));
+        assert_eq!(request.operation, ElasticsearchOperation::DeleteByQuery);
+
+        let request = build(builder(QueryOperation::Delete).all());
+        assert_eq!(request.operation, ElasticsearchOperation::DeleteByQuery);
+        assert_eq!(request.body, json!({ "query": { "match_all": {} } }));
+    }
+
+    #[test]
+    fn test_unconditional_writes_require_all() {
+        assert!(matches!(
+            build_query(&builder(QueryOperation::Delete)),
+            Err(QueryBuilderError::InvalidQuery(_))
+        ), "A delete without conditions should not wipe the index");
+        assert!(matches!(
+            build_query(&builder(QueryOperation::Update).field(Field::Name).value(json!("Ada"))),
+            Err(QueryBuilderError::InvalidQuery(_))
+        ));
+
+        let request = build(builder(QueryOperation::Update).field(Field::Name).value(json!("Ada")).all());
+        assert_eq!(request.operation, ElasticsearchOperation::UpdateByQuery);
+        assert_eq!(request.body["query"], json!({ "match_all": {} }));
+    }
+
+    #[test]
+    fn test_writes_require_index() {
+        let builder = QueryBuilder::new(DatabaseType::Elasticsearch)
+            .operation(QueryOperation::Insert)
+            .field(Field::Name)
+            .value(json!("Ada"));
+        assert!(build_query(&builder).is_err());
+    }
+
+    #[test]
+    fn test_tagged_tasks_are_found_for_cancellation() {
+        let response = json!({
+            "nodes": {
+                "node-1": {
+                    "tasks": {
+                        "node-1:10": { "action": "indices:data/read/search", "cancellable": true, "headers": { "X-Opaque-Id": "q1" } },
+                        "node-1:11": { "action": "indices:data/read/search[phase/query]", "cancellable": true, "parent_task_id": "node-1:10", "headers": { "X-Opaque-Id": "q1" } },
+                        "node-1:12": { "action": "indices:data/read/search", "cancellable": true, "headers": { "X-Opaque-Id": "q2" } },
+                        "node-1:13": { "action": "cluster:monitor/tasks/lists", "cancellable": false, "headers": { "X-Opaque-Id": "q1" } }
+                    }
+                },
+                "node-2": { "tasks": {} }
+            }
+        });
+
+        assert_eq!(tagged_tasks(&response, "q1"), vec!["node-1:10".to_string()]);
+        assert!(tagged_tasks(&response, "q3").is_empty());
+        assert!(tagged_tasks(&json!({}), "q1").is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+//! Query DSL generation for Elasticsearch builder queries.
+//! Conditions become `term`/`terms`/`range`/`wildcard`/`exists`/`match` clauses placed in the
+//! `filter` or `must_not` section of a `bool` query. When the index mapping is known, exact
+//! matches on `text` fields use their `keyword` sub-field, or `match` when there is none.
+use std::collections::HashMap;
+use serde_json::{json, Map, Value};
+use crate::db::query_builder::{Condition, Operator};
+
+/// The mapping of a single (possibly nested, dot separated) field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMapping {
+    pub field_type: String,
+    /// A `keyword` multi-field of a `text` field, e.g. `name.keyword`.
+    pub keyword_field: Option<String>,
+}
+
+/// Field mappings of an index, keyed by dot separated field path.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FieldMappings {
+    fields: HashMap<String, FieldMapping>,
+}
+
+impl FieldMappings {
+    /// Builds the mappings from the `properties` object of an index mapping.
+    pub fn from_properties(properties: &Value) -> Self {
+        let mut mappings = FieldMappings::default();
+        mappings.collect(properties, "");
+        mappings
+    }
+
+    /// Builds the mappings from a `GET <index>/_mapping` response, merging every index in it.
+    pub fn from_mapping_response(response: &Value) -> Self {
+        let mut mappings = FieldMappings::default();
+        if let Some(indices) = response.as_object() {
+            for index in indices.values() {
+                mappings.collect(&index["mappings"]["properties"], "");
+            }
+        }
+        mappings
+    }
+
+    fn collect(&mut self, properties: &Value, prefix: &str) {
+        let Some(properties) = properties.as_object() else { return };
+        for (name, mapping) in properties {
+            let path = if prefix.is_empty() { name.clone() } else { format!("{}.{}", prefix, name) };
+            if mapping.get("properties").is_some() {
+                self.collect(&mapping["properties"], &path);
+                continue;
+            }
+
+            let keyword_field = mapping["fields"].as_object().and_then(|fields| {
+                fields.iter()
+                    .find(|(_, sub)| sub["type"] == "keyword")
+                    .map(|(sub_name, _)| format!("{}.{}", path, sub_name))
+            });
+            self.fields.insert(path, FieldMapping {
+                field_type: mapping["type"].as_str().unwrap_or("object").to_string(),
+                keyword_field,
+            });
+        }
+    }
+
+    pub fn get(&self, field: &str) -> Option<&FieldMapping> {
+        self.fields.get(field)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// The field to use for exact matching, sorting and aggregations. `None` for `text`
+    /// fields without a `keyword` sub-field, which can only be searched with `match`.
+    pub fn exact_field(&self, field: &str) -> Option<String> {
+        match self.fields.get(field) {
+            Some(mapping) if mapping.field_type == "text" => mapping.keyword_field.clone(),
+            _ => Some(field.to_string()),
+        }
+    }
+
+    /// Like `exact_field`, but falls back to the field itself.
+    pub fn sort_field(&self, field: &str) -> String {
+        self.exact_field(field).unwrap_or_else(|| field.to_string())
+    }
+}
+
+/// Converts a SQL `LIKE` pattern into a `wildcard` pattern: `%` becomes `*`, `_` becomes `?`,
+/// and characters that are special to `wildcard` are escaped. `\%` and `\_` match literally.
+pub fn like_to_wildcard(pattern: &str) -> String {
+    fn push_literal(out: &mut String, c: char) {
+        if matches!(c, '*' | '?' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+
+    let mut out = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => push_literal(&mut out, chars.next().unwrap_or('\\')),
+            '%' => out.push('*'),
+            '_' => out.push('?'),
+            c => push_literal(&mut out, c),
+        }
+    }
+    out
+}
+
+fn as_array(value: &Value) -> Vec<Value> {
+    match value {
+        Value::Array(values) => values.clone(),
+        value => vec![value.clone()],
+    }
+}
+
+/// Matches any of `values` on a field without an exact sub-field.
+fn match_any(field: &str, values: &[Value]) -> Value {
+    json!({
+        "bool": {
+            "should": values.iter().map(|v| json!({ "match": { field: { "query": v, "operator": "and" } } })).collect::<Vec<_>>(),
+            "minimum_should_match": 1
+        }
+    })
+}
+
+/// Builds the `query` clause matching all conditions, or `match_all` when there are none.
+pub fn build_query_clause(conditions: &[Condition], mappings: &FieldMappings) -> Value {
+    let mut filter: Vec<Value> = Vec::new();
+    let mut must_not: Vec<Value> = Vec::new();
+    // Range conditions on the same field are merged into one `range` clause.
+    let mut ranges: Vec<(String, Map<String, Value>)> = Vec::new();
+
+    for condition in conditions {
+        let field = condition.field.as_str();
+        let exact = mappings.exact_field(field);
+        let value = &condition.value;
+
+        match condition.operator {
+            Operator::Eq if value.is_null() => must_not.push(json!({ "exists": { "field": field } })),
+            Operator::Ne if value.is_null() => filter.push(json!({ "exists": { "field": field } })),
+            Operator::Eq => filter.push(match exact {
+                Some(exact) => json!({ "term": { exact: value } }),
+                None => json!({ "match": { field: { "query": value, "operator": "and" } } }),
+            }),
+            Operator::Ne => must_not.push(match exact {
+                Some(exact) => json!({ "term": { exact: value } }),
+                None => json!({ "match": { field: { "query": value, "operator": "and" } } }),
+            }),
+            Operator::In | Operator::NotIn => {
+                let values = as_array(value);
+                let clause = match exact {
+                    Some(exact) => json!({ "terms": { exact: values } }),
+                    None => match_any(field, &values),
+                };
+                if matches!(condition.operator, Operator::In) { filter.push(clause) } else { must_not.push(clause) }
+            }
+            Operator::Like => {
+                let pattern = like_to_wildcard(value.as_str().unwrap_or_default());
+                filter.push(json!({
+                    "wildcard": { mappings.sort_field(field): { "value": pattern, "case_insensitive": true } }
+                }));
+            }
+            Operator::Gt | Operator::Gte | Operator::Lt | Operator::Lte => {
+                let key = match condition.operator {
+                    Operator::Gt => "gt",
+                    Operator::Gte => "gte",
+                    Operator::Lt => "lt",
+                    _ => "lte",
+                };
+                let target = mappings.sort_field(field);
+                match ranges.iter_mut().find(|(f, _)| *f == target) {
+                    Some((_, bounds)) => { bounds.insert(key.to_string(), value.clone()); }
+                    None => ranges.push((target, [(key.to_string(), value.clone())].into_iter().collect())),
+                }
+            }
+        }
+    }
+
+    filter.extend(ranges.into_iter().map(|(field, bounds)| json!({ "range": { field: bounds } })));
+
+    if filter.is_empty() && must_not.is_empty() {
+        return json!({ "match_all": {} });
+    }
+
+    let mut query = Map::new();
+    if !filter.is_empty() {
+        query.insert("filter".to_string(), json!(filter));
+    }
+    if !must_not.is_empty() {
+        query.insert("must_not".to_string(), json!(must_not));
+    }
+    json!({ "bool": query })
+}
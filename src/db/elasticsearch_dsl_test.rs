@@ -0,0 +1,93 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::elasticsearch_dsl::{build_query_clause, like_to_wildcard, FieldMappings};
+    use crate::db::query_builder::{Condition, DatabaseType, Field, Operator, QueryBuilder};
+    use serde_json::{json, Value};
+
+    fn conditions(conditions: Vec<(&str, Operator, Value)>) -> Vec<Condition> {
+        conditions.into_iter()
+            .fold(QueryBuilder::new(DatabaseType::Elasticsearch), |b, (f, o, v)| b.condition(Field::from(f), o, v))
+            .conditions
+    }
+
+    fn mappings() -> FieldMappings {
+        FieldMappings::from_properties(&json!({
+            "title": { "type": "text", "fields": { "raw": { "type": "keyword" } } },
+            "body": { "type": "text" },
+            "status": { "type": "keyword" },
+            "author": { "properties": { "name": { "type": "text", "fields": { "keyword": { "type": "keyword" } } } } }
+        }))
+    }
+
+    #[test]
+    fn test_empty_conditions_match_all() {
+        assert_eq!(build_query_clause(&[], &FieldMappings::default()), json!({ "match_all": {} }));
+    }
+
+    #[test]
+    fn test_term_terms_and_must_not() {
+        let query = build_query_clause(&conditions(vec![
+            ("status", Operator::Eq, json!("active")),
+            ("role", Operator::Ne, json!("admin")),
+            ("tag", Operator::In, json!(["a", "b"])),
+            ("team", Operator::NotIn, json!("x")),
+        ]), &FieldMappings::default());
+
+        assert_eq!(query, json!({
+            "bool": {
+                "filter": [
+                    { "term": { "status": "active" } },
+                    { "terms": { "tag": ["a", "b"] } }
+                ],
+                "must_not": [
+                    { "term": { "role": "admin" } },
+                    { "terms": { "team": ["x"] } }
+                ]
+            }
+        }));
+    }
+
+    #[test]
+    fn test_ranges_are_merged_per_field() {
+        let query = build_query_clause(&conditions(vec![
+            ("age", Operator::Gte, json!(18)),
+            ("age", Operator::Lt, json!(65)),
+        ]), &FieldMappings::default());
+
+        assert_eq!(query, json!({ "bool": { "filter": [ { "range": { "age": { "gte": 18, "lt": 65 } } } ] } }));
+    }
+
+    #[test]
+    fn test_null_values_use_exists() {
+        let query = build_query_clause(&conditions(vec![
+            ("deleted_at", Operator::Eq, Value::Null),
+            ("email", Operator::Ne, Value::Null),
+        ]), &FieldMappings::default());
+
+        assert_eq!(query["bool"]["filter"], json!([{ "exists": { "field": "email" } }]));
+        assert_eq!(query["bool"]["must_not"], json!([{ "exists": { "field": "deleted_at" } }]));
+    }
+
+    #[test]
+    fn test_like_to_wildcard() {
+        assert_eq!(like_to_wildcard("jo%n_"), "jo*n?");
+        assert_eq!(like_to_wildcard(r"100\%*"), r"100%\*");
+        let query = build_query_clause(&conditions(vec![("status", Operator::Like, json!("act%"))]), &FieldMappings::default());
+        assert_eq!(query["bool"]["filter"][0], json!({ "wildcard": { "status": { "value": "act*", "case_insensitive": true } } }));
+    }
+
+    #[test]
+    fn test_text_fields_use_keyword_or_match() {
+        let query = build_query_clause(&conditions(vec![
+            ("title", Operator::Eq, json!("Rust")),
+            ("body", Operator::Eq, json!("hello world")),
+            ("author.name", Operator::In, json!(["Ada"])),
+        ]), &mappings());
+
+        assert_eq!(query["bool"]["filter"], json!([
+            { "term": { "title.raw": "Rust" } },
+            { "match": { "body": { "query": "hello world", "operator": "and" } } },
+            { "terms": { "author.name.keyword": ["Ada"] } }
+        ]));
+    }
+}
@@ -0,0 +1,273 @@
+//! Deep pagination for Elasticsearch searches. `from`/`size` paging stops at
+//! `index.max_result_window` (10k hits by default), so cursors walk a point in time (PIT) with
+//! `search_after` instead, falling back to the scroll API on clusters without PIT support (before 7.10).
+use std::collections::HashMap;
+use elasticsearch::{ClearScrollParts, OpenPointInTimeParts, ScrollParts, SearchParts};
+use futures::{future, Stream, StreamExt, TryStreamExt};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::db::elasticsearch_custom::{read_response, transport_error, ElasticsearchOperation, ElasticsearchPool, ElasticsearchRequest};
+use crate::db::elasticsearch_results::{hits_to_result, hits_to_rows, hits_total};
+use crate::db::result_set::ResultSet;
+use crate::utils::errors::DatabaseError;
+
+/// How long a point in time or scroll context is kept open between two pages.
+pub const CURSOR_KEEP_ALIVE: &str = "2m";
+
+/// Where a cursor stands. `search_after` holds the sort values of the last hit returned and
+/// `scroll_id` is `None` until the first page opens the scroll context.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CursorPosition {
+    PointInTime { pit_id: String, search_after: Option<Value> },
+    Scroll { scroll_id: Option<String> },
+}
+
+/// A resumable position in the hits of a search. Cursors serialize, so a client can hand the
+/// cursor of a page back to fetch the next one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchCursor {
+    pub index: String,
+    /// The search body without `from`; its `size` is the page size.
+    pub body: Value,
+    pub position: CursorPosition,
+}
+
+impl SearchCursor {
+    fn page_size(&self) -> usize {
+        self.body["size"].as_u64().unwrap_or(0) as usize
+    }
+}
+
+/// A page of hits. `cursor` fetches the following page and is `None` once all hits were returned,
+/// in which case the point in time or scroll context has already been released.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchPage {
+    pub rows: Vec<HashMap<String, Value>>,
+    pub total: Option<u64>,
+    pub cursor: Option<SearchCursor>,
+}
+
+/// Prepares a search body for cursor paging: `from` is dropped and `size` becomes the page size.
+pub fn page_body(body: Value, page_size: usize) -> Result<Value, DatabaseError> {
+    let mut body = match body {
+        Value::Object(body) => body,
+        Value::Null => serde_json::Map::new(),
+        _ => return Err(DatabaseError::InvalidQuery("Search body must be a JSON object".to_string())),
+    };
+    body.remove("from");
+    body.insert("size".to_string(), json!(page_size.max(1)));
+    Ok(Value::Object(body))
+}
+
+/// Whether a native search can be streamed with a cursor: it names an index and neither limits
+/// its hits with `size` or `from` nor computes aggregations, which every page would repeat.
+pub fn can_stream(request: &ElasticsearchRequest) -> bool {
+    request.operation == ElasticsearchOperation::Search
+        && !request.index.is_empty()
+        && ["size", "from", "aggs", "aggregations"].iter().all(|key| request.body.get(key).is_none())
+}
+
+/// The body of a PIT search. Without an explicit sort, hits are returned in `_shard_doc`
+/// order, which is the cheapest order to page through.
+pub fn pit_search_body(body: &Value, pit_id: &str, search_after: Option<&Value>) -> Value {
+    let mut body = body.clone();
+    body["pit"] = json!({ "id": pit_id, "keep_alive": CURSOR_KEEP_ALIVE });
+    if body.get("sort").is_none() {
+        body["sort"] = json!([{ "_shard_doc": "asc" }]);
+    }
+    if let Some(search_after) = search_after {
+        body["search_after"] = search_after.clone();
+    }
+    body
+}
+
+/// The body of the initial scroll search, sorted by `_doc` unless a sort was given.
+pub fn scroll_search_body(body: &Value) -> Value {
+    let mut body = body.clone();
+    if body.get("sort").is_none() {
+        body["sort"] = json!(["_doc"]);
+    }
+    body
+}
+
+/// The sort values of the last hit, used as `search_after` for the next page.
+pub fn last_sort_values(response: &Value) -> Option<Value> {
+    response["hits"]["hits"].as_array()?.last()?.get("sort").cloned()
+}
+
+enum PageState {
+    Start(Value),
+    Next(SearchCursor),
+    Done,
+}
+
+impl ElasticsearchPool {
+    async fn open_point_in_time(&self, index: &str) -> Result<String, DatabaseError> {
+        let response = self.client()
+            .open_point_in_time(OpenPointInTimeParts::Index(&[index]))
+            .keep_alive(CURSOR_KEEP_ALIVE)
+            .send()
+            .await
+            .map_err(transport_error)?;
+        read_response(response).await?["id"].as_str()
+            .map(String::from)
+            .ok_or_else(|| DatabaseError::QueryError("Point in time response has no id".to_string()))
+    }
+
+    /// Opens a cursor over all hits of `body` in `index`, using a point in time when the cluster
+    /// supports it and a scroll otherwise.
+    pub async fn open_cursor(&self, index: &str, body: Value, page_size: usize) -> Result<SearchCursor, DatabaseError> {
+        if index.is_empty() {
+            return Err(DatabaseError::MissingField("index".to_string()));
+        }
+        let body = page_body(body, page_size)?;
+
+        let position = match self.open_point_in_time(index).await {
+            Ok(pit_id) => CursorPosition::PointInTime { pit_id, search_after: None },
+            Err(e) => {
+                warn!("Point in time is unavailable for {}, paging with scroll: {}", index, e);
+                CursorPosition::Scroll { scroll_id: None }
+            }
+        };
+        Ok(SearchCursor { index: index.to_string(), body, position })
+    }
+
+    /// Fetches the page at `cursor`. The context is released after the last page.
+    pub async fn next_page(&self, cursor: SearchCursor) -> Result<SearchPage, DatabaseError> {
+        let (response, cursor) = self.fetch_page(cursor).await?;
+        Ok(SearchPage {
+            rows: hits_to_rows(&response).unwrap_or_default(),
+            total: hits_total(&response).map(|(value, _)| value),
+            cursor,
+        })
+    }
+
+    /// Fetches the search response at `cursor`, and the cursor of the following page unless
+    /// this was the last one.
+    async fn fetch_page(&self, cursor: SearchCursor) -> Result<(Value, Option<SearchCursor>), DatabaseError> {
+        let page_size = cursor.page_size();
+        let SearchCursor { index, body, position } = cursor;
+
+        let (response, position) = match position {
+            CursorPosition::PointInTime { pit_id, search_after } => {
+                // PIT searches must not name an index, the PIT already pins it
+                let response = self.client()
+                    .search(SearchParts::None)
+                    .body(pit_search_body(&body, &pit_id, search_after.as_ref()))
+                    .send()
+                    .await
+                    .map_err(transport_error)?;
+                let response = read_response(response).await?;
+                // The PIT id may change between searches, always continue with the latest
+                let pit_id = response["pit_id"].as_str().map(String::from).unwrap_or(pit_id);
+                let search_after = last_sort_values(&response).or(search_after);
+                (response, CursorPosition::PointInTime { pit_id, search_after })
+            }
+            CursorPosition::Scroll { scroll_id: None } => {
+                let response = self.client()
+                    .search(SearchParts::Index(&[index.as_str()]))
+                    .scroll(CURSOR_KEEP_ALIVE)
+                    .body(scroll_search_body(&body))
+                    .send()
+                    .await
+                    .map_err(transport_error)?;
+                let response = read_response(response).await?;
+                let scroll_id = response["_scroll_id"].as_str().map(String::from);
+                (response, CursorPosition::Scroll { scroll_id })
+            }
+            CursorPosition::Scroll { scroll_id: Some(scroll_id) } => {
+                let response = self.client()
+                    .scroll(ScrollParts::None)
+                    .body(json!({ "scroll": CURSOR_KEEP_ALIVE, "scroll_id": scroll_id }))
+                    .send()
+                    .await
+                    .map_err(transport_error)?;
+                let response = read_response(response).await?;
+                let scroll_id = response["_scroll_id"].as_str().map(String::from).unwrap_or(scroll_id);
+                (response, CursorPosition::Scroll { scroll_id: Some(scroll_id) })
+            }
+        };
+
+        let hits = response["hits"]["hits"].as_array().map(Vec::len)
+            .ok_or_else(|| DatabaseError::QueryError("Invalid response format".to_string()))?;
+        let cursor = SearchCursor { index, body, position };
+
+        if hits < page_size {
+            if let Err(e) = self.close_cursor(&cursor).await {
+                warn!("Could not release search cursor on {}: {}", cursor.index, e);
+            }
+            return Ok((response, None));
+        }
+        Ok((response, Some(cursor)))
+    }
+
+    /// Releases the point in time or scroll context of a cursor that is abandoned before its
+    /// last page. Unreleased contexts expire after `CURSOR_KEEP_ALIVE`.
+    pub async fn close_cursor(&self, cursor: &SearchCursor) -> Result<(), DatabaseError> {
+        let response = match &cursor.position {
+            CursorPosition::PointInTime { pit_id, .. } => {
+                self.client().close_point_in_time().body(json!({ "id": pit_id })).send().await
+            }
+            CursorPosition::Scroll { scroll_id: Some(scroll_id) } => {
+                self.client().clear_scroll(ClearScrollParts::None).body(json!({ "scroll_id": [scroll_id] })).send().await
+            }
+            CursorPosition::Scroll { scroll_id: None } => return Ok(()),
+        }.map_err(transport_error)?;
+        read_response(response).await.map(|_| ())
+    }
+
+    /// Walks every hit of a search, yielding one batch of rows per page. Dropping the stream
+    /// early leaves its context to expire after `CURSOR_KEEP_ALIVE`.
+    pub fn search_pages<'a>(&'a self, index: &'a str, body: Value, page_size: usize)
+        -> impl Stream<Item = Result<Vec<HashMap<String, Value>>, DatabaseError>> + 'a
+    {
+        self.search_responses(index, body, page_size)
+            .map_ok(|response| hits_to_rows(&response).unwrap_or_default())
+            .try_filter(|rows| future::ready(!rows.is_empty()))
+    }
+
+    /// Like `search_pages`, yielding the raw `hits.hits` of each page.
+    pub fn search_hits<'a>(&'a self, index: &'a str, body: Value, page_size: usize)
+        -> impl Stream<Item = Result<Vec<Value>, DatabaseError>> + 'a
+    {
+        self.search_responses(index, body, page_size)
+            .map_ok(|mut response| match response["hits"]["hits"].take() {
+                Value::Array(hits) => hits,
+                _ => Vec::new(),
+            })
+            .try_filter(|hits| future::ready(!hits.is_empty()))
+    }
+
+    /// Like `search_pages`, yielding each page as a result. The first page is always yielded and
+    /// carries the total.
+    pub fn search_results<'a>(&'a self, index: &'a str, body: Value, page_size: usize)
+        -> impl Stream<Item = Result<ResultSet, DatabaseError>> + 'a
+    {
+        self.search_responses(index, body, page_size).enumerate().map(|(page, response)| -> Result<ResultSet, DatabaseError> {
+            let result = hits_to_result(&response?).unwrap_or_default();
+            let total_rows = if page == 0 { result.total_rows } else { None };
+            Ok(ResultSet { truncated: false, total_rows, ..result })
+        })
+    }
+
+    /// The search responses of every page, ending after the last page with hits.
+    fn search_responses<'a>(&'a self, index: &'a str, body: Value, page_size: usize)
+        -> impl Stream<Item = Result<Value, DatabaseError>> + 'a
+    {
+        futures::stream::try_unfold(PageState::Start(body), move |state| async move {
+            let (cursor, first) = match state {
+                PageState::Start(body) => (self.open_cursor(index, body, page_size).await?, true),
+                PageState::Next(cursor) => (cursor, false),
+                PageState::Done => return Ok::<_, DatabaseError>(None),
+            };
+
+            let (response, cursor) = self.fetch_page(cursor).await?;
+            if !first && response["hits"]["hits"].as_array().is_some_and(Vec::is_empty) {
+                return Ok(None);
+            }
+            Ok(Some((response, cursor.map_or(PageState::Done, PageState::Next))))
+        })
+    }
+}
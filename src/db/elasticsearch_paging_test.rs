@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::elasticsearch_custom::{ElasticsearchOperation, ElasticsearchRequest};
+    use crate::db::elasticsearch_paging::{
+        can_stream, last_sort_values, page_body, pit_search_body, scroll_search_body, CursorPosition, SearchCursor, CURSOR_KEEP_ALIVE,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn test_page_body_drops_from_and_sets_size() {
+        let body = page_body(json!({ "query": { "match_all": {} }, "from": 20000, "size": 10 }), 500).unwrap();
+        assert_eq!(body, json!({ "query": { "match_all": {} }, "size": 500 }));
+        assert_eq!(page_body(json!(null), 0).unwrap(), json!({ "size": 1 }));
+        assert!(page_body(json!([1, 2]), 10).is_err());
+    }
+
+    #[test]
+    fn test_pit_search_body_adds_pit_tiebreak_and_search_after() {
+        let body = json!({ "query": { "match_all": {} }, "size": 100 });
+        assert_eq!(pit_search_body(&body, "abc", None), json!({
+            "query": { "match_all": {} },
+            "size": 100,
+            "pit": { "id": "abc", "keep_alive": CURSOR_KEEP_ALIVE },
+            "sort": [{ "_shard_doc": "asc" }]
+        }));
+
+        let sorted = json!({ "size": 100, "sort": [{ "timestamp": { "order": "desc" } }] });
+        let next = pit_search_body(&sorted, "abc", Some(&json!([1700000000000u64, 42])));
+        assert_eq!(next["sort"], sorted["sort"]);
+        assert_eq!(next["search_after"], json!([1700000000000u64, 42]));
+    }
+
+    #[test]
+    fn test_scroll_search_body_defaults_to_doc_order() {
+        assert_eq!(scroll_search_body(&json!({ "size": 10 }))["sort"], json!(["_doc"]));
+        assert_eq!(scroll_search_body(&json!({ "sort": ["timestamp"] }))["sort"], json!(["timestamp"]));
+    }
+
+    #[test]
+    fn test_last_sort_values() {
+        let response = json!({ "hits": { "hits": [{ "_id": "1", "sort": [1, 7] }, { "_id": "2", "sort": [2, 9] }] } });
+        assert_eq!(last_sort_values(&response), Some(json!([2, 9])));
+        assert_eq!(last_sort_values(&json!({ "hits": { "hits": [] } })), None);
+    }
+
+    #[test]
+    fn test_cursor_round_trips_through_json() {
+        let cursor = SearchCursor {
+            index: "logs".to_string(),
+            body: json!({ "size": 100 }),
+            position: CursorPosition::PointInTime { pit_id: "abc".to_string(), search_after: Some(json!([3])) },
+        };
+        let encoded = serde_json::to_value(&cursor).unwrap();
+        assert_eq!(encoded["position"]["type"], json!("point_in_time"));
+        assert_eq!(serde_json::from_value::<SearchCursor>(encoded).unwrap(), cursor);
+    }
+
+    #[test]
+    fn test_only_unlimited_searches_are_streamed() {
+        let search = |index: &str, body| ElasticsearchRequest {
+            index: index.to_string(),
+            operation: ElasticsearchOperation::Search,
+            id: None,
+            refresh: None,
+            body,
+        };
+        assert!(can_stream(&search("logs", json!({ "query": { "match_all": {} } }))));
+        assert!(!can_stream(&search("logs", json!({ "query": { "match_all": {} }, "size": 10 }))), "An explicit size limits the hits");
+        assert!(!can_stream(&search("logs", json!({ "aggs": { "levels": { "terms": { "field": "level" } } } }))));
+        assert!(!can_stream(&search("", json!({}))), "A cursor needs an index");
+        assert!(!can_stream(&ElasticsearchRequest { operation: ElasticsearchOperation::DeleteByQuery, ..search("logs", json!({})) }));
+    }
+}
@@ -0,0 +1,230 @@
+//! Conversion of Elasticsearch search responses into rows.
+//! Hits become one row per document: the `_source` fields plus the `_id`, `_index`, `_score`,
+//! `_sort` and `_highlight` metadata and the `_total`/`_total_relation` of the search.
+//! Aggregations are flattened into one row per leaf bucket: bucket keys are stored under the
+//! aggregation name, document counts under `<name>.doc_count` and metrics under their name.
+//! A bucket whose sub-aggregation has no buckets still gets a row, with nulls for that
+//! sub-aggregation.
+//! As a `ResultSet`, hits keep their `_source` field order, aggregation columns follow the order
+//! of the response, and the total goes to `total_rows`.
+use std::collections::HashMap;
+use serde_json::{Map, Value};
+use crate::db::result_set::{Cell, ResultSet, ResultSetBuilder};
+
+/// Columns added to hit rows next to the `_source` fields.
+pub const HIT_METADATA_COLUMNS: [&str; 7] = ["_id", "_index", "_score", "_sort", "_highlight", "_total", "_total_relation"];
+
+/// The hit keys copied next to the `_source` fields, and their column names.
+const HIT_METADATA: [(&str, &str); 5] = [("_id", "_id"), ("_index", "_index"), ("_score", "_score"), ("sort", "_sort"), ("highlight", "_highlight")];
+
+/// Keys of a bucket object that describe the bucket itself rather than a sub-aggregation.
+const BUCKET_KEYS: [&str; 8] = ["key", "key_as_string", "doc_count", "from", "from_as_string", "to", "to_as_string", "doc_count_error_upper_bound"];
+
+/// Converts a search response into rows, preferring aggregations over hits when present.
+pub fn search_response_to_rows(body: &Value) -> Option<Vec<HashMap<String, Value>>> {
+    match body.get("aggregations") {
+        Some(aggregations) if aggregations.as_object().is_some_and(|a| !a.is_empty()) => {
+            Some(aggregations_to_rows(aggregations))
+        }
+        _ => hits_to_rows(body),
+    }
+}
+
+/// Converts a search response into a result, see `search_response_to_rows`.
+pub fn search_response_to_result(body: &Value) -> Option<ResultSet> {
+    match body.get("aggregations") {
+        Some(aggregations) if aggregations.as_object().is_some_and(|a| !a.is_empty()) => {
+            Some(aggregations_to_result(aggregations))
+        }
+        _ => hits_to_result(body),
+    }
+}
+
+/// Returns `hits.total` as `(value, relation)`. The relation is `gte` when the count is a lower bound.
+pub fn hits_total(body: &Value) -> Option<(u64, String)> {
+    match &body["hits"]["total"] {
+        Value::Number(n) => Some((n.as_u64()?, "eq".to_string())),
+        Value::Object(total) => Some((
+            total.get("value")?.as_u64()?,
+            total.get("relation").and_then(Value::as_str).unwrap_or("eq").to_string(),
+        )),
+        _ => None,
+    }
+}
+
+/// Converts `hits.hits` into rows. Returns `None` when the response has no hits array.
+pub fn hits_to_rows(body: &Value) -> Option<Vec<HashMap<String, Value>>> {
+    let hits = body["hits"]["hits"].as_array()?;
+    let total = hits_total(body);
+
+    Some(hits.iter().map(|hit| {
+        let mut row: HashMap<String, Value> = hit["_source"].as_object()
+            .map(|obj| obj.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+            .unwrap_or_default();
+
+        for (key, column) in HIT_METADATA {
+            if let Some(value) = hit.get(key) {
+                row.insert(column.to_string(), value.clone());
+            }
+        }
+        if let Some((value, relation)) = &total {
+            row.insert("_total".to_string(), Value::from(*value));
+            row.insert("_total_relation".to_string(), Value::from(relation.clone()));
+        }
+        row
+    }).collect())
+}
+
+/// Converts `hits.hits` into a result with the metadata columns first, then the `_source` fields
+/// in the order they first appear. It is truncated when the search matched more hits than it
+/// returned; `total_rows` is only set when the total is exact.
+pub fn hits_to_result(body: &Value) -> Option<ResultSet> {
+    let hits = body["hits"]["hits"].as_array()?;
+    let mut builder = ResultSetBuilder::default();
+    for hit in hits {
+        let metadata = HIT_METADATA.iter()
+            .filter_map(|(key, column)| hit.get(*key).map(|value| Cell::json(*column, value.clone())));
+        let source = hit["_source"].as_object().into_iter().flatten()
+            .map(|(name, value)| Cell::json(name.clone(), value.clone()));
+        builder.push_row(metadata.chain(source));
+    }
+
+    let mut result = builder.finish();
+    if let Some((total, relation)) = hits_total(body) {
+        result.truncated = (hits.len() as u64) < total;
+        result.total_rows = (relation == "eq").then_some(total);
+    }
+    Some(result)
+}
+
+/// Splits a hit into its document id and `_source` document. Hit metadata never mixes with the
+/// document, so a document keeps its own fields named like metadata columns, e.g. `_score`.
+pub fn hit_document(hit: &Value) -> (Option<String>, Map<String, Value>) {
+    let id = hit.get("_id").and_then(Value::as_str).map(String::from);
+    let document = hit.get("_source").and_then(Value::as_object).cloned().unwrap_or_default();
+    (id, document)
+}
+
+/// Flattens an `aggregations` object into rows.
+pub fn aggregations_to_rows(aggregations: &Value) -> Vec<HashMap<String, Value>> {
+    flatten(aggregations, &Map::new()).into_iter().map(|row| row.into_iter().collect()).collect()
+}
+
+/// Flattens an `aggregations` object into a result whose columns appear in the order of the
+/// response, the metrics of each level before the buckets below it.
+pub fn aggregations_to_result(aggregations: &Value) -> ResultSet {
+    let mut builder = ResultSetBuilder::default();
+    for row in flatten(aggregations, &Map::new()) {
+        builder.push_row(row.into_iter().map(|(name, value)| Cell::json(name, value)));
+    }
+    builder.finish()
+}
+
+/// Rows are ordered maps, so columns keep the order they were added in.
+fn flatten(aggregations: &Value, base: &Map<String, Value>) -> Vec<Map<String, Value>> {
+    let Some(aggregations) = aggregations.as_object() else {
+        return vec![base.clone()];
+    };
+
+    let mut row = base.clone();
+    let mut bucketed: Vec<(&String, &Value)> = Vec::new();
+    for (name, aggregation) in aggregations {
+        if is_bucket_aggregation(aggregation) {
+            bucketed.push((name, aggregation));
+        } else {
+            add_metric(&mut row, name, aggregation);
+        }
+    }
+
+    if bucketed.is_empty() {
+        return vec![row];
+    }
+
+    let mut rows = Vec::new();
+    for (name, aggregation) in bucketed {
+        if aggregation.get("buckets").is_none() {
+            // Single bucket aggregations such as `filter`, `nested` or `global`
+            let mut bucket_row = row.clone();
+            bucket_row.insert(format!("{}.doc_count", name), aggregation["doc_count"].clone());
+            rows.extend(flatten(&sub_aggregations(aggregation), &bucket_row));
+            continue;
+        }
+
+        let buckets = buckets(aggregation);
+        if buckets.is_empty() && !row.is_empty() {
+            // Keep the enclosing bucket or metrics, with nulls for this aggregation
+            let mut empty_row = row.clone();
+            empty_row.insert(name.clone(), Value::Null);
+            empty_row.insert(format!("{}.doc_count", name), Value::Null);
+            rows.push(empty_row);
+        }
+        for (key, bucket) in buckets {
+            let mut bucket_row = row.clone();
+            match key {
+                // Composite aggregations have one key per source
+                Value::Object(parts) => {
+                    for (part, value) in parts {
+                        bucket_row.insert(format!("{}.{}", name, part), value);
+                    }
+                }
+                key => {
+                    bucket_row.insert(name.clone(), key);
+                }
+            }
+            bucket_row.insert(format!("{}.doc_count", name), bucket["doc_count"].clone());
+            rows.extend(flatten(&sub_aggregations(bucket), &bucket_row));
+        }
+    }
+    rows
+}
+
+fn is_bucket_aggregation(aggregation: &Value) -> bool {
+    aggregation.get("buckets").is_some()
+        || (aggregation.get("doc_count").is_some() && sub_aggregations(aggregation).as_object().is_some_and(|s| !s.is_empty()))
+}
+
+/// The bucket key (preferring the formatted `key_as_string`) and the bucket of each bucket.
+fn buckets(aggregation: &Value) -> Vec<(Value, &Value)> {
+    match &aggregation["buckets"] {
+        Value::Array(buckets) => buckets.iter()
+            .map(|bucket| {
+                let key = bucket.get("key_as_string").or_else(|| bucket.get("key")).cloned().unwrap_or(Value::Null);
+                (key, bucket)
+            })
+            .collect(),
+        // Keyed buckets, e.g. `filters` or `range` with `keyed: true`
+        Value::Object(buckets) => buckets.iter().map(|(key, bucket)| (Value::from(key.clone()), bucket)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn sub_aggregations(bucket: &Value) -> Value {
+    let subs: Map<String, Value> = bucket.as_object()
+        .map(|obj| obj.iter()
+            .filter(|(k, v)| v.is_object() && !BUCKET_KEYS.contains(&k.as_str()))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect())
+        .unwrap_or_default();
+    Value::Object(subs)
+}
+
+fn add_metric(row: &mut Map<String, Value>, name: &str, aggregation: &Value) {
+    if let Some(value) = aggregation.get("value") {
+        row.insert(name.to_string(), value.clone());
+    } else if let Some(values) = aggregation.get("values").and_then(Value::as_object) {
+        // percentiles and percentile_ranks
+        for (percent, value) in values {
+            row.insert(format!("{}.{}", name, percent), value.clone());
+        }
+    } else if let Some(hits) = aggregation["hits"]["hits"].as_array() {
+        // top_hits
+        row.insert(name.to_string(), Value::Array(hits.iter().map(|hit| hit["_source"].clone()).collect()));
+    } else if let Some(fields) = aggregation.as_object() {
+        // stats, extended_stats and single bucket aggregations without sub-aggregations
+        for (field, value) in fields {
+            if value.is_number() || value.is_null() {
+                row.insert(format!("{}.{}", name, field), value.clone());
+            }
+        }
+    }
+}
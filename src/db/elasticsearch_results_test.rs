@@ -0,0 +1,168 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::elasticsearch_results::{aggregations_to_result, aggregations_to_rows, hits_total, search_response_to_result, search_response_to_rows};
+    use crate::db::result_set::DataType;
+    use serde_json::json;
+
+    #[test]
+    fn test_hits_include_metadata_and_total() {
+        let body = json!({
+            "hits": {
+                "total": { "value": 10000, "relation": "gte" },
+                "hits": [{
+                    "_index": "logs", "_id": "1", "_score": 1.5,
+                    "_source": { "message": "disk full" },
+                    "highlight": { "message": ["<em>disk</em> full"] }
+                }]
+            }
+        });
+
+        let rows = search_response_to_rows(&body).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["message"], json!("disk full"));
+        assert_eq!(rows[0]["_id"], json!("1"));
+        assert_eq!(rows[0]["_index"], json!("logs"));
+        assert_eq!(rows[0]["_score"], json!(1.5));
+        assert_eq!(rows[0]["_highlight"], json!({ "message": ["<em>disk</em> full"] }));
+        assert_eq!(rows[0]["_total"], json!(10000));
+        assert_eq!(rows[0]["_total_relation"], json!("gte"));
+    }
+
+    #[test]
+    fn test_hits_result_keeps_source_order() {
+        let body = json!({
+            "hits": {
+                "total": { "value": 2, "relation": "eq" },
+                "hits": [
+                    { "_index": "logs", "_id": "1", "_source": { "message": "disk full", "level": 3 } },
+                    { "_index": "logs", "_id": "2", "_source": { "message": "ok", "host": "a" } }
+                ]
+            }
+        });
+        let result = search_response_to_result(&body).unwrap();
+        let names: Vec<_> = result.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["_id", "_index", "message", "level", "host"]);
+        assert_eq!(result.columns[3].data_type, DataType::Integer);
+        assert!(result.columns[4].nullable);
+        assert_eq!((result.total_rows, result.truncated), (Some(2), false));
+
+        let partial = json!({ "hits": { "total": { "value": 10000, "relation": "gte" }, "hits": [] } });
+        let result = search_response_to_result(&partial).unwrap();
+        assert_eq!((result.total_rows, result.truncated), (None, true), "A lower bound is not an exact total");
+    }
+
+    #[test]
+    fn test_hits_total_legacy_number() {
+        assert_eq!(hits_total(&json!({ "hits": { "total": 3 } })), Some((3, "eq".to_string())));
+        assert_eq!(hits_total(&json!({ "hits": {} })), None);
+    }
+
+    #[test]
+    fn test_missing_hits_is_none() {
+        assert!(search_response_to_rows(&json!({ "acknowledged": true })).is_none());
+    }
+
+    #[test]
+    fn test_nested_terms_and_date_histogram_with_metrics() {
+        let rows = aggregations_to_rows(&json!({
+            "by_host": {
+                "buckets": [
+                    {
+                        "key": "web-1", "doc_count": 5,
+                        "per_hour": {
+                            "buckets": [
+                                { "key": 1700000000000u64, "key_as_string": "2023-11-14T22:00:00Z", "doc_count": 3, "avg_latency": { "value": 12.5 } },
+                                { "key": 1700003600000u64, "key_as_string": "2023-11-14T23:00:00Z", "doc_count": 2, "avg_latency": { "value": null } }
+                            ]
+                        }
+                    },
+                    { "key": "web-2", "doc_count": 0, "per_hour": { "buckets": [] } }
+                ]
+            },
+            "total_bytes": { "value": 2048.0 }
+        }));
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0]["by_host"], json!("web-1"));
+        assert_eq!(rows[0]["by_host.doc_count"], json!(5));
+        assert_eq!(rows[0]["per_hour"], json!("2023-11-14T22:00:00Z"));
+        assert_eq!(rows[0]["per_hour.doc_count"], json!(3));
+        assert_eq!(rows[0]["avg_latency"], json!(12.5));
+        assert_eq!(rows[0]["total_bytes"], json!(2048.0));
+        assert_eq!(rows[1]["avg_latency"], json!(null));
+        assert_eq!(rows[2]["by_host"], json!("web-2"), "A bucket with empty sub-aggregations should keep its row");
+        assert_eq!(rows[2]["by_host.doc_count"], json!(0));
+        assert_eq!(rows[2]["per_hour"], json!(null));
+        assert_eq!(rows[2]["per_hour.doc_count"], json!(null));
+        assert_eq!(rows[2]["total_bytes"], json!(2048.0));
+    }
+
+    #[test]
+    fn test_aggregation_result_keeps_response_order() {
+        let result = aggregations_to_result(&json!({
+            "zone": {
+                "buckets": [
+                    { "key": "eu", "doc_count": 4, "p95": { "value": 80.0 }, "avg": { "value": 31.5 } },
+                    { "key": "us", "doc_count": 1, "p95": { "value": null }, "avg": { "value": 12.0 } }
+                ]
+            },
+            "max_bytes": { "value": 512 }
+        }));
+
+        let names: Vec<_> = result.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["max_bytes", "zone", "zone.doc_count", "p95", "avg"], "Columns should not be sorted by name");
+        assert_eq!(result.rows[1], vec![json!(512), json!("us"), json!(1), json!(null), json!(12.0)]);
+        assert_eq!(result.columns[2].data_type, DataType::Integer);
+        assert!(result.columns[3].nullable);
+    }
+
+    #[test]
+    fn test_empty_top_level_buckets_have_no_rows() {
+        assert!(aggregations_to_rows(&json!({ "by_host": { "buckets": [] } })).is_empty());
+    }
+
+    #[test]
+    fn test_range_stats_and_percentiles() {
+        let rows = aggregations_to_rows(&json!({
+            "price_ranges": {
+                "buckets": [
+                    { "key": "*-100.0", "to": 100.0, "doc_count": 2, "price_stats": { "count": 2, "min": 10.0, "max": 50.0, "avg": 30.0, "sum": 60.0 } },
+                    { "key": "100.0-*", "from": 100.0, "doc_count": 1, "load": { "values": { "50.0": 120.0, "99.0": 180.0 } } }
+                ]
+            }
+        }));
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["price_ranges"], json!("*-100.0"));
+        assert_eq!(rows[0]["price_stats.avg"], json!(30.0));
+        assert_eq!(rows[0]["price_stats.count"], json!(2));
+        assert_eq!(rows[1]["load.99.0"], json!(180.0));
+        assert!(!rows[1].contains_key("to"));
+    }
+
+    #[test]
+    fn test_keyed_filter_and_composite_buckets() {
+        let rows = aggregations_to_rows(&json!({
+            "levels": { "buckets": { "errors": { "doc_count": 4 }, "warnings": { "doc_count": 1 } } },
+            "recent": { "doc_count": 7, "hosts": { "buckets": [{ "key": { "host": "a", "dc": "eu" }, "doc_count": 7 }] } }
+        }));
+
+        assert_eq!(rows.len(), 3);
+        assert!(rows.iter().any(|r| r.get("levels") == Some(&json!("errors")) && r["levels.doc_count"] == json!(4)));
+        let composite = rows.iter().find(|r| r.contains_key("recent.doc_count")).unwrap();
+        assert_eq!(composite["hosts.host"], json!("a"));
+        assert_eq!(composite["hosts.dc"], json!("eu"));
+        assert_eq!(composite["hosts.doc_count"], json!(7));
+    }
+
+    #[test]
+    fn test_aggregations_take_precedence_over_hits() {
+        let body = json!({
+            "hits": { "total": { "value": 5, "relation": "eq" }, "hits": [] },
+            "aggregations": { "max_size": { "value": 99 } }
+        });
+        let rows = search_response_to_rows(&body).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["max_size"], json!(99));
+    }
+}
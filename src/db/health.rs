@@ -0,0 +1,40 @@
+//! Liveness checks of registered connections. A check that succeeds marks a connection as
+//! connected, or degraded when it is slow; a check that fails or times out marks it as failed,
+//! after which it is reopened with exponential backoff.
+use std::time::Duration;
+use tokio::time::Instant;
+use crate::db::connection_manager::{ConnectionStatus, DatabaseConnection};
+use crate::utils::errors::DatabaseError;
+
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+pub const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Checks slower than this mark the connection as degraded.
+pub const DEGRADED_LATENCY: Duration = Duration::from_secs(1);
+
+pub const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+pub const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Runs `health_check` with a timeout and returns its latency.
+pub async fn check(connection: &dyn DatabaseConnection) -> Result<Duration, DatabaseError> {
+    let started = Instant::now();
+    match tokio::time::timeout(HEALTH_CHECK_TIMEOUT, connection.health_check()).await {
+        Ok(Ok(())) => Ok(started.elapsed()),
+        Ok(Err(e)) => Err(e),
+        Err(_) => Err(DatabaseError::ConnectionError(format!("Health check timed out after {}s", HEALTH_CHECK_TIMEOUT.as_secs()))),
+    }
+}
+
+/// The status of a connection whose health check succeeded in `latency`.
+pub fn status_for(latency: Duration) -> ConnectionStatus {
+    if latency > DEGRADED_LATENCY {
+        ConnectionStatus::Degraded
+    } else {
+        ConnectionStatus::Connected
+    }
+}
+
+/// The delay before reconnect attempt `attempt`, counting from 0: 1s, 2s, 4s, ... up to 60s.
+pub fn backoff_delay(attempt: u32) -> Duration {
+    RECONNECT_BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt)).min(RECONNECT_MAX_DELAY)
+}
@@ -0,0 +1,19 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::connection_manager::ConnectionStatus;
+    use crate::db::health::{backoff_delay, status_for, DEGRADED_LATENCY, RECONNECT_MAX_DELAY};
+    use std::time::Duration;
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_maximum() {
+        let delays: Vec<u64> = (0..8).map(|attempt| backoff_delay(attempt).as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(backoff_delay(u32::MAX), RECONNECT_MAX_DELAY);
+    }
+
+    #[test]
+    fn test_slow_checks_are_degraded() {
+        assert_eq!(status_for(Duration::from_millis(20)), ConnectionStatus::Connected);
+        assert_eq!(status_for(DEGRADED_LATENCY + Duration::from_millis(1)), ConnectionStatus::Degraded);
+    }
+}
@@ -4,11 +4,51 @@ pub mod connection_manager;
 pub mod postgres_custom;
 pub mod mongodb_custom;
 pub mod redis_custom;
+pub mod redis_command;
+pub mod redis_pubsub;
+pub mod redis_streams;
 pub mod elasticsearch_custom;
+pub mod elasticsearch_bulk;
+pub mod elasticsearch_cluster;
+pub mod elasticsearch_dsl;
+pub mod elasticsearch_paging;
+pub mod elasticsearch_results;
+pub mod cancel;
+pub mod health;
+pub mod native_query;
+pub mod result_set;
+pub mod retry;
+pub mod row_stream;
+pub mod tls;
+mod connection_manager_test;
+mod mongodb_custom_test;
+mod postgres_custom_test;
+mod redis_custom_test;
+mod redis_command_test;
+mod redis_pubsub_test;
+mod redis_streams_test;
+mod elasticsearch_custom_test;
+mod elasticsearch_bulk_test;
+mod elasticsearch_cluster_test;
+mod elasticsearch_dsl_test;
+mod elasticsearch_paging_test;
+mod elasticsearch_results_test;
+mod cancel_test;
+mod health_test;
+mod native_query_test;
+mod result_set_test;
+mod retry_test;
+mod row_stream_test;
+mod tls_test;
 
 use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::Arc;
 use async_trait::async_trait;
+use crate::db::native_query::NativeQuery;
+use crate::db::query_builder::QueryBuilder;
+use crate::db::result_set::ResultSet;
+use crate::db::row_stream::{collected, RowStream};
 use crate::utils::errors::DatabaseError;
 
 /// The `DatabasePool` trait defines the methods that a database connection pool should implement.
@@ -17,6 +57,67 @@ use crate::utils::errors::DatabaseError;
 #[async_trait]
 pub trait DatabasePool: Send + Sync {
     async fn execute(&self, query: &str, params: Vec<Value>) -> Result<Vec<HashMap<String, Value>>, DatabaseError>;
+
+    /// Builds and executes a `QueryBuilder` query. Pools that need information from the server
+    /// to build a query, such as index mappings, override this.
+    async fn execute_builder(&self, builder: &QueryBuilder) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        let query = builder.build()?;
+        self.execute(&query, builder.values.clone()).await
+    }
+
+    /// Builds and executes a `QueryBuilder` query like `execute_builder`, keeping the order of
+    /// its columns. By default the rows of `execute_builder` are typed from their values.
+    async fn query_builder(&self, builder: &QueryBuilder) -> Result<ResultSet, DatabaseError> {
+        self.execute_builder(builder).await.map(ResultSet::from_maps)
+    }
+
+    /// Executes a query typed by the user. Pools validate queries in their own language and
+    /// reject the others.
+    async fn execute_native(&self, query: &NativeQuery) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        Err(query.unsupported())
+    }
+
+    /// Executes a native query, keeping the order, names and types of its columns. Pools
+    /// override this; by default the rows of `execute_native` are typed from their values.
+    async fn query_native(&self, query: &NativeQuery) -> Result<ResultSet, DatabaseError> {
+        self.execute_native(query).await.map(ResultSet::from_maps)
+    }
+
+    /// Streams the rows of a native query in batches of at most `batch_size` rows. Pools read
+    /// from a server-side cursor where they can; by default the query runs to completion and
+    /// its rows are split.
+    fn stream_native<'a>(&'a self, query: &'a NativeQuery, batch_size: usize) -> RowStream<'a> {
+        collected(self.query_native(query), batch_size)
+    }
+}
+
+/// Shared pools, e.g. the same pool registered in both the `DatabaseManager` and the
+/// `ConnectionManager`, forward to the pool they wrap.
+#[async_trait]
+impl<T: DatabasePool + ?Sized> DatabasePool for Arc<T> {
+    async fn execute(&self, query: &str, params: Vec<Value>) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        (**self).execute(query, params).await
+    }
+
+    async fn execute_builder(&self, builder: &QueryBuilder) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        (**self).execute_builder(builder).await
+    }
+
+    async fn execute_native(&self, query: &NativeQuery) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        (**self).execute_native(query).await
+    }
+
+    async fn query_builder(&self, builder: &QueryBuilder) -> Result<ResultSet, DatabaseError> {
+        (**self).query_builder(builder).await
+    }
+
+    async fn query_native(&self, query: &NativeQuery) -> Result<ResultSet, DatabaseError> {
+        (**self).query_native(query).await
+    }
+
+    fn stream_native<'a>(&'a self, query: &'a NativeQuery, batch_size: usize) -> RowStream<'a> {
+        (**self).stream_native(query, batch_size)
+    }
 }
 
 /// The `Database` trait defines the methods that a database connection should implement.
@@ -29,6 +130,11 @@ pub trait Database: Send + Sync {
     async fn list_databases(&self) -> Result<Vec<String>, DatabaseError>;
     async fn list_collections(&self, database: &str) -> Result<Vec<String>, DatabaseError>;
     async fn get_schema(&self, database: &str, collection: &str) -> Result<Value, DatabaseError>;
+
+    /// Checks that the server is reachable. Backends override this with a cheap ping.
+    async fn health_check(&self) -> Result<(), DatabaseError> {
+        self.list_databases().await.map(|_| ())
+    }
 }
 
 /// The `DatabaseType` enum defines the types of databases that can be used in the application.
@@ -37,32 +143,199 @@ pub trait Database: Send + Sync {
 /// Example usage can be found in the `db_manager` module.
 pub mod db_manager {
     use super::*;
+    use log::{info, warn};
+    use crate::config::config::{redact_url, Config, SourceOptions};
+    use crate::db::connection_manager::{ConnectionManager, DatabaseConnection};
     use crate::db::query_builder::{DatabaseType, DatabaseManager};
+    use crate::db::retry::RetryPolicy;
     use crate::utils::errors::DatabaseError;
-    use std::sync::Arc;
 
-    /// Initializes the `DatabaseManager` with the required database connection pools.
-    /// This function creates instances of the database connection pools and adds them to the `DatabaseManager`.
-    /// The connection strings for the databases should be provided as arguments to the functions.
-    pub async fn initialize_db_manager() -> Result<DatabaseManager, DatabaseError> {
+    /// A source from the configuration, in the form needed to open it and to tell whether it
+    /// changed between two configurations. Not `Debug`, since the URL may contain a password.
+    #[derive(Clone, PartialEq)]
+    pub struct SourceSpec {
+        pub name: String,
+        pub database_type: DatabaseType,
+        /// The connection URL, or why it could not be built.
+        pub url: Result<String, String>,
+        pub options: SourceOptions,
+    }
+
+    /// The sources in `config` that can be opened, in declaration order per type.
+    /// MySQL and SQLite sources are not supported yet and are left out.
+    pub fn source_specs(config: &Config) -> Vec<SourceSpec> {
+        let databases = &config.databases;
+        let mut specs = Vec::new();
+
+        let url_sources = [
+            (&databases.postgresql, DatabaseType::PostgreSQL, "postgres", 5432),
+            (&databases.mongodb, DatabaseType::MongoDB, "mongodb", 27017),
+            (&databases.cassandra, DatabaseType::Cassandra, "cassandra", 9042),
+        ];
+        for (configs, database_type, scheme, default_port) in url_sources {
+            specs.extend(configs.iter().map(|source| SourceSpec {
+                name: source.name.clone(),
+                database_type: database_type.clone(),
+                url: source.connection_url(scheme, default_port).map_err(|e| e.to_string()),
+                options: source.options.clone(),
+            }));
+        }
+        specs.extend(databases.elasticsearch.iter().map(|source| {
+            let scheme = if source.options.tls_enabled() { "https" } else { "http" };
+            SourceSpec {
+                name: source.name.clone(),
+                database_type: DatabaseType::Elasticsearch,
+                url: source.connection_url(scheme, 9200).map_err(|e| e.to_string()),
+                options: source.options.clone(),
+            }
+        }));
+        specs.extend(databases.redis.iter().map(|source| SourceSpec {
+            name: source.name.clone(),
+            database_type: DatabaseType::Redis,
+            url: source.connection_url().map_err(|e| e.to_string()),
+            options: source.options.clone(),
+        }));
+        specs
+    }
+
+    /// An opened pool. Redis and Elasticsearch pools are also kept with their concrete type,
+    /// since pub/sub and monitoring need more than `DatabaseConnection`.
+    pub struct OpenedPool {
+        pub handle: Arc<dyn DatabaseConnection>,
+        pub redis: Option<Arc<redis_custom::RedisPool>>,
+        pub elasticsearch: Option<Arc<elasticsearch_custom::ElasticsearchPool>>,
+    }
+
+    impl OpenedPool {
+        fn new(handle: Arc<dyn DatabaseConnection>) -> Self {
+            OpenedPool { handle, redis: None, elasticsearch: None }
+        }
+    }
+
+    pub async fn open_pool(spec: &SourceSpec) -> Result<OpenedPool, DatabaseError> {
+        let url = spec.url.as_ref().map_err(|e| DatabaseError::ConfigurationError(e.clone()))?;
+        let opened = match spec.database_type {
+            DatabaseType::PostgreSQL => postgres_custom::PostgresPool::open(url, &spec.options).await
+                .map(|pool| OpenedPool::new(Arc::new(pool))),
+            DatabaseType::MongoDB => mongodb_custom::MongoPool::open(url, &spec.options).await
+                .map(|pool| OpenedPool::new(Arc::new(pool))),
+            DatabaseType::Redis => redis_custom::RedisPool::open(url, &spec.options).await.map(|pool| {
+                let pool = Arc::new(pool);
+                OpenedPool { redis: Some(Arc::clone(&pool)), ..OpenedPool::new(pool) }
+            }),
+            DatabaseType::Elasticsearch => elasticsearch_custom::ElasticsearchPool::open(url, &spec.options).await.map(|pool| {
+                let pool = Arc::new(pool);
+                OpenedPool { elasticsearch: Some(Arc::clone(&pool)), ..OpenedPool::new(pool) }
+            }),
+            DatabaseType::Cassandra => {
+                Err(DatabaseError::UnsupportedOperation("Cassandra sources are not supported yet".to_string()))
+            }
+        };
+        opened.map_err(|e| redact_error(e, url))
+    }
+
+    /// Drivers may echo the connection string in their errors; keeps passwords out of logs and `last_error`.
+    fn redact_error(error: DatabaseError, url: &str) -> DatabaseError {
+        match error {
+            DatabaseError::ConnectionError(message) => DatabaseError::ConnectionError(message.replace(url, &redact_url(url))),
+            DatabaseError::ConfigurationError(message) => DatabaseError::ConfigurationError(message.replace(url, &redact_url(url))),
+            error => error,
+        }
+    }
+
+    /// Opens a source and registers it in `connections`. Failures are registered with a
+    /// `Failed` status and returned.
+    pub async fn register_source(connections: &ConnectionManager, spec: &SourceSpec) -> Result<OpenedPool, DatabaseError> {
+        match open_pool(spec).await {
+            Ok(opened) => {
+                connections.add_connection(&spec.name, spec.database_type.clone(), Arc::clone(&opened.handle)).await?;
+                Ok(opened)
+            }
+            Err(e) => {
+                warn!("Could not connect to {}: {}", spec.name, e);
+                connections.add_failed_connection(&spec.name, spec.database_type.clone(), e.to_string()).await?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Builds a `DatabaseManager` serving the first connected source of each type in `specs`.
+    pub async fn build_db_manager(connections: &ConnectionManager, specs: &[SourceSpec]) -> DatabaseManager {
         let mut db_manager = DatabaseManager::new();
+        for spec in specs {
+            if db_manager.has_pool(&spec.database_type) {
+                continue;
+            }
+            if let Some(handle) = connections.tracked_connection(&spec.name).await {
+                db_manager.add_source(&spec.name, spec.database_type.clone(), Box::new(handle));
+                if let Some(retry) = &spec.options.retry {
+                    db_manager.set_retry_policy(&spec.name, RetryPolicy::from(retry));
+                }
+            }
+        }
+        db_manager
+    }
 
-        // Add PostgreSQL pool
-        let postgres_pool = Arc::new(postgres_custom::PostgresPool::new("your_postgres_connection_string_here").await?);
-        db_manager.add_pool(DatabaseType::PostgreSQL, Box::new(Arc::clone(&postgres_pool) as Arc<dyn DatabasePool>));
+    /// The sources opened from the configuration. Every configured source is registered in
+    /// `connections`, including the ones that failed to connect. The first connected source of
+    /// each type also serves `QueryBuilder` and native queries through `db_manager`.
+    pub struct Sources {
+        pub db_manager: DatabaseManager,
+        pub connections: ConnectionManager,
+        /// The configured sources, used to reopen them.
+        pub specs: Vec<SourceSpec>,
+        /// Used for pub/sub, streams and server introspection.
+        pub redis: Option<Arc<redis_custom::RedisPool>>,
+        /// Used for cluster monitoring.
+        pub elasticsearch: Option<Arc<elasticsearch_custom::ElasticsearchPool>>,
+        /// The sources `redis` and `elasticsearch` were opened for.
+        pub pinned: Vec<String>,
+    }
 
-        // Add MongoDB pool
-        let mongo_pool = Arc::new(mongodb_custom::MongoPool::new("your_mongodb_connection_string_here").await?);
-        db_manager.add_pool(DatabaseType::MongoDB, Box::new(Arc::clone(&mongo_pool) as Arc<dyn DatabasePool>));
+    impl Sources {
+        pub fn empty() -> Self {
+            Sources {
+                db_manager: DatabaseManager::new(),
+                connections: ConnectionManager::new(),
+                specs: Vec::new(),
+                redis: None,
+                elasticsearch: None,
+                pinned: Vec::new(),
+            }
+        }
+    }
 
-        // Add Redis pool
-        let redis_pool = Arc::new(redis_custom::RedisPool::new("your_redis_connection_string_here").await?);
-        db_manager.add_pool(DatabaseType::Redis, Box::new(Arc::clone(&redis_pool) as Arc<dyn DatabasePool>));
+    /// Opens every source in the configuration. Sources that fail to connect are logged and
+    /// flagged instead of aborting startup.
+    pub async fn initialize_sources(config: &Config) -> Sources {
+        let mut sources = Sources::empty();
 
-        // Add Elasticsearch pool
-        let elasticsearch_pool = Arc::new(elasticsearch_custom::ElasticsearchPool::new("your_elasticsearch_connection_string_here").await?);
-        db_manager.add_pool(DatabaseType::Elasticsearch, Box::new(Arc::clone(&elasticsearch_pool) as Arc<dyn DatabasePool>));
+        for mysql in &config.databases.mysql {
+            warn!("Skipping {}: MySQL sources are not supported yet", mysql.name);
+        }
+        for sqlite in &config.databases.sqlite {
+            warn!("Skipping {}: SQLite sources are not supported yet", sqlite.name);
+        }
 
-        Ok(db_manager)
+        let specs = source_specs(config);
+        for spec in &specs {
+            let Ok(opened) = register_source(&sources.connections, spec).await else {
+                continue;
+            };
+            // Pub/sub and monitoring keep these pools, so reloads must not close them
+            if (sources.redis.is_none() && opened.redis.is_some()) || (sources.elasticsearch.is_none() && opened.elasticsearch.is_some()) {
+                let _ = sources.connections.share_handle(&spec.name).await;
+                sources.pinned.push(spec.name.clone());
+            }
+            sources.redis = sources.redis.or(opened.redis);
+            sources.elasticsearch = sources.elasticsearch.or(opened.elasticsearch);
+        }
+        sources.db_manager = build_db_manager(&sources.connections, &specs).await;
+        sources.specs = specs;
+
+        for source in sources.connections.list_connections().await {
+            info!("Source {} ({:?}): {:?}", source.name, source.database_type, source.status);
+        }
+        sources
     }
-}
\ No newline at end of file
+}
@@ -1,6 +1,18 @@
 use super::*;
 use mongodb::{bson::{doc, Document, Bson}, bson, options::FindOptions};
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::options::{ClientOptions, Tls, TlsOptions};
+use mongodb::ClientSession;
+use futures::StreamExt;
+use std::path::PathBuf;
+use crate::config::config::{SourceOptions, TlsConfig, TlsVerify};
+use crate::db::cancel::{query_tag, spawn_cancel, CancelGuard};
+use crate::db::result_set::{Cell, DataType, ResultSet, ResultSetBuilder};
+use crate::db::row_stream::{failed, RowStream};
+use crate::db::tls::TlsSupport;
+use std::time::Duration;
 use serde_json::json;
+use crate::db::native_query::{pipeline_command, validate_mongo_command, NativeQuery};
 use crate::db::query_builder::{Condition, Operator, OrderDirection, QueryBuilder, QueryOperation};
 use crate::utils::errors::{QueryBuilderError, DatabaseError};
 
@@ -13,7 +25,7 @@ pub fn build_query(builder: &QueryBuilder) -> Result<String, QueryBuilderError>
         QueryOperation::Aggregate => build_aggregate_query(builder),
     }?;
 
-    Ok(serde_json::to_string(&query).map_err(|e| QueryBuilderError::InvalidQuery(e.to_string()))?)
+    serde_json::to_string(&query).map_err(|e| QueryBuilderError::InvalidQuery(e.to_string()))
 }
 
 fn build_find_query(builder: &QueryBuilder) -> Result<Document, QueryBuilderError> {
@@ -172,45 +184,429 @@ fn build_filter(conditions: &[Condition]) -> Result<Document, QueryBuilderError>
     Ok(filter)
 }
 
+/// The driver reads the certificate files itself, and always sends the host as the server name.
+pub(crate) fn mongo_tls(tls: &TlsConfig) -> Result<Tls, DatabaseError> {
+    if !tls.enabled {
+        return Ok(Tls::Disabled);
+    }
+    TlsSupport { verify_ca_only: true, server_name: false }.check("MongoDB", tls)?;
+    if tls.key_file.is_some() && tls.key_file != tls.cert_file {
+        return Err(DatabaseError::UnsupportedOperation(
+            "MongoDB reads the client certificate and key from one PEM file; put both in cert_file".to_string()
+        ));
+    }
+
+    let options = TlsOptions::builder()
+        .ca_file_path(tls.ca_file.as_ref().map(PathBuf::from))
+        .cert_key_file_path(tls.cert_file.as_ref().map(PathBuf::from))
+        .allow_invalid_certificates(tls.verify == TlsVerify::Disabled)
+        .allow_invalid_hostnames(tls.verify != TlsVerify::Full)
+        .build();
+    Ok(Tls::Enabled(options))
+}
+
+/// Classifies an error opening a client. Invalid options are configuration errors; others, such
+/// as a failed SRV lookup, are connection errors.
+fn setup_error(error: mongodb::error::Error) -> DatabaseError {
+    match error.kind.as_ref() {
+        ErrorKind::InvalidArgument { .. } | ErrorKind::InvalidTlsConfig { .. } => DatabaseError::ConfigurationError(error.to_string()),
+        _ => DatabaseError::ConnectionError(error.to_string()),
+    }
+}
+
+/// Classifies a driver error. Errors reported by the server keep their code, and are retryable
+/// when the code is a retryable read code; network errors and failed server selection, e.g.
+/// during an election, are connection errors.
+pub(crate) fn mongo_error(error: mongodb::error::Error) -> DatabaseError {
+    let reported = match error.kind.as_ref() {
+        ErrorKind::Command(e) => Some((e.code, e.message.clone())),
+        ErrorKind::Write(WriteFailure::WriteError(e)) => Some((e.code, e.message.clone())),
+        ErrorKind::Write(WriteFailure::WriteConcernError(e)) => Some((e.code, e.message.clone())),
+        ErrorKind::Io(_) | ErrorKind::ConnectionPoolCleared { .. } | ErrorKind::ServerSelection { .. } => {
+            return DatabaseError::ConnectionError(error.to_string());
+        }
+        _ => None,
+    };
+    match reported {
+        Some((code, message)) => DatabaseError::mongo(code, message),
+        None => DatabaseError::QueryError(error.to_string()),
+    }
+}
+
+/// Commands that may run long. They get the default `maxTimeMS` and are tagged with a `comment`
+/// so they can be found and killed on the server.
+const CANCELLABLE_COMMANDS: [&str; 4] = ["find", "aggregate", "count", "distinct"];
+
+/// Applies the default time limit to a cancellable command that sets none, and tags it unless it
+/// already has a comment. Returns whether the command was tagged.
+pub(crate) fn prepare_command(command: &mut Document, tag: &str, max_time: Option<Duration>) -> bool {
+    let name = command.keys().next().cloned().unwrap_or_default();
+    if !CANCELLABLE_COMMANDS.contains(&name.as_str()) {
+        return false;
+    }
+    if let Some(max_time) = max_time.filter(|_| !command.contains_key("maxTimeMS")) {
+        command.insert("maxTimeMS", i64::try_from(max_time.as_millis()).unwrap_or(i64::MAX));
+    }
+    if command.contains_key("comment") {
+        return false;
+    }
+    command.insert("comment", tag);
+    true
+}
+
+/// Kills the operations running a command tagged with `tag`.
+async fn kill_tagged(client: mongodb::Client, tag: String) -> Result<(), DatabaseError> {
+    let admin = client.database("admin");
+    let current = admin.run_command(doc! { "currentOp": true, "command.comment": tag.as_str() }, None).await
+        .map_err(mongo_error)?;
+    let operations = current.get_array("inprog").map(|operations| operations.to_vec()).unwrap_or_default();
+    for opid in operations.iter().filter_map(|op| op.as_document().and_then(|op| op.get("opid"))) {
+        admin.run_command(doc! { "killOp": 1, "op": opid.clone() }, None).await
+            .map_err(mongo_error)?;
+    }
+    Ok(())
+}
+
+/// The database, collection and id of a cursor the server keeps open after the first batch.
+pub(crate) fn open_cursor(result: &Document) -> Option<(String, String, i64)> {
+    let cursor = result.get_document("cursor").ok()?;
+    let id = cursor.get_i64("id").ok().filter(|id| *id != 0)?;
+    let (database, collection) = cursor.get_str("ns").ok()?.split_once('.')?;
+    Some((database.to_string(), collection.to_string(), id))
+}
+
+/// Sets the batch size of a cursor command that does not set one.
+pub(crate) fn set_batch_size(command: &mut Document, batch_size: usize) {
+    let batch_size = i64::try_from(batch_size.max(1)).unwrap_or(i64::MAX);
+    if command.keys().next().map(String::as_str) == Some("find") {
+        if !command.contains_key("batchSize") {
+            command.insert("batchSize", batch_size);
+        }
+    } else if let Ok(cursor) = command.get_document_mut("cursor") {
+        if !cursor.contains_key("batchSize") {
+            cursor.insert("batchSize", batch_size);
+        }
+    }
+}
+
+/// Closes a cursor on the server, in the session that opened it.
+async fn kill_cursor(client: mongodb::Client, mut session: ClientSession, database: String, collection: String, id: i64) -> Result<(), DatabaseError> {
+    client.database(&database).run_command_with_session(doc! { "killCursors": collection, "cursors": [id] }, None, &mut session).await
+        .map_err(mongo_error)?;
+    Ok(())
+}
+
+/// A cursor left open on the server after a batch. Dropped before it is exhausted, it is
+/// closed in the background.
+struct MongoCursor {
+    client: mongodb::Client,
+    /// Cursors can only be read and closed from the session that opened them.
+    session: Option<ClientSession>,
+    database: String,
+    collection: String,
+    id: i64,
+}
+
+impl Drop for MongoCursor {
+    fn drop(&mut self) {
+        if self.id == 0 {
+            return;
+        }
+        if let Some(session) = self.session.take() {
+            let database = std::mem::take(&mut self.database);
+            let collection = std::mem::take(&mut self.collection);
+            spawn_cancel(kill_cursor(self.client.clone(), session, database, collection, self.id));
+        }
+    }
+}
+
+/// Where a streamed command stands.
+enum CursorState {
+    Start(String, Document),
+    Next(Box<MongoCursor>),
+    Done,
+}
+
+fn documents_to_rows(documents: Vec<Bson>) -> Vec<HashMap<String, Value>> {
+    documents.iter()
+        .filter_map(Bson::as_document)
+        .map(|doc| doc.iter().map(|(k, v)| (k.to_string(), bson_to_json(v))).collect())
+        .collect()
+}
+
+/// The BSON type name of a value, as `$type` spells it, and its column type.
+pub(crate) fn bson_type(value: &Bson) -> (&'static str, DataType) {
+    match value {
+        Bson::Double(_) => ("double", DataType::Float),
+        Bson::String(_) => ("string", DataType::Text),
+        Bson::Document(_) => ("object", DataType::Json),
+        Bson::Array(_) => ("array", DataType::Json),
+        Bson::Binary(_) => ("binData", DataType::Binary),
+        Bson::ObjectId(_) => ("objectId", DataType::Text),
+        Bson::Boolean(_) => ("bool", DataType::Boolean),
+        Bson::DateTime(_) => ("date", DataType::Timestamp),
+        Bson::Null => ("null", DataType::Unknown),
+        Bson::RegularExpression(_) => ("regex", DataType::Text),
+        Bson::JavaScriptCode(_) => ("javascript", DataType::Text),
+        Bson::JavaScriptCodeWithScope(_) => ("javascriptWithScope", DataType::Json),
+        Bson::Int32(_) => ("int", DataType::Integer),
+        Bson::Int64(_) => ("long", DataType::Integer),
+        Bson::Timestamp(_) => ("timestamp", DataType::Timestamp),
+        Bson::Decimal128(_) => ("decimal", DataType::Decimal),
+        Bson::Symbol(_) => ("symbol", DataType::Text),
+        Bson::Undefined => ("undefined", DataType::Unknown),
+        Bson::MaxKey => ("maxKey", DataType::Unknown),
+        Bson::MinKey => ("minKey", DataType::Unknown),
+        Bson::DbPointer(_) => ("dbPointer", DataType::Json),
+    }
+}
+
+/// Documents as rows, with a column per field in the order the fields first appear.
+pub(crate) fn documents_to_result(documents: Vec<Bson>) -> ResultSet {
+    let mut builder = ResultSetBuilder::default();
+    for document in documents.iter().filter_map(Bson::as_document) {
+        builder.push_row(document.iter().map(|(name, value)| {
+            let (source_type, data_type) = bson_type(value);
+            Cell { name: name.clone(), value: bson_to_json(value), source_type: source_type.to_string(), data_type }
+        }));
+    }
+    builder.finish()
+}
+
+/// The number of documents a write command wrote, from its result.
+pub(crate) fn affected_documents(command: &str, result: &Document) -> Option<u64> {
+    let field = match command {
+        "insert" | "delete" => "n",
+        "update" => "nModified",
+        _ => return None,
+    };
+    match result.get(field)? {
+        Bson::Int32(n) => u64::try_from(*n).ok(),
+        Bson::Int64(n) => u64::try_from(*n).ok(),
+        _ => None,
+    }
+}
+
+/// The first batch of a command as a result. Write commands report the documents they wrote.
+fn batch_result(command: &str, batch: Vec<Bson>) -> ResultSet {
+    let affected_rows = batch.first()
+        .and_then(Bson::as_document)
+        .and_then(|result| affected_documents(command, result));
+    ResultSet { affected_rows, ..documents_to_result(batch) }
+}
+
+/// The database and command of a native query.
+fn native_command(query: &NativeQuery) -> Result<(&Option<String>, Value), DatabaseError> {
+    match query {
+        NativeQuery::MongoCommand { database, command } => Ok((database, command.clone())),
+        NativeQuery::MongoPipeline { database, collection, pipeline } => Ok((database, pipeline_command(collection, pipeline))),
+        other => Err(other.unsupported()),
+    }
+}
+
 pub struct MongoPool {
     client: mongodb::Client,
     db_name: String,
+    /// The default `maxTimeMS` of long-running commands.
+    max_time: Option<Duration>,
 }
 
 impl MongoPool {
     pub async fn new(connection_string: &str) -> Result<Self, DatabaseError> {
-        let client = mongodb::Client::with_uri_str(connection_string).await
+        Self::open(connection_string, &SourceOptions::default()).await
+    }
+
+    /// Opens a client with the options of a configured source. A configured `tls` table
+    /// replaces the TLS options of the connection string.
+    pub async fn open(connection_string: &str, options: &SourceOptions) -> Result<Self, DatabaseError> {
+        let mut client_options = ClientOptions::parse(connection_string).await.map_err(setup_error)?;
+        if let Some(tls) = &options.tls {
+            client_options.tls = Some(mongo_tls(tls)?);
+        }
+        if let Some(size) = options.pool_size {
+            client_options.max_pool_size = Some(u32::try_from(size).unwrap_or(u32::MAX));
+        }
+        client_options.connect_timeout = options.connect_timeout().or(client_options.connect_timeout);
+        client_options.max_idle_time = options.idle_timeout().or(client_options.max_idle_time);
+
+        let client = mongodb::Client::with_options(client_options).map_err(setup_error)?;
+        let db_name = client.default_database()
+            .map(|db| db.name().to_string())
+            .unwrap_or_else(|| "test".to_string());
+        Ok(MongoPool { client, db_name, max_time: options.query_timeout() })
+    }
+
+    /// Runs a command and returns the documents of its first cursor batch, or the command
+    /// result itself when it has no cursor.
+    async fn run_command(&self, database: &str, command: Document) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        // The rest of the cursor is closed when it is dropped
+        let (batch, _cursor) = self.open_command(database, command).await?;
+        Ok(documents_to_rows(batch))
+    }
+
+    /// Like `run_command`, keeping the fields in order with their BSON types. The result is
+    /// truncated when the cursor had more batches.
+    async fn query_command(&self, database: &str, command: Document) -> Result<ResultSet, DatabaseError> {
+        let name = command.keys().next().cloned().unwrap_or_default();
+        let (batch, cursor) = self.open_command(database, command).await?;
+        Ok(ResultSet { truncated: cursor.is_some(), ..batch_result(&name, batch) })
+    }
+
+    /// Runs a command in a session of its own. Returns its first batch, or the command result
+    /// itself when it has no cursor, and the cursor when more batches remain.
+    async fn open_command(&self, database: &str, mut command: Document) -> Result<(Vec<Bson>, Option<MongoCursor>), DatabaseError> {
+        let mut session = self.client.start_session(None).await
             .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
-        let db_name = "test".to_string();
-        Ok(MongoPool { client, db_name })
+        let tag = query_tag();
+        let guard = prepare_command(&mut command, &tag, self.max_time)
+            .then(|| CancelGuard::new(kill_tagged(self.client.clone(), tag)));
+        let result = self.client.database(database).run_command_with_session(command, None, &mut session).await;
+        if let Some(guard) = guard {
+            guard.disarm();
+        }
+        let mut result = result.map_err(mongo_error)?;
+
+        let cursor = open_cursor(&result).map(|(database, collection, id)| MongoCursor {
+            client: self.client.clone(),
+            session: Some(session),
+            database,
+            collection,
+            id,
+        });
+        let batch = match result.get_document_mut("cursor") {
+            Ok(cursor) => match cursor.remove("firstBatch") {
+                Some(Bson::Array(batch)) => batch,
+                _ => Vec::new(),
+            },
+            Err(_) => vec![Bson::Document(result)],
+        };
+        Ok((batch, cursor))
+    }
+
+    /// Fetches the next batch of a cursor. The cursor is returned while more batches remain.
+    async fn get_more(&self, mut cursor: MongoCursor, batch_size: usize) -> Result<(Vec<Bson>, Option<MongoCursor>), DatabaseError> {
+        let command = doc! {
+            "getMore": cursor.id,
+            "collection": cursor.collection.as_str(),
+            "batchSize": i64::try_from(batch_size.max(1)).unwrap_or(i64::MAX),
+        };
+        let db = self.client.database(&cursor.database);
+        let session = cursor.session.as_mut().expect("The session is only taken when the cursor is dropped");
+        let mut result = db.run_command_with_session(command, None, session).await
+            .map_err(mongo_error)?;
+
+        let reply = result.get_document_mut("cursor")
+            .map_err(|_| DatabaseError::QueryError("getMore returned no cursor".to_string()))?;
+        cursor.id = reply.get_i64("id").unwrap_or(0);
+        let batch = match reply.remove("nextBatch") {
+            Some(Bson::Array(batch)) => batch,
+            _ => Vec::new(),
+        };
+        Ok((batch, (cursor.id != 0).then_some(cursor)))
+    }
+
+    /// Streams every document of a cursor command, such as `find` or `aggregate`, one cursor
+    /// batch at a time. Other commands yield their result as a single row.
+    fn stream_command(&self, database: String, mut command: Document, batch_size: usize) -> RowStream<'_> {
+        set_batch_size(&mut command, batch_size);
+        futures::stream::try_unfold(CursorState::Start(database, command), move |state| async move {
+            let (result, cursor) = match state {
+                CursorState::Start(database, command) => {
+                    let name = command.keys().next().cloned().unwrap_or_default();
+                    let (batch, cursor) = self.open_command(&database, command).await?;
+                    (batch_result(&name, batch), cursor)
+                }
+                CursorState::Next(cursor) => {
+                    let (batch, cursor) = self.get_more(*cursor, batch_size).await?;
+                    if batch.is_empty() && cursor.is_none() {
+                        return Ok(None);
+                    }
+                    (documents_to_result(batch), cursor)
+                }
+                CursorState::Done => return Ok::<_, DatabaseError>(None),
+            };
+            Ok(Some((result, cursor.map_or(CursorState::Done, |cursor| CursorState::Next(Box::new(cursor))))))
+        }).boxed()
+    }
+}
+
+/// Converts a command given in MongoDB Extended JSON, so `{"$oid": ...}` and `{"$date": ...}`
+/// values keep their BSON types.
+fn json_command(command: Value) -> Result<Document, DatabaseError> {
+    validate_mongo_command(&command)?;
+    match Bson::try_from(command).map_err(|e| DatabaseError::InvalidQuery(e.to_string()))? {
+        Bson::Document(command) => Ok(command),
+        _ => Err(DatabaseError::InvalidQuery("A MongoDB command must be a JSON object".to_string())),
     }
 }
 
 #[async_trait::async_trait]
 impl DatabasePool for MongoPool {
     async fn execute(&self, query: &str, _params: Vec<Value>) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
-        let db = self.client.database(&self.db_name);
         let command: Document = serde_json::from_str(query).map_err(|e| DatabaseError::InvalidQuery(e.to_string()))?;
+        self.run_command(&self.db_name, command).await
+    }
 
-        let result = db.run_command(command, None).await
-            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+    async fn execute_native(&self, query: &NativeQuery) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        let (database, command) = native_command(query)?;
+        self.run_command(database.as_deref().unwrap_or(&self.db_name), json_command(command)?).await
+    }
 
-        let result_vec = match result.get("cursor") {
-            Some(cursor) => {
-                cursor.as_document()
-                    .and_then(|doc| doc.get("firstBatch"))
-                    .and_then(|batch| batch.as_array())
-                    .map(|arr| arr.to_vec())
-                    .unwrap_or_default()
-            },
-            None => vec![Bson::Document(result)],
-        };
+    async fn query_native(&self, query: &NativeQuery) -> Result<ResultSet, DatabaseError> {
+        let (database, command) = native_command(query)?;
+        self.query_command(database.as_deref().unwrap_or(&self.db_name), json_command(command)?).await
+    }
+
+    fn stream_native<'a>(&'a self, query: &'a NativeQuery, batch_size: usize) -> RowStream<'a> {
+        let command = native_command(query).and_then(|(database, command)| Ok((database, json_command(command)?)));
+        match command {
+            Ok((database, command)) => self.stream_command(database.clone().unwrap_or_else(|| self.db_name.clone()), command, batch_size),
+            Err(e) => failed(e),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Database for MongoPool {
+    async fn connect(connection_string: &str) -> Result<Self, DatabaseError> {
+        Self::new(connection_string).await
+    }
+
+    async fn disconnect(&self) -> Result<(), DatabaseError> {
+        // The driver closes its connections when the client is dropped
+        Ok(())
+    }
+
+    async fn execute_query(&self, query: &str) -> Result<Vec<Value>, DatabaseError> {
+        let rows = self.execute(query, Vec::new()).await?;
+        Ok(rows.into_iter().map(|row| Value::Object(row.into_iter().collect())).collect())
+    }
+
+    async fn list_databases(&self) -> Result<Vec<String>, DatabaseError> {
+        self.client.list_database_names(None, None).await
+            .map_err(mongo_error)
+    }
 
-        Ok(result_vec.into_iter().map(|doc| {
-            doc.as_document().unwrap().iter().map(|(k, v)| {
-                (k.to_string(), bson_to_json(v))
-            }).collect()
-        }).collect())
+    async fn list_collections(&self, database: &str) -> Result<Vec<String>, DatabaseError> {
+        self.client.database(database).list_collection_names(None).await
+            .map_err(mongo_error)
+    }
+
+    /// MongoDB has no fixed schema, so this returns the fields of a sample document with their BSON types.
+    async fn get_schema(&self, database: &str, collection: &str) -> Result<Value, DatabaseError> {
+        let sample = self.client.database(database).collection::<Document>(collection)
+            .find_one(None, None).await
+            .map_err(mongo_error)?;
+
+        Ok(Value::Array(sample.unwrap_or_default().iter().map(|(field, value)| {
+            json!({ "column_name": field, "data_type": format!("{:?}", value.element_type()) })
+        }).collect()))
+    }
+
+    async fn health_check(&self) -> Result<(), DatabaseError> {
+        self.client.database("admin").run_command(doc! { "ping": 1 }, None).await
+            .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
+        Ok(())
     }
 }
 
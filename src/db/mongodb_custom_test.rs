@@ -0,0 +1,84 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::mongodb_custom::{affected_documents, documents_to_result, open_cursor, prepare_command, set_batch_size};
+    use crate::db::result_set::DataType;
+    use mongodb::bson::{doc, oid::ObjectId, Bson};
+    use serde_json::Value;
+    use std::time::Duration;
+
+    #[test]
+    fn test_long_running_commands_get_a_deadline_and_a_tag() {
+        let mut find = doc! { "find": "users", "filter": { "active": true } };
+        assert!(prepare_command(&mut find, "q1", Some(Duration::from_secs(30))));
+        assert_eq!(find.get_i64("maxTimeMS").unwrap(), 30_000);
+        assert_eq!(find.get_str("comment").unwrap(), "q1");
+        assert_eq!(find.keys().next().map(String::as_str), Some("find"), "The command name must stay first");
+
+        let mut aggregate = doc! { "aggregate": "users", "pipeline": [], "cursor": {}, "maxTimeMS": 500, "comment": "mine" };
+        assert!(!prepare_command(&mut aggregate, "q2", Some(Duration::from_secs(30))), "A user comment cannot be replaced");
+        assert_eq!(aggregate.get_i32("maxTimeMS").unwrap(), 500, "An explicit limit should be kept");
+
+        let mut insert = doc! { "insert": "users", "documents": [] };
+        assert!(!prepare_command(&mut insert, "q3", Some(Duration::from_secs(30))));
+        assert_eq!(insert, doc! { "insert": "users", "documents": [] });
+
+        let mut count = doc! { "count": "users" };
+        assert!(prepare_command(&mut count, "q4", None));
+        assert!(!count.contains_key("maxTimeMS"));
+    }
+
+    #[test]
+    fn test_open_cursors_are_found() {
+        let open = doc! { "cursor": { "firstBatch": [], "id": 42_i64, "ns": "shop.orders.archive" }, "ok": 1 };
+        assert_eq!(open_cursor(&open), Some(("shop".to_string(), "orders.archive".to_string(), 42)));
+
+        let exhausted = doc! { "cursor": { "firstBatch": [], "id": 0_i64, "ns": "shop.orders" }, "ok": 1 };
+        assert_eq!(open_cursor(&exhausted), None);
+        assert_eq!(open_cursor(&doc! { "n": 3, "ok": 1 }), None);
+    }
+
+    #[test]
+    fn test_cursor_commands_get_the_batch_size() {
+        let mut find = doc! { "find": "users" };
+        set_batch_size(&mut find, 100);
+        assert_eq!(find.get_i64("batchSize").unwrap(), 100);
+
+        let mut aggregate = doc! { "aggregate": "users", "pipeline": [], "cursor": {} };
+        set_batch_size(&mut aggregate, 100);
+        assert_eq!(aggregate.get_document("cursor").unwrap().get_i64("batchSize").unwrap(), 100);
+
+        let mut explicit = doc! { "find": "users", "batchSize": 5 };
+        set_batch_size(&mut explicit, 100);
+        assert_eq!(explicit.get_i32("batchSize").unwrap(), 5, "An explicit batch size should be kept");
+
+        let mut count = doc! { "count": "users" };
+        set_batch_size(&mut count, 100);
+        assert_eq!(count, doc! { "count": "users" });
+    }
+
+    #[test]
+    fn test_documents_keep_field_order_and_bson_types() {
+        let id = ObjectId::new();
+        let result = documents_to_result(vec![
+            Bson::Document(doc! { "_id": id, "name": "a", "count": 1_i64 }),
+            Bson::Document(doc! { "_id": ObjectId::new(), "count": 2.5, "tags": ["x"] }),
+        ]);
+        let names: Vec<_> = result.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["_id", "name", "count", "tags"]);
+        assert_eq!((result.columns[0].source_type.as_str(), result.columns[0].nullable), ("objectId", false));
+        assert_eq!(result.columns[2].source_type, "long");
+        assert_eq!(result.columns[2].data_type, DataType::Float, "Longs and doubles merge to floats");
+        assert!(result.columns[1].nullable, "A field missing from a document is nullable");
+        assert_eq!(result.rows[1][1], Value::Null);
+        assert!(result.rows[0][0].as_str().is_some_and(|value| value.contains(&id.to_string())));
+        assert_eq!(result.columns[3].data_type, DataType::Json);
+    }
+
+    #[test]
+    fn test_write_commands_report_affected_documents() {
+        assert_eq!(affected_documents("insert", &doc! { "n": 3, "ok": 1 }), Some(3));
+        assert_eq!(affected_documents("delete", &doc! { "n": 2_i64, "ok": 1 }), Some(2));
+        assert_eq!(affected_documents("update", &doc! { "n": 5, "nModified": 4, "ok": 1 }), Some(4));
+        assert_eq!(affected_documents("count", &doc! { "n": 7, "ok": 1 }), None);
+    }
+}
@@ -0,0 +1,193 @@
+//! Queries typed by the user in the backend's own language, as opposed to `QueryBuilder` output.
+//! Every variant names its language with a `language` tag, e.g.
+//! `{"language": "sql", "sql": "SELECT * FROM users WHERE id = $1", "params": [1]}`.
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use crate::db::elasticsearch_custom::{ElasticsearchOperation, ElasticsearchRequest};
+use crate::db::query_builder::DatabaseType;
+use crate::db::redis_command;
+use crate::db::result_set::{Cell, ResultSet, ResultSetBuilder};
+use crate::utils::errors::DatabaseError;
+
+/// The MongoDB commands that can be run as native queries. They only read; any other command,
+/// including writes and administration, is rejected.
+pub const ALLOWED_MONGO_COMMANDS: [&str; 18] = [
+    "find", "aggregate", "count", "distinct", "explain", "listCollections", "listIndexes", "listDatabases",
+    "collStats", "dbStats", "dataSize", "serverStatus", "buildInfo", "hostInfo", "ping", "hello", "isMaster",
+    "connectionStatus",
+];
+
+/// Aggregation stages that write their output to a collection.
+const WRITING_STAGES: [&str; 2] = ["$out", "$merge"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "language", rename_all = "snake_case")]
+pub enum NativeQuery {
+    /// A single SQL statement with `$1`, `$2`, ... placeholders bound to `params`.
+    Sql {
+        sql: String,
+        #[serde(default)]
+        params: Vec<Value>,
+    },
+    /// A database command such as `{"find": "users", "filter": {...}}`, in MongoDB Extended JSON.
+    /// The command name must be the first key, and one of `ALLOWED_MONGO_COMMANDS`.
+    MongoCommand {
+        #[serde(default)]
+        database: Option<String>,
+        command: Value,
+    },
+    /// An aggregation pipeline run against `collection`.
+    MongoPipeline {
+        #[serde(default)]
+        database: Option<String>,
+        collection: String,
+        pipeline: Vec<Value>,
+    },
+    /// One or more `redis-cli` command lines, see `RedisPool::run_console`.
+    Redis {
+        #[serde(default)]
+        db: Option<i64>,
+        command: String,
+    },
+    /// A query DSL body routed to an index and API.
+    Elasticsearch(ElasticsearchRequest),
+}
+
+impl NativeQuery {
+    pub fn parse(query: &str) -> Result<Self, DatabaseError> {
+        serde_json::from_str(query).map_err(|e| DatabaseError::InvalidQuery(e.to_string()))
+    }
+
+    /// The type of database the query is written for.
+    pub fn database_type(&self) -> DatabaseType {
+        match self {
+            NativeQuery::Sql { .. } => DatabaseType::PostgreSQL,
+            NativeQuery::MongoCommand { .. } | NativeQuery::MongoPipeline { .. } => DatabaseType::MongoDB,
+            NativeQuery::Redis { .. } => DatabaseType::Redis,
+            NativeQuery::Elasticsearch(_) => DatabaseType::Elasticsearch,
+        }
+    }
+
+    pub fn language(&self) -> &'static str {
+        match self {
+            NativeQuery::Sql { .. } => "sql",
+            NativeQuery::MongoCommand { .. } => "mongo_command",
+            NativeQuery::MongoPipeline { .. } => "mongo_pipeline",
+            NativeQuery::Redis { .. } => "redis",
+            NativeQuery::Elasticsearch(_) => "elasticsearch",
+        }
+    }
+
+    /// Whether the query only reads, so it can be sent again after a transient failure. SQL
+    /// counts as a read when it starts with `SELECT`, `SHOW`, `VALUES` or `TABLE`; a `WITH` query
+    /// may modify data and is not.
+    pub fn is_read(&self) -> bool {
+        match self {
+            NativeQuery::Sql { sql, .. } => {
+                let keyword = sql.trim_start().split(|c: char| !c.is_ascii_alphabetic()).next().unwrap_or_default();
+                ["SELECT", "SHOW", "VALUES", "TABLE"].iter().any(|read| read.eq_ignore_ascii_case(keyword))
+            }
+            NativeQuery::MongoCommand { .. } | NativeQuery::MongoPipeline { .. } => true,
+            NativeQuery::Redis { command, .. } => redis_command::parse_batch(command)
+                .is_ok_and(|batch| batch.commands.iter().all(|args| redis_command::is_read_only(args))),
+            NativeQuery::Elasticsearch(request) => request.operation == ElasticsearchOperation::Search,
+        }
+    }
+
+    /// The error returned by pools that receive a query in another language.
+    pub fn unsupported(&self) -> DatabaseError {
+        DatabaseError::UnsupportedOperation(format!("{} queries are not supported by this database", self.language()))
+    }
+}
+
+/// Checks that `sql` holds exactly one statement and returns it without trailing semicolons.
+/// Quoted strings, quoted identifiers, dollar-quoted bodies and comments are skipped.
+pub fn validate_sql(sql: &str) -> Result<String, DatabaseError> {
+    let bytes = sql.as_bytes();
+    let mut statement_end = None;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'\'' | b'"') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += 1;
+                }
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = sql[i + 2..].find("*/").map_or(bytes.len(), |end| i + 2 + end + 1);
+            }
+            b'$' => {
+                // `$tag$ ... $tag$`; `$1` placeholders have no closing `$`
+                let tag_end = sql[i + 1..].find(|c: char| !(c.is_alphanumeric() || c == '_')).map(|end| i + 1 + end);
+                if let Some(tag_end) = tag_end.filter(|&end| bytes[end] == b'$' && !bytes[i + 1].is_ascii_digit()) {
+                    let tag = &sql[i..=tag_end];
+                    i = sql[tag_end + 1..].find(tag).map_or(bytes.len(), |end| tag_end + end + tag.len());
+                }
+            }
+            b';' => {
+                statement_end.get_or_insert(i);
+            }
+            c if !c.is_ascii_whitespace() && statement_end.is_some() => {
+                return Err(DatabaseError::InvalidQuery("Only one SQL statement can be run at a time".to_string()));
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let statement = sql[..statement_end.unwrap_or(sql.len())].trim();
+    if statement.is_empty() {
+        return Err(DatabaseError::InvalidQuery("Empty query".to_string()));
+    }
+    Ok(statement.to_string())
+}
+
+/// Returns the name of a MongoDB command, which is its first key, rejecting commands that are
+/// not in `ALLOWED_MONGO_COMMANDS` and pipelines ending in `$out` or `$merge`.
+pub fn validate_mongo_command(command: &Value) -> Result<String, DatabaseError> {
+    let name = command.as_object()
+        .and_then(|command| command.keys().next())
+        .ok_or_else(|| DatabaseError::InvalidQuery("A MongoDB command must be a non-empty JSON object".to_string()))?;
+
+    if !ALLOWED_MONGO_COMMANDS.iter().any(|allowed| allowed.eq_ignore_ascii_case(name)) {
+        return Err(DatabaseError::UnsupportedOperation(format!("{} is not allowed as a native query", name)));
+    }
+    let stages = command["pipeline"].as_array().into_iter().flatten();
+    if let Some(stage) = stages.filter_map(|stage| stage.as_object()?.keys().next()).find(|stage| WRITING_STAGES.contains(&stage.as_str())) {
+        return Err(DatabaseError::UnsupportedOperation(format!("The {} stage is not allowed in a native query", stage)));
+    }
+    Ok(name.clone())
+}
+
+/// Shapes values into rows: objects keep their fields, anything else becomes a `value` column.
+pub fn values_to_rows(values: Vec<Value>) -> Vec<HashMap<String, Value>> {
+    values.into_iter().map(|value| match value {
+        Value::Object(fields) => fields.into_iter().collect(),
+        value => [("value".to_string(), value)].into_iter().collect(),
+    }).collect()
+}
+
+/// Shapes values into a result like `values_to_rows`, keeping the order of object fields.
+pub fn values_to_result(values: Vec<Value>) -> ResultSet {
+    let mut builder = ResultSetBuilder::default();
+    for value in values {
+        match value {
+            Value::Object(fields) => builder.push_row(fields.into_iter().map(|(name, value)| Cell::json(name, value))),
+            value => builder.push_row([Cell::json("value", value)]),
+        }
+    }
+    builder.finish()
+}
+
+/// The command running `pipeline` against `collection` with a default cursor.
+pub fn pipeline_command(collection: &str, pipeline: &[Value]) -> Value {
+    json!({ "aggregate": collection, "pipeline": pipeline, "cursor": {} })
+}
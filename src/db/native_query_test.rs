@@ -0,0 +1,96 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::elasticsearch_custom::ElasticsearchOperation;
+    use crate::db::native_query::{pipeline_command, validate_mongo_command, validate_sql, values_to_result, values_to_rows, NativeQuery};
+    use crate::db::query_builder::DatabaseType;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_tagged_queries() {
+        let sql = NativeQuery::parse(r#"{"language": "sql", "sql": "SELECT $1", "params": [1]}"#).unwrap();
+        assert_eq!(sql, NativeQuery::Sql { sql: "SELECT $1".to_string(), params: vec![json!(1)] });
+        assert_eq!(sql.database_type(), DatabaseType::PostgreSQL);
+
+        let redis = NativeQuery::parse(r#"{"language": "redis", "command": "GET key"}"#).unwrap();
+        assert_eq!(redis, NativeQuery::Redis { db: None, command: "GET key".to_string() });
+
+        let search = NativeQuery::parse(r#"{"language": "elasticsearch", "index": "logs", "body": {"query": {"match_all": {}}}}"#).unwrap();
+        match search {
+            NativeQuery::Elasticsearch(request) => {
+                assert_eq!(request.index, "logs");
+                assert_eq!(request.operation, ElasticsearchOperation::Search);
+            }
+            other => panic!("unexpected query {:?}", other),
+        }
+
+        assert!(NativeQuery::parse(r#"{"language": "cql", "query": "SELECT 1"}"#).is_err());
+    }
+
+    #[test]
+    fn test_reads_are_told_apart_from_writes() {
+        let is_read = |query: &str| NativeQuery::parse(query).unwrap().is_read();
+        assert!(is_read(r#"{"language": "sql", "sql": "  select * from users"}"#));
+        assert!(is_read(r#"{"language": "sql", "sql": "SHOW search_path"}"#));
+        assert!(!is_read(r#"{"language": "sql", "sql": "WITH d AS (DELETE FROM users RETURNING *) SELECT * FROM d"}"#));
+        assert!(!is_read(r#"{"language": "sql", "sql": "UPDATE users SET name = 'a'"}"#));
+
+        assert!(is_read(r#"{"language": "mongo_command", "command": {"find": "users"}}"#));
+        assert!(is_read(r#"{"language": "redis", "command": "GET a\nhgetall b\nXINFO STREAM events"}"#));
+        assert!(!is_read(r#"{"language": "redis", "command": "GET a\nINCR b"}"#));
+        assert!(!is_read(r#"{"language": "redis", "command": "XREAD BLOCK 5000 STREAMS events $"}"#));
+        assert!(!is_read(r#"{"language": "redis", "command": "XINFO HELP"}"#));
+
+        assert!(is_read(r#"{"language": "elasticsearch", "index": "logs"}"#));
+        assert!(!is_read(r#"{"language": "elasticsearch", "index": "logs", "operation": "delete_by_query"}"#));
+    }
+
+    #[test]
+    fn test_validate_sql_single_statement() {
+        assert_eq!(validate_sql("  SELECT 1;  ").unwrap(), "SELECT 1");
+        assert_eq!(validate_sql("SELECT ';' AS a, \"b;c\" FROM t -- trailing; comment").unwrap(), "SELECT ';' AS a, \"b;c\" FROM t -- trailing; comment");
+        assert_eq!(validate_sql("SELECT $$a;b$$, $tag$;$tag$, $1 /* ; */;").unwrap(), "SELECT $$a;b$$, $tag$;$tag$, $1 /* ; */");
+        assert_eq!(validate_sql("SELECT 1; -- done").unwrap(), "SELECT 1");
+        assert!(validate_sql("SELECT 1; DROP TABLE users").is_err());
+        assert!(validate_sql(" ; ").is_err());
+    }
+
+    #[test]
+    fn test_validate_mongo_command() {
+        assert_eq!(validate_mongo_command(&json!({ "find": "users", "filter": {} })).unwrap(), "find");
+        assert!(validate_mongo_command(&json!({ "dropDatabase": 1 })).is_err());
+        for command in [
+            json!({ "drop": "users" }),
+            json!({ "delete": "users", "deletes": [{ "q": {}, "limit": 0 }] }),
+            json!({ "update": "users", "updates": [{ "q": {}, "u": { "$set": { "admin": true } } }] }),
+            json!({ "dropIndexes": "users", "index": "*" }),
+            json!({ "renameCollection": "app.users", "to": "app.old" }),
+            json!({ "createUser": "eve", "pwd": "x", "roles": ["root"] }),
+            json!({ "grantRolesToUser": "eve", "roles": ["root"] }),
+            json!({ "applyOps": [] }),
+        ] {
+            assert!(validate_mongo_command(&command).is_err(), "{} should be rejected", command);
+        }
+        assert_eq!(validate_mongo_command(&json!({ "aggregate": "users", "pipeline": [{ "$match": {} }], "cursor": {} })).unwrap(), "aggregate");
+        assert!(validate_mongo_command(&json!({ "aggregate": "users", "pipeline": [{ "$match": {} }, { "$out": "copy" }], "cursor": {} })).is_err(),
+            "Pipelines should not write to collections");
+        assert!(validate_mongo_command(&json!({ "aggregate": "users", "pipeline": [{ "$merge": { "into": "copy" } }], "cursor": {} })).is_err());
+        assert!(validate_mongo_command(&json!({})).is_err());
+        assert!(validate_mongo_command(&json!([{ "find": "users" }])).is_err());
+    }
+
+    #[test]
+    fn test_result_shaping() {
+        let rows = values_to_rows(vec![json!({ "command": "GET a", "text": "\"1\"" }), json!(3)]);
+        assert_eq!(rows[0]["command"], json!("GET a"));
+        assert_eq!(rows[1]["value"], json!(3));
+
+        let result = values_to_result(vec![json!({ "text": "\"1\"", "command": "GET a" }), json!(3)]);
+        let names: Vec<_> = result.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["text", "command", "value"], "Object fields should keep their order");
+        assert_eq!(result.rows[1], vec![json!(null), json!(null), json!(3)]);
+
+        assert_eq!(pipeline_command("users", &[json!({ "$match": { "age": 3 } })]), json!({
+            "aggregate": "users", "pipeline": [{ "$match": { "age": 3 } }], "cursor": {}
+        }));
+    }
+}
@@ -1,6 +1,7 @@
 use crate::db::{
     Database, DatabasePool,
-    query_builder::{QueryBuilder, QueryOperation, Operator, OrderDirection, Field}
+    native_query::{validate_sql, NativeQuery},
+    query_builder::{QueryBuilder, QueryOperation, Operator, OrderDirection}
 };
 use crate::utils::errors::{DatabaseError, QueryBuilderError};
 use async_trait::async_trait;
@@ -8,7 +9,18 @@ use serde_json::{json, Value};
 use std::collections::HashMap;
 use tokio_postgres;
 use deadpool_postgres;
-use tokio_postgres::types::Type;
+use tokio_postgres::types::{to_sql_checked, FromSql, IsNull, ToSql, Type};
+use bytes::BytesMut;
+use tokio_postgres::tls::MakeTlsConnect;
+use tokio::io::{AsyncRead, AsyncWrite};
+use crate::config::config::{SourceOptions, TlsConfig, TlsVerify};
+use crate::db::cancel::spawn_cancel;
+use crate::db::result_set::{Column, DataType, ResultSet};
+use crate::db::row_stream::{concatenated, failed, RowStream, DEFAULT_BATCH_SIZE};
+use crate::db::tls::{tls_error, TlsFiles, TlsSupport};
+use futures::StreamExt;
+use std::pin::Pin;
+use std::time::Duration;
 
 pub fn build_query(builder: &QueryBuilder) -> Result<String, QueryBuilderError> {
     let mut query = match builder.operation {
@@ -82,14 +94,149 @@ fn operator_to_sql(operator: &Operator) -> &'static str {
 
 pub struct PostgresPool {
     pool: deadpool_postgres::Pool,
+    tls: Option<PostgresTls>,
+    idle_timeout: Option<Duration>,
 }
 
 impl PostgresPool {
     pub async fn new(connection_string: &str) -> Result<Self, DatabaseError> {
-        let config = connection_string.parse()
-            .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
-        let pool = deadpool_postgres::Pool::new(config);
-        Ok(PostgresPool { pool })
+        Self::open(connection_string, &SourceOptions::default()).await
+    }
+
+    /// Opens a pool with the options of a configured source. With TLS enabled, connections
+    /// that cannot be encrypted are refused. `query_timeout` becomes the `statement_timeout`
+    /// of every connection.
+    pub async fn open(connection_string: &str, options: &SourceOptions) -> Result<Self, DatabaseError> {
+        let mut config: tokio_postgres::Config = connection_string.parse()
+            .map_err(|e: tokio_postgres::Error| DatabaseError::ConfigurationError(e.to_string()))?;
+        if let Some(timeout) = options.connect_timeout() {
+            config.connect_timeout(timeout);
+        }
+        if let Some(timeout) = options.query_timeout() {
+            let statement_timeout = format!("-c statement_timeout={}", timeout.as_millis());
+            let server_options = match config.get_options() {
+                Some(existing) => format!("{} {}", existing, statement_timeout),
+                None => statement_timeout,
+            };
+            config.options(&server_options);
+        }
+
+        let tls = match options.tls.as_ref().filter(|tls| tls.enabled) {
+            Some(tls) => {
+                config.ssl_mode(tokio_postgres::config::SslMode::Require);
+                Some(PostgresTls::new(tls)?)
+            }
+            None => None,
+        };
+        let manager = match &tls {
+            Some(tls) => deadpool_postgres::Manager::new(config, tls.clone()),
+            None => deadpool_postgres::Manager::new(config, tokio_postgres::NoTls),
+        };
+
+        let mut builder = deadpool_postgres::Pool::builder(manager)
+            .runtime(deadpool_postgres::Runtime::Tokio1)
+            .wait_timeout(options.acquire_timeout());
+        if let Some(size) = options.pool_size {
+            builder = builder.max_size(size);
+        }
+        let pool = builder.build().map_err(|e| DatabaseError::ConfigurationError(e.to_string()))?;
+        Ok(PostgresPool { pool, tls, idle_timeout: options.idle_timeout() })
+    }
+
+    /// Takes a connection from the pool for a query, closing connections that sat idle for
+    /// longer than `idle_timeout` first.
+    async fn start_query(&self) -> Result<RunningQuery, DatabaseError> {
+        if let Some(idle_timeout) = self.idle_timeout {
+            self.pool.retain(|_, metrics| metrics.last_used() < idle_timeout);
+        }
+        let client = self.pool.get().await.map_err(pool_error)?;
+        Ok(RunningQuery { client: Some(client), tls: self.tls.clone() })
+    }
+}
+
+/// A pooled connection running a query. Dropped before `finish`, e.g. because the query's
+/// deadline passed, it cancels the query and closes the connection instead of returning it to
+/// the pool, so the cancel request cannot hit a later query.
+struct RunningQuery {
+    client: Option<deadpool_postgres::Object>,
+    tls: Option<PostgresTls>,
+}
+
+impl RunningQuery {
+    fn finish(mut self) {
+        self.client.take();
+    }
+}
+
+impl std::ops::Deref for RunningQuery {
+    type Target = deadpool_postgres::Object;
+
+    fn deref(&self) -> &Self::Target {
+        self.client.as_ref().expect("The client is only taken when the query ends")
+    }
+}
+
+impl Drop for RunningQuery {
+    fn drop(&mut self) {
+        let Some(client) = self.client.take() else {
+            return;
+        };
+        let client = deadpool_postgres::Object::take(client);
+        let token = client.cancel_token();
+        let tls = self.tls.take();
+        spawn_cancel(async move {
+            let result = match tls {
+                Some(tls) => token.cancel_query(tls).await,
+                None => token.cancel_query(tokio_postgres::NoTls).await,
+            };
+            drop(client);
+            result.map_err(|e| DatabaseError::ConnectionError(e.to_string()))
+        });
+    }
+}
+
+/// Native TLS connector that sends the configured `server_name` instead of the host.
+#[derive(Clone)]
+pub(crate) struct PostgresTls {
+    connector: native_tls::TlsConnector,
+    server_name: Option<String>,
+}
+
+impl PostgresTls {
+    pub(crate) fn new(tls: &TlsConfig) -> Result<Self, DatabaseError> {
+        TlsSupport { verify_ca_only: true, server_name: true }.check("PostgreSQL", tls)?;
+        let files = TlsFiles::load(tls)?;
+
+        let mut builder = native_tls::TlsConnector::builder();
+        if let Some(ca) = &files.ca {
+            builder.add_root_certificate(native_tls::Certificate::from_pem(ca).map_err(tls_error)?);
+        }
+        if let Some((cert, key)) = files.identity() {
+            // The key must be PKCS#8, i.e. `BEGIN PRIVATE KEY`
+            builder.identity(native_tls::Identity::from_pkcs8(cert, key).map_err(tls_error)?);
+        }
+        match tls.verify {
+            TlsVerify::Full => {}
+            TlsVerify::CaOnly => { builder.danger_accept_invalid_hostnames(true); }
+            TlsVerify::Disabled => { builder.danger_accept_invalid_certs(true); }
+        }
+
+        let connector = builder.build().map_err(tls_error)?;
+        Ok(PostgresTls { connector, server_name: tls.server_name.clone() })
+    }
+}
+
+impl<S> MakeTlsConnect<S> for PostgresTls
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Stream = postgres_native_tls::TlsStream<S>;
+    type TlsConnect = postgres_native_tls::TlsConnector;
+    type Error = native_tls::Error;
+
+    fn make_tls_connect(&mut self, host: &str) -> Result<Self::TlsConnect, Self::Error> {
+        let domain = self.server_name.as_deref().unwrap_or(host);
+        Ok(postgres_native_tls::TlsConnector::new(self.connector.clone(), domain))
     }
 }
 
@@ -105,15 +252,16 @@ impl Database for PostgresPool {
     }
 
     async fn execute_query(&self, query: &str) -> Result<Vec<Value>, DatabaseError> {
-        let client = self.pool.get().await
-            .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
-        let rows = client.query(query, &[]).await
-            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        let client = self.start_query().await?;
+        let rows = client.query(query, &[]).await;
+        client.finish();
+        let rows = rows.map_err(postgres_error)?;
 
         Ok(rows.into_iter().map(|row| {
             let json: serde_json::Map<String, Value> = row.columns()
                 .iter()
-                .map(|column| (column.name().to_string(), postgres_value_to_json(&row, column)))
+                .enumerate()
+                .map(|(index, column)| (column.name().to_string(), postgres_value_to_json(&row, index)))
                 .collect();
             Value::Object(json)
         }).collect())
@@ -143,35 +291,292 @@ impl Database for PostgresPool {
         let result = self.execute_query(&query).await?;
         Ok(Value::Array(result))
     }
+
+    async fn health_check(&self) -> Result<(), DatabaseError> {
+        let client = self.start_query().await?;
+        let result = client.simple_query("SELECT 1").await;
+        client.finish();
+        result.map(|_| ()).map_err(|e| DatabaseError::ConnectionError(e.to_string()))
+    }
 }
 
 #[async_trait]
 impl DatabasePool for PostgresPool {
     async fn execute(&self, query: &str, params: Vec<Value>) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
-        let client = self.pool.get().await
-            .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
-        let stmt = client.prepare(query).await
-            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        let params: Vec<SqlParam> = params.iter().map(SqlParam).collect();
+        let params: Vec<&(dyn ToSql + Sync)> = params.iter().map(|p| p as &(dyn ToSql + Sync)).collect();
 
-        let params: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = params.iter()
-            .map(|v| v as &(dyn tokio_postgres::types::ToSql + Sync))
-            .collect();
+        // Errors come from the server, so the query is over either way
+        let client = self.start_query().await?;
+        let rows = match client.prepare(query).await {
+            Ok(stmt) => client.query(&stmt, &params).await,
+            Err(e) => Err(e),
+        };
+        client.finish();
+        let rows = rows.map_err(postgres_error)?;
 
-        let rows = client.query(&stmt, &params)
-            .await
-            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+        Ok(rows.iter().map(row_to_map).collect())
+    }
 
-        Ok(rows.into_iter().map(|row| {
-            row.columns().iter().map(|column| {
-                (column.name().to_string(), postgres_value_to_json(&row, column))
-            }).collect()
-        }).collect())
+    /// Keeps the columns of the prepared statement, so joined columns sharing a name are all
+    /// returned in the order they were selected.
+    async fn query_builder(&self, builder: &QueryBuilder) -> Result<ResultSet, DatabaseError> {
+        let query = builder.build()?;
+        let params: Vec<SqlParam> = builder.values.iter().map(SqlParam).collect();
+        let params: Vec<&(dyn ToSql + Sync)> = params.iter().map(|p| p as &(dyn ToSql + Sync)).collect();
+
+        let client = self.start_query().await?;
+        let result = async {
+            let stmt = client.prepare(&query).await?;
+            let columns: Vec<Column> = stmt.columns().iter().map(result_column).collect();
+            if columns.is_empty() {
+                let affected_rows = client.execute(&stmt, &params).await?;
+                return Ok(ResultSet { affected_rows: Some(affected_rows), ..Default::default() });
+            }
+            let rows = client.query(&stmt, &params).await?;
+            let rows = rows.iter().map(|row| (0..row.len()).map(|index| postgres_value_to_json(row, index)).collect()).collect();
+            Ok(ResultSet { columns, rows, ..Default::default() })
+        }.await;
+        client.finish();
+        result.map_err(postgres_error)
+    }
+
+    async fn execute_native(&self, query: &NativeQuery) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        match query {
+            NativeQuery::Sql { sql, params } => self.execute(&validate_sql(sql)?, params.clone()).await,
+            other => Err(other.unsupported()),
+        }
     }
+
+    /// Reads every batch of `stream_native`, so both return the same columns.
+    async fn query_native(&self, query: &NativeQuery) -> Result<ResultSet, DatabaseError> {
+        concatenated(self.stream_native(query, DEFAULT_BATCH_SIZE)).await
+    }
+
+    /// Reads rows as the server sends them. Dropping the stream early cancels the query.
+    fn stream_native<'a>(&'a self, query: &'a NativeQuery, batch_size: usize) -> RowStream<'a> {
+        let NativeQuery::Sql { sql, params } = query else {
+            return failed(query.unsupported());
+        };
+        let batch_size = batch_size.max(1);
+
+        futures::stream::try_unfold(SqlRows::Start, move |state| async move {
+            let (client, columns, mut rows, first) = match state {
+                SqlRows::Start => {
+                    let sql = validate_sql(sql)?;
+                    let client = self.start_query().await?;
+                    let params = params.iter().map(SqlParam);
+                    let started = async {
+                        let stmt = client.prepare(&sql).await?;
+                        let rows = client.query_raw(&stmt, params).await?;
+                        Ok::<_, tokio_postgres::Error>((stmt.columns().iter().map(result_column).collect(), rows))
+                    }.await;
+                    match started {
+                        Ok((columns, rows)) => (client, columns, Box::pin(rows), true),
+                        Err(e) => {
+                            client.finish();
+                            return Err(postgres_error(e));
+                        }
+                    }
+                }
+                SqlRows::Reading { client, columns, rows } => (client, columns, rows, false),
+                SqlRows::Done => return Ok(None),
+            };
+
+            let mut batch = Vec::with_capacity(batch_size);
+            while batch.len() < batch_size {
+                match rows.next().await {
+                    Some(Ok(row)) => batch.push((0..row.len()).map(|index| postgres_value_to_json(&row, index)).collect()),
+                    Some(Err(e)) => {
+                        client.finish();
+                        return Err(postgres_error(e));
+                    }
+                    None => {
+                        client.finish();
+                        if batch.is_empty() && !first {
+                            return Ok(None);
+                        }
+                        // Statements without columns, such as an UPDATE, report the rows they wrote
+                        let affected_rows = if columns.is_empty() { rows.rows_affected() } else { None };
+                        let result = ResultSet { columns, rows: batch, affected_rows, ..Default::default() };
+                        return Ok(Some((result, SqlRows::Done)));
+                    }
+                }
+            }
+            let result = ResultSet { columns: columns.clone(), rows: batch, ..Default::default() };
+            Ok(Some((result, SqlRows::Reading { client, columns, rows })))
+        }).boxed()
+    }
+}
+
+/// Classifies a driver error. Errors reported by the server keep their SQLSTATE; I/O errors and
+/// closed connections are connection errors, e.g. after a reset or a failover.
+pub(crate) fn postgres_error(error: tokio_postgres::Error) -> DatabaseError {
+    if let Some(db_error) = error.as_db_error() {
+        return DatabaseError::postgres(db_error.code().code(), db_error.message());
+    }
+    let io_error = std::error::Error::source(&error).is_some_and(|source| source.is::<std::io::Error>());
+    if error.is_closed() || io_error {
+        return DatabaseError::ConnectionError(error.to_string());
+    }
+    DatabaseError::QueryError(error.to_string())
+}
+
+/// Classifies an error getting a connection from the pool, such as a failed login.
+fn pool_error(error: deadpool_postgres::PoolError) -> DatabaseError {
+    match error {
+        deadpool_postgres::PoolError::Backend(error) => match postgres_error(error) {
+            DatabaseError::QueryError(message) => DatabaseError::ConnectionError(message),
+            error => error,
+        },
+        error => DatabaseError::ConnectionError(error.to_string()),
+    }
+}
+
+/// Where a streamed SQL query stands. The connection is held until the last row is read.
+#[allow(clippy::large_enum_variant)]
+enum SqlRows {
+    Start,
+    Reading { client: RunningQuery, columns: Vec<Column>, rows: Pin<Box<tokio_postgres::RowStream>> },
+    Done,
+}
+
+/// A JSON parameter, bound as the type the server expects for it, e.g. `1` as an `int4` or
+/// `"2024-01-31"` as a `date`. `serde_json::Value` on its own only binds to `json` and `jsonb`.
+#[derive(Debug)]
+pub(crate) struct SqlParam<'a>(pub &'a Value);
+
+impl ToSql for SqlParam<'_> {
+    fn to_sql(&self, ty: &Type, out: &mut BytesMut) -> Result<IsNull, Box<dyn std::error::Error + Sync + Send>> {
+        let value = self.0;
+        let integer = || value.as_i64().ok_or_else(|| format!("{} is not an integer", value));
+        match (value, ty) {
+            (Value::Null, _) => Ok(IsNull::Yes),
+            (_, &Type::JSON | &Type::JSONB) => value.to_sql(ty, out),
+            (Value::Bool(b), &Type::BOOL) => b.to_sql(ty, out),
+            (Value::Number(_), &Type::INT2) => i16::try_from(integer()?)?.to_sql(ty, out),
+            (Value::Number(_), &Type::INT4) => i32::try_from(integer()?)?.to_sql(ty, out),
+            (Value::Number(_), &Type::INT8) => integer()?.to_sql(ty, out),
+            (Value::Number(n), &Type::FLOAT4) => (n.as_f64().unwrap_or_default() as f32).to_sql(ty, out),
+            (Value::Number(n), &Type::FLOAT8) => n.as_f64().unwrap_or_default().to_sql(ty, out),
+            (Value::String(s), &Type::TIMESTAMPTZ) => chrono::DateTime::parse_from_rfc3339(s)?.to_sql(ty, out),
+            (Value::String(s), &Type::TIMESTAMP) => s.parse::<chrono::NaiveDateTime>()?.to_sql(ty, out),
+            (Value::String(s), &Type::DATE) => s.parse::<chrono::NaiveDate>()?.to_sql(ty, out),
+            (Value::String(s), &Type::TIME) => s.parse::<chrono::NaiveTime>()?.to_sql(ty, out),
+            (Value::String(s), &Type::TEXT | &Type::VARCHAR | &Type::BPCHAR | &Type::NAME | &Type::UNKNOWN) => s.as_str().to_sql(ty, out),
+            (Value::Bool(_) | Value::Number(_), &Type::TEXT | &Type::VARCHAR | &Type::BPCHAR) => value.to_string().as_str().to_sql(ty, out),
+            _ => Err(format!("cannot bind {} to a parameter of type {}", value, ty).into()),
+        }
+    }
+
+    fn accepts(_: &Type) -> bool {
+        true
+    }
+
+    to_sql_checked!();
+}
+
+/// A column value read as the text PostgreSQL would print for it: `name`, `numeric`, which
+/// may not fit a float, `bytea` as `\x` and hex digits, and `timetz`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct PgText(pub String);
+
+impl<'a> FromSql<'a> for PgText {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        let text = match *ty {
+            Type::NUMERIC => numeric_text(raw)?,
+            Type::BYTEA => raw.iter().fold("\\x".to_string(), |hex, byte| hex + &format!("{:02x}", byte)),
+            Type::TIMETZ => timetz_text(raw)?,
+            _ => std::str::from_utf8(raw)?.to_string(),
+        };
+        Ok(PgText(text))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(*ty, Type::NAME | Type::NUMERIC | Type::BYTEA | Type::TIMETZ)
+    }
+}
+
+/// Renders a binary `numeric`: a sign, a scale, and base 10000 digits from a weight.
+fn numeric_text(raw: &[u8]) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+    let word = |index: usize| raw.get(2 * index..2 * index + 2)
+        .map(|bytes| i16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or("truncated numeric value");
+    let (count, weight, sign, scale) = (word(0)? as usize, i32::from(word(1)?), word(2)? as u16, word(3)? as usize);
+    match sign {
+        0xC000 => return Ok("NaN".to_string()),
+        0xD000 => return Ok("Infinity".to_string()),
+        0xF000 => return Ok("-Infinity".to_string()),
+        _ => {}
+    }
+    let digits = (0..count).map(|index| word(4 + index)).collect::<Result<Vec<_>, _>>()?;
+    let digit = |position: i32| usize::try_from(position).ok().and_then(|position| digits.get(position).copied()).unwrap_or(0);
+
+    let mut text = if sign == 0x4000 { "-".to_string() } else { String::new() };
+    if weight < 0 {
+        text.push('0');
+    }
+    for position in 0..=weight {
+        text += &if position == 0 { digit(0).to_string() } else { format!("{:04}", digit(position)) };
+    }
+    if scale > 0 {
+        let mut fraction = String::new();
+        let mut position = weight + 1;
+        while fraction.len() < scale {
+            fraction += &format!("{:04}", digit(position));
+            position += 1;
+        }
+        fraction.truncate(scale);
+        text = text + "." + &fraction;
+    }
+    Ok(text)
+}
+
+/// Renders a binary `timetz`: microseconds since midnight and an offset in seconds west of UTC.
+fn timetz_text(raw: &[u8]) -> Result<String, Box<dyn std::error::Error + Sync + Send>> {
+    let (micros, offset) = raw.split_at_checked(8).ok_or("truncated timetz value")?;
+    let micros = i64::from_be_bytes(micros.try_into()?);
+    let offset = i32::from_be_bytes(offset.try_into()?);
+    let time = chrono::NaiveTime::from_num_seconds_from_midnight_opt(
+        u32::try_from(micros / 1_000_000)?,
+        u32::try_from(micros % 1_000_000 * 1000)?,
+    ).ok_or("timetz value out of range")?;
+    let offset = chrono::FixedOffset::east_opt(-offset).ok_or("timetz offset out of range")?;
+    Ok(format!("{}{}", time, offset))
+}
+
+/// A column of a prepared statement. PostgreSQL does not tell whether a result column may be null.
+fn result_column(column: &tokio_postgres::Column) -> Column {
+    let data_type = match *column.type_() {
+        Type::BOOL => DataType::Boolean,
+        Type::INT2 | Type::INT4 | Type::INT8 => DataType::Integer,
+        Type::FLOAT4 | Type::FLOAT8 => DataType::Float,
+        Type::NUMERIC => DataType::Decimal,
+        Type::VARCHAR | Type::TEXT | Type::BPCHAR | Type::NAME => DataType::Text,
+        Type::BYTEA => DataType::Binary,
+        Type::DATE => DataType::Date,
+        Type::TIME | Type::TIMETZ => DataType::Time,
+        Type::TIMESTAMP | Type::TIMESTAMPTZ => DataType::Timestamp,
+        Type::JSON | Type::JSONB => DataType::Json,
+        _ => DataType::Unknown,
+    };
+    Column {
+        name: column.name().to_string(),
+        source_type: column.type_().name().to_string(),
+        data_type,
+        nullable: true,
+    }
+}
+
+fn row_to_map(row: &tokio_postgres::Row) -> HashMap<String, Value> {
+    row.columns().iter()
+        .enumerate()
+        .map(|(index, column)| (column.name().to_string(), postgres_value_to_json(row, index)))
+        .collect()
 }
 
-fn postgres_value_to_json(row: &tokio_postgres::Row, column: &tokio_postgres::Column) -> Value {
-    let col_idx = column.ordinal();
-    match *column.type_() {
+fn postgres_value_to_json(row: &tokio_postgres::Row, col_idx: usize) -> Value {
+    match *row.columns()[col_idx].type_() {
         Type::BOOL => json!(row.get::<_, Option<bool>>(col_idx)),
         Type::INT2 => json!(row.get::<_, Option<i16>>(col_idx)),
         Type::INT4 => json!(row.get::<_, Option<i32>>(col_idx)),
@@ -179,6 +584,7 @@ fn postgres_value_to_json(row: &tokio_postgres::Row, column: &tokio_postgres::Co
         Type::FLOAT4 => json!(row.get::<_, Option<f32>>(col_idx)),
         Type::FLOAT8 => json!(row.get::<_, Option<f64>>(col_idx)),
         Type::VARCHAR | Type::TEXT | Type::BPCHAR => json!(row.get::<_, Option<String>>(col_idx)),
+        Type::NAME | Type::NUMERIC | Type::BYTEA | Type::TIMETZ => json!(row.get::<_, Option<PgText>>(col_idx).map(|text| text.0)),
         Type::JSON | Type::JSONB => json!(row.get::<_, Option<serde_json::Value>>(col_idx)),
         Type::TIMESTAMP => {
             let dt: Option<chrono::NaiveDateTime> = row.get(col_idx);
@@ -0,0 +1,61 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::postgres_custom::{PgText, SqlParam};
+    use bytes::BytesMut;
+    use serde_json::json;
+    use tokio_postgres::types::{FromSql, IsNull, ToSql, Type};
+
+    fn encode(value: serde_json::Value, ty: &Type) -> Result<Vec<u8>, String> {
+        let mut out = BytesMut::new();
+        match SqlParam(&value).to_sql_checked(ty, &mut out) {
+            Ok(IsNull::No) => Ok(out.to_vec()),
+            Ok(IsNull::Yes) => Ok(Vec::new()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_params_are_bound_as_the_expected_type() {
+        assert_eq!(encode(json!(1), &Type::INT4), Ok(1i32.to_be_bytes().to_vec()));
+        assert_eq!(encode(json!(1), &Type::INT8), Ok(1i64.to_be_bytes().to_vec()));
+        assert_eq!(encode(json!(1.5), &Type::FLOAT8), Ok(1.5f64.to_be_bytes().to_vec()));
+        assert_eq!(encode(json!("a"), &Type::TEXT), Ok(b"a".to_vec()));
+        assert_eq!(encode(json!(true), &Type::BOOL), Ok(vec![1]));
+        assert_eq!(encode(json!(null), &Type::INT4), Ok(Vec::new()), "Null should bind to any type");
+        assert_eq!(encode(json!({"a": 1}), &Type::JSONB), Ok(b"\x01{\"a\":1}".to_vec()));
+        assert!(encode(json!("2024-01-31"), &Type::DATE).is_ok());
+    }
+
+    #[test]
+    fn test_mismatched_params_are_rejected() {
+        assert!(encode(json!(70000), &Type::INT2).is_err(), "Out of range integers should be rejected");
+        assert!(encode(json!(1.5), &Type::INT4).is_err());
+        assert!(encode(json!("a"), &Type::INT4).is_err());
+        assert!(encode(json!("yesterday"), &Type::DATE).is_err());
+    }
+
+    fn decode(ty: &Type, raw: &[u8]) -> String {
+        PgText::from_sql(ty, raw).unwrap().0
+    }
+
+    /// The binary `numeric` with the given sign, weight, scale and base 10000 digits.
+    fn numeric(sign: u16, weight: i16, scale: u16, digits: &[i16]) -> Vec<u8> {
+        let header = [digits.len() as u16, weight as u16, sign, scale];
+        header.iter().map(|word| *word as i16).chain(digits.iter().copied()).flat_map(i16::to_be_bytes).collect()
+    }
+
+    #[test]
+    fn test_text_like_values_are_read() {
+        assert_eq!(decode(&Type::NAME, b"postgres"), "postgres");
+        assert_eq!(decode(&Type::NUMERIC, &numeric(0, 1, 2, &[1, 2345, 6700])), "12345.67");
+        assert_eq!(decode(&Type::NUMERIC, &numeric(0x4000, -1, 5, &[12])), "-0.00120");
+        assert_eq!(decode(&Type::NUMERIC, &numeric(0, 2, 0, &[3])), "300000000");
+        assert_eq!(decode(&Type::NUMERIC, &numeric(0, 0, 0, &[])), "0");
+        assert_eq!(decode(&Type::NUMERIC, &numeric(0xC000, 0, 0, &[])), "NaN");
+        assert_eq!(decode(&Type::BYTEA, &[0xde, 0xad, 0x01]), "\\xdead01");
+
+        let timetz: Vec<u8> = (45_296_500_000i64).to_be_bytes().into_iter().chain((-7200i32).to_be_bytes()).collect();
+        assert_eq!(decode(&Type::TIMETZ, &timetz), "12:34:56.500+02:00");
+        assert!(PgText::from_sql(&Type::TIMETZ, &timetz[..6]).is_err());
+    }
+}
@@ -1,7 +1,16 @@
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crate::db::cancel::with_timeout;
 use crate::db::{DatabasePool, elasticsearch_custom, mongodb_custom, postgres_custom, redis_custom};
-use crate::utils::errors::QueryBuilderError;
+use crate::db::native_query::NativeQuery;
+use crate::db::result_set::ResultSet;
+use crate::db::retry::{RetryPolicy, RETRY_METRICS};
+use crate::db::row_stream::RowStream;
+use crate::utils::errors::{DatabaseError, QueryBuilderError};
 
 // Type-safe field and table names
 macro_rules! define_type_safe_names {
@@ -35,7 +44,8 @@ macro_rules! define_type_safe_names {
 define_type_safe_names!(Field, Id, Name, Email, Age, CreatedAt, UpdatedAt);
 define_type_safe_names!(Table, Users, Posts, Comments, Products, Orders);
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DatabaseType {
     PostgreSQL,
     MongoDB,
@@ -96,6 +106,8 @@ pub struct QueryBuilder {
     pub limit: Option<usize>,
     pub offset: Option<usize>,
     pub values: Vec<Value>,
+    /// Set by `all`.
+    pub all: bool,
 }
 
 impl QueryBuilder {
@@ -110,6 +122,7 @@ impl QueryBuilder {
             limit: None,
             offset: None,
             values: Vec::new(),
+            all: false,
         }
     }
 
@@ -153,6 +166,13 @@ impl QueryBuilder {
         self
     }
 
+    /// Allows an update or delete without conditions to apply to every document. Elasticsearch
+    /// refuses such queries without it.
+    pub fn all(mut self) -> Self {
+        self.all = true;
+        self
+    }
+
     pub fn build(&self) -> Result<String, QueryBuilderError> {
         match self.database_type {
             DatabaseType::PostgreSQL => postgres_custom::build_query(self),
@@ -164,26 +184,152 @@ impl QueryBuilder {
     }
 }
 
+/// The pool used for each database type. Cloning shares the pools, so a query can keep running
+/// on a copy after the shared manager is replaced or locked for a rebuild.
+#[derive(Clone)]
 pub struct DatabaseManager {
-    pools: HashMap<DatabaseType, Box<dyn DatabasePool>>,
+    pools: HashMap<DatabaseType, Arc<dyn DatabasePool>>,
+    /// The name of the source serving each type, for pools added with `add_source`.
+    sources: HashMap<DatabaseType, String>,
+    retry_policies: HashMap<String, RetryPolicy>,
+}
+
+impl Default for DatabaseManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DatabaseManager {
     pub fn new() -> Self {
         DatabaseManager {
             pools: HashMap::new(),
+            sources: HashMap::new(),
+            retry_policies: HashMap::new(),
         }
     }
 
     pub fn add_pool(&mut self, db_type: DatabaseType, pool: Box<dyn DatabasePool>) {
-        self.pools.insert(db_type, pool);
+        self.sources.remove(&db_type);
+        self.pools.insert(db_type, Arc::from(pool));
+    }
+
+    /// Adds the pool of the source `name`, whose retry policy then applies to its reads.
+    pub fn add_source(&mut self, name: &str, db_type: DatabaseType, pool: Box<dyn DatabasePool>) {
+        self.pools.insert(db_type.clone(), Arc::from(pool));
+        self.sources.insert(db_type, name.to_string());
+    }
+
+    pub fn has_pool(&self, db_type: &DatabaseType) -> bool {
+        self.pools.contains_key(db_type)
+    }
+
+    /// Sets how reads of the source `name` are retried. Without a policy they are not.
+    pub fn set_retry_policy(&mut self, name: &str, policy: RetryPolicy) {
+        self.retry_policies.insert(name.to_string(), policy);
+    }
+
+    /// The retry policy of the source serving `db_type`.
+    fn retry_policy(&self, db_type: &DatabaseType) -> RetryPolicy {
+        self.sources.get(db_type)
+            .and_then(|name| self.retry_policies.get(name))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub async fn execute(&self, query_builder: &QueryBuilder) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        self.with_retries(query_builder, |pool| pool.execute_builder(query_builder)).await.0
+    }
+
+    /// Runs a builder query like `execute`, returning its columns in order with how long it
+    /// took and how many times it was retried.
+    pub async fn query(&self, query_builder: &QueryBuilder) -> Result<ResultSet, DatabaseError> {
+        let started = Instant::now();
+        let (result, retries) = self.with_retries(query_builder, |pool| pool.query_builder(query_builder)).await;
+        let mut result = result?;
+        result.elapsed_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+        result.retries = retries;
+        Ok(result)
+    }
+
+    /// Selects and aggregations are retried after transient failures following the source's
+    /// `RetryPolicy`; other operations are sent once, since they may have been applied before
+    /// the failure.
+    async fn with_retries<'a, T, F, Fut>(&'a self, query_builder: &QueryBuilder, attempt: F) -> (Result<T, DatabaseError>, u32)
+    where
+        F: Fn(&'a dyn DatabasePool) -> Fut,
+        Fut: Future<Output = Result<T, DatabaseError>>,
+    {
+        let Some(pool) = self.pools.get(&query_builder.database_type) else {
+            return (Err(QueryBuilderError::UnsupportedDatabaseType.into()), 0);
+        };
+        // Build once up front so invalid queries are reported as builder errors
+        if let Err(e) = query_builder.build() {
+            return (Err(e.into()), 0);
+        }
+        if !matches!(query_builder.operation, QueryOperation::Select | QueryOperation::Aggregate) {
+            return (attempt(pool.as_ref()).await, 0);
+        }
+
+        let policy = self.retry_policy(&query_builder.database_type);
+        let (result, retries) = policy.run(|| attempt(pool.as_ref())).await;
+        RETRY_METRICS.record(&result, retries);
+        (result, retries)
+    }
+
+    /// The pool of the database type a native query is written for.
+    fn native_pool(&self, query: &NativeQuery) -> Result<&dyn DatabasePool, DatabaseError> {
+        self.pools.get(&query.database_type())
+            .map(|pool| pool.as_ref())
+            .ok_or_else(|| DatabaseError::UnsupportedOperation(format!("No {:?} database is configured", query.database_type())))
+    }
+
+    /// Runs a native query on the pool of the database type it is written for.
+    pub async fn execute_native(&self, query: &NativeQuery) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        self.execute_native_with_timeout(query, None).await
+    }
+
+    /// Runs a native query, cancelling it on the server if it is still running after `timeout`.
+    /// Without a timeout the source's `query_timeout` applies.
+    pub async fn execute_native_with_timeout(&self, query: &NativeQuery, timeout: Option<Duration>) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        let pool = self.native_pool(query)?;
+        let (rows, _) = self.native_with_retries(query, timeout, || pool.execute_native(query)).await?;
+        Ok(rows)
+    }
+
+    /// Runs a native query like `execute_native_with_timeout`, returning its columns in order
+    /// with how long it took and how many times it was retried.
+    pub async fn query_native_with_timeout(&self, query: &NativeQuery, timeout: Option<Duration>) -> Result<ResultSet, DatabaseError> {
+        let pool = self.native_pool(query)?;
+        let started = Instant::now();
+        let (mut result, retries) = self.native_with_retries(query, timeout, || pool.query_native(query)).await?;
+        result.elapsed_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+        result.retries = retries;
+        Ok(result)
+    }
+
+    /// Reads, see `NativeQuery::is_read`, are retried like builder selects, with every attempt
+    /// made within `timeout`; other native queries are sent once.
+    async fn native_with_retries<T, F, Fut>(&self, query: &NativeQuery, timeout: Option<Duration>, attempt: F) -> Result<(T, u32), DatabaseError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, DatabaseError>>,
+    {
+        if !query.is_read() {
+            return with_timeout(timeout, attempt()).await.map(|result| (result, 0));
+        }
+        let policy = self.retry_policy(&query.database_type());
+        with_timeout(timeout, async {
+            let (result, retries) = policy.run(attempt).await;
+            RETRY_METRICS.record(&result, retries);
+            result.map(|result| (result, retries))
+        }).await
     }
 
-    pub async fn execute(&self, query_builder: &QueryBuilder) -> Result<Vec<HashMap<String, Value>>, QueryBuilderError> {
-        let pool = self.pools.get(&query_builder.database_type)
-            .ok_or_else(|| QueryBuilderError::UnsupportedDatabaseType)?;
-        let query = query_builder.build()?;
-        pool.execute(&query, query_builder.values.clone()).await
-            .map_err(|e| QueryBuilderError::DatabaseError(e.to_string()))
+    /// Streams a native query in batches of at most `batch_size` rows, see `DatabasePool::stream_native`.
+    /// Streams are not retried, since their first batches may already have been sent.
+    pub fn stream_native<'a>(&'a self, query: &'a NativeQuery, batch_size: usize) -> Result<RowStream<'a>, DatabaseError> {
+        let pool = self.native_pool(query)?;
+        Ok(pool.stream_native(query, batch_size))
     }
 }
\ No newline at end of file
@@ -0,0 +1,366 @@
+//! Parsing and rendering for the raw Redis command console.
+//! Command lines follow `redis-cli` rules: arguments are separated by whitespace and can be
+//! wrapped in double quotes (with `\n`, `\t`, `\"`, `\xHH`, ... escapes) or single quotes.
+use serde_json::{json, Value};
+use crate::utils::errors::DatabaseError;
+
+/// Commands rejected by the default `CommandPolicy`. Two-word entries match a subcommand.
+/// Scripts and functions are denied too, since they can call any command from Lua, e.g.
+/// `EVAL "return redis.call('FLUSHALL')" 0`.
+pub const DEFAULT_DENIED_COMMANDS: [&str; 25] = [
+    "FLUSHALL", "FLUSHDB", "CONFIG SET", "CONFIG REWRITE", "CONFIG RESETSTAT", "DEBUG",
+    "SHUTDOWN", "SLAVEOF", "REPLICAOF", "MIGRATE", "MODULE", "CLUSTER", "FAILOVER",
+    "ACL SETUSER", "ACL DELUSER", "SCRIPT FLUSH", "FUNCTION FLUSH",
+    "EVAL", "EVALSHA", "EVAL_RO", "EVALSHA_RO", "FCALL", "FCALL_RO", "SCRIPT LOAD", "FUNCTION LOAD",
+];
+
+/// Commands that take over the connection and therefore cannot run from the console.
+const CONNECTION_COMMANDS: [&str; 9] = [
+    "SUBSCRIBE", "PSUBSCRIBE", "SSUBSCRIBE", "MONITOR", "SYNC", "PSYNC", "QUIT", "RESET", "HELLO",
+];
+
+/// Commands that block their connection or change its state, so the console runs them on a
+/// connection of their own instead of the shared one.
+const DEDICATED_CONNECTION_COMMANDS: [&str; 11] = [
+    "BLPOP", "BRPOP", "BLMOVE", "BRPOPLPUSH", "BLMPOP", "BZPOPMIN", "BZPOPMAX", "BZMPOP", "WAIT", "WAITAOF", "SELECT",
+];
+
+/// Commands that only read, so a console batch made of them can be retried. Two-word entries
+/// match a subcommand.
+const READ_COMMANDS: [&str; 48] = [
+    "GET", "MGET", "GETRANGE", "STRLEN", "EXISTS", "TYPE", "TTL", "PTTL", "EXPIRETIME", "PEXPIRETIME",
+    "KEYS", "SCAN", "DBSIZE", "HGET", "HMGET", "HGETALL", "HKEYS", "HVALS", "HLEN", "HEXISTS", "HSCAN",
+    "LRANGE", "LINDEX", "LLEN", "LPOS", "SMEMBERS", "SISMEMBER", "SMISMEMBER", "SCARD", "SSCAN",
+    "ZRANGE", "ZRANGEBYSCORE", "ZREVRANGE", "ZSCORE", "ZMSCORE", "ZRANK", "ZCARD", "ZCOUNT", "ZSCAN",
+    "XRANGE", "XREVRANGE", "XLEN", "XREAD", "XINFO STREAM", "XINFO GROUPS", "INFO", "PING", "CONFIG GET",
+];
+
+/// Commands whose flat array reply is a list of field/value pairs.
+const MAP_REPLY_COMMANDS: [&str; 4] = ["HGETALL", "CONFIG GET", "XINFO STREAM", "XINFO GROUPS"];
+
+/// Splits a command line into its arguments.
+pub fn parse_command_line(line: &str) -> Result<Vec<Vec<u8>>, DatabaseError> {
+    let bytes = line.as_bytes();
+    let mut args = Vec::new();
+    let mut i = 0;
+
+    loop {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i >= bytes.len() {
+            return Ok(args);
+        }
+
+        let mut current = Vec::new();
+        let mut in_double = false;
+        let mut in_single = false;
+        loop {
+            let c = bytes.get(i).copied();
+            if in_double {
+                match c {
+                    None => return Err(DatabaseError::InvalidQuery("Unbalanced double quotes".to_string())),
+                    Some(b'\\') if bytes.get(i + 1) == Some(&b'x') && hex_byte(bytes.get(i + 2..i + 4)).is_some() => {
+                        current.push(hex_byte(bytes.get(i + 2..i + 4)).unwrap_or_default());
+                        i += 3;
+                    }
+                    Some(b'\\') if i + 1 < bytes.len() => {
+                        i += 1;
+                        current.push(match bytes[i] {
+                            b'n' => b'\n',
+                            b'r' => b'\r',
+                            b't' => b'\t',
+                            b'b' => 0x08,
+                            b'a' => 0x07,
+                            other => other,
+                        });
+                    }
+                    Some(b'"') => {
+                        closing_quote(bytes, i)?;
+                        i += 1;
+                        break;
+                    }
+                    Some(c) => current.push(c),
+                }
+            } else if in_single {
+                match c {
+                    None => return Err(DatabaseError::InvalidQuery("Unbalanced single quotes".to_string())),
+                    Some(b'\\') if bytes.get(i + 1) == Some(&b'\'') => {
+                        current.push(b'\'');
+                        i += 1;
+                    }
+                    Some(b'\'') => {
+                        closing_quote(bytes, i)?;
+                        i += 1;
+                        break;
+                    }
+                    Some(c) => current.push(c),
+                }
+            } else {
+                match c {
+                    None => break,
+                    Some(c) if c.is_ascii_whitespace() => break,
+                    Some(b'"') => in_double = true,
+                    Some(b'\'') => in_single = true,
+                    Some(c) => current.push(c),
+                }
+            }
+            i += 1;
+        }
+        args.push(current);
+    }
+}
+
+fn hex_byte(digits: Option<&[u8]>) -> Option<u8> {
+    let digits = digits.filter(|d| d.iter().all(u8::is_ascii_hexdigit))?;
+    u8::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()
+}
+
+/// A closing quote must end the argument, as in `redis-cli`.
+fn closing_quote(bytes: &[u8], i: usize) -> Result<(), DatabaseError> {
+    match bytes.get(i + 1) {
+        Some(next) if !next.is_ascii_whitespace() => {
+            Err(DatabaseError::InvalidQuery("Closing quote must be followed by a space".to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Quotes an argument so that `parse_command_line` returns it unchanged.
+pub fn quote_arg(arg: &str) -> String {
+    let plain = !arg.is_empty() && arg.bytes().all(|b| b.is_ascii_graphic() && b != b'"' && b != b'\'' && b != b'\\');
+    if plain {
+        return arg.to_string();
+    }
+
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    for c in arg.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// The uppercase command name, including the subcommand for two-word commands listed in `known`.
+fn command_name(args: &[Vec<u8>], known: &[&str]) -> String {
+    let name = args.first().map(|a| String::from_utf8_lossy(a).to_uppercase()).unwrap_or_default();
+    if let Some(sub) = args.get(1) {
+        let full = format!("{} {}", name, String::from_utf8_lossy(sub).to_uppercase());
+        if known.contains(&full.as_str()) {
+            return full;
+        }
+    }
+    name
+}
+
+/// Whether a command must not run on a shared connection. `XREAD` and `XREADGROUP` only block
+/// with the `BLOCK` option.
+pub fn needs_dedicated_connection(args: &[Vec<u8>]) -> bool {
+    let name = command_name(args, &[]);
+    DEDICATED_CONNECTION_COMMANDS.contains(&name.as_str())
+        || (matches!(name.as_str(), "XREAD" | "XREADGROUP") && args.iter().any(|arg| arg.eq_ignore_ascii_case(b"BLOCK")))
+}
+
+/// Whether a command only reads and returns without blocking.
+pub fn is_read_only(args: &[Vec<u8>]) -> bool {
+    READ_COMMANDS.contains(&command_name(args, &READ_COMMANDS).as_str()) && !needs_dedicated_connection(args)
+}
+
+/// The keys matched by a `KEYS` or `SCAN` command, walked with `SCAN` so they can be streamed
+/// in batches instead of read at once.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct KeyScan {
+    /// Where the walk starts; `KEYS` starts at 0.
+    pub cursor: u64,
+    pub pattern: Option<Vec<u8>>,
+    pub key_type: Option<Vec<u8>>,
+}
+
+impl KeyScan {
+    /// Recognizes `KEYS pattern` and `SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]`.
+    /// `COUNT` is left out, since the batch size takes its place.
+    pub fn parse(args: &[Vec<u8>]) -> Option<Self> {
+        match command_name(args, &[]).as_str() {
+            "KEYS" if args.len() == 2 => Some(KeyScan { pattern: Some(args[1].clone()), ..Default::default() }),
+            "SCAN" => {
+                let cursor = std::str::from_utf8(args.get(1)?).ok()?.parse().ok()?;
+                let mut scan = KeyScan { cursor, ..Default::default() };
+                for option in args[2..].chunks(2) {
+                    let [name, value] = option else {
+                        return None;
+                    };
+                    match String::from_utf8_lossy(name).to_uppercase().as_str() {
+                        "MATCH" => scan.pattern = Some(value.clone()),
+                        "TYPE" => scan.key_type = Some(value.clone()),
+                        "COUNT" => {}
+                        _ => return None,
+                    }
+                }
+                Some(scan)
+            }
+            _ => None,
+        }
+    }
+
+    /// The `SCAN` command reading about `count` keys after `cursor`.
+    pub fn command(&self, cursor: u64, count: usize) -> Vec<Vec<u8>> {
+        let mut args = vec![b"SCAN".to_vec(), cursor.to_string().into_bytes()];
+        if let Some(pattern) = &self.pattern {
+            args.extend([b"MATCH".to_vec(), pattern.clone()]);
+        }
+        args.extend([b"COUNT".to_vec(), count.max(1).to_string().into_bytes()]);
+        if let Some(key_type) = &self.key_type {
+            args.extend([b"TYPE".to_vec(), key_type.clone()]);
+        }
+        args
+    }
+}
+
+/// Renders arguments back into a command line, e.g. for display next to the reply.
+pub fn display_command(args: &[Vec<u8>]) -> String {
+    args.iter().map(|a| quote_arg(&String::from_utf8_lossy(a))).collect::<Vec<_>>().join(" ")
+}
+
+/// Decides which commands can be sent from the console.
+#[derive(Debug, Clone)]
+pub struct CommandPolicy {
+    denied: Vec<String>,
+}
+
+impl CommandPolicy {
+    pub fn new(denied: Vec<String>) -> Self {
+        CommandPolicy { denied: denied.into_iter().map(|c| c.to_uppercase()).collect() }
+    }
+
+    pub fn check(&self, args: &[Vec<u8>]) -> Result<(), DatabaseError> {
+        let denied: Vec<&str> = self.denied.iter().map(String::as_str).collect();
+        let name = command_name(args, &denied);
+        if name.is_empty() {
+            return Err(DatabaseError::InvalidQuery("Empty command".to_string()));
+        }
+        if denied.contains(&name.as_str()) {
+            return Err(DatabaseError::UnsupportedOperation(format!("{} is not allowed from the console", name)));
+        }
+        if CONNECTION_COMMANDS.contains(&name.as_str()) {
+            return Err(DatabaseError::UnsupportedOperation(format!("{} requires a dedicated connection", name)));
+        }
+        Ok(())
+    }
+}
+
+impl Default for CommandPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_DENIED_COMMANDS.iter().map(|c| c.to_string()).collect())
+    }
+}
+
+/// One or more commands sent in a single round trip. `atomic` batches were wrapped in
+/// `MULTI`/`EXEC` and run as a transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedisBatch {
+    pub commands: Vec<Vec<Vec<u8>>>,
+    pub atomic: bool,
+}
+
+/// Parses console input with one command per line. If the first command is `MULTI` the last
+/// one must be `EXEC`, and the commands in between form a transaction.
+pub fn parse_batch(input: &str) -> Result<RedisBatch, DatabaseError> {
+    let mut commands = Vec::new();
+    for line in input.lines() {
+        let args = parse_command_line(line)?;
+        if !args.is_empty() {
+            commands.push(args);
+        }
+    }
+
+    let is = |args: &Vec<Vec<u8>>, name: &str| args[0].eq_ignore_ascii_case(name.as_bytes());
+    let atomic = commands.first().is_some_and(|c| is(c, "MULTI"));
+    if atomic {
+        if commands.len() < 2 || !is(&commands[commands.len() - 1], "EXEC") {
+            return Err(DatabaseError::InvalidQuery("MULTI without a matching EXEC".to_string()));
+        }
+        commands.remove(0);
+        commands.pop();
+    }
+
+    if commands.iter().any(|c| ["MULTI", "EXEC", "DISCARD", "WATCH", "UNWATCH"].iter().any(|n| is(c, n))) {
+        return Err(DatabaseError::InvalidQuery(
+            "Transactions must start with MULTI on the first line and end with EXEC on the last".to_string(),
+        ));
+    }
+    if commands.is_empty() {
+        return Err(DatabaseError::InvalidQuery("Empty command".to_string()));
+    }
+
+    Ok(RedisBatch { commands, atomic })
+}
+
+/// Builds the `redis::Cmd` for parsed arguments.
+pub fn to_cmd(args: &[Vec<u8>]) -> redis::Cmd {
+    let mut cmd = redis::Cmd::new();
+    for arg in args {
+        cmd.arg(&arg[..]);
+    }
+    cmd
+}
+
+/// Converts a reply into a typed JSON value: `{"type": "nil" | "integer" | "bulk" | "status" |
+/// "array" | "map", "value": ...}`. `args` are the command that produced the reply.
+pub fn reply_to_json(args: &[Vec<u8>], reply: &redis::Value) -> Value {
+    let is_map = MAP_REPLY_COMMANDS.contains(&command_name(args, &MAP_REPLY_COMMANDS).as_str());
+    typed_reply(reply, is_map)
+}
+
+fn typed_reply(reply: &redis::Value, as_map: bool) -> Value {
+    match reply {
+        redis::Value::Nil => json!({ "type": "nil" }),
+        redis::Value::Int(v) => json!({ "type": "integer", "value": v }),
+        redis::Value::Data(bytes) => json!({ "type": "bulk", "value": String::from_utf8_lossy(bytes) }),
+        redis::Value::Status(s) => json!({ "type": "status", "value": s }),
+        redis::Value::Okay => json!({ "type": "status", "value": "OK" }),
+        redis::Value::Bulk(items) if as_map && items.len() % 2 == 0 => {
+            let map: serde_json::Map<String, Value> = items.chunks(2)
+                .map(|pair| (redis::from_redis_value::<String>(&pair[0]).unwrap_or_default(), typed_reply(&pair[1], false)))
+                .collect();
+            json!({ "type": "map", "value": map })
+        }
+        redis::Value::Bulk(items) => {
+            json!({ "type": "array", "value": items.iter().map(|i| typed_reply(i, false)).collect::<Vec<_>>() })
+        }
+    }
+}
+
+/// Renders a reply the way `redis-cli` prints it.
+pub fn reply_to_text(reply: &redis::Value) -> String {
+    render_text(reply, 0)
+}
+
+fn render_text(reply: &redis::Value, indent: usize) -> String {
+    match reply {
+        redis::Value::Nil => "(nil)".to_string(),
+        redis::Value::Int(v) => format!("(integer) {}", v),
+        redis::Value::Data(bytes) => format!("{:?}", String::from_utf8_lossy(bytes)),
+        redis::Value::Status(s) => s.clone(),
+        redis::Value::Okay => "OK".to_string(),
+        redis::Value::Bulk(items) if items.is_empty() => "(empty array)".to_string(),
+        redis::Value::Bulk(items) => {
+            let width = items.len().to_string().len();
+            items.iter().enumerate()
+                .map(|(i, item)| {
+                    let prefix = format!("{:>width$}) ", i + 1, width = width);
+                    let padding = if i == 0 { String::new() } else { " ".repeat(indent) };
+                    format!("{}{}{}", padding, prefix, render_text(item, indent + prefix.len()))
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    }
+}
@@ -0,0 +1,114 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::redis_command::{needs_dedicated_connection, parse_batch, parse_command_line, quote_arg, reply_to_json, reply_to_text, CommandPolicy, KeyScan};
+    use redis::Value;
+    use serde_json::json;
+
+    fn args(line: &str) -> Vec<String> {
+        parse_command_line(line).unwrap().into_iter().map(|a| String::from_utf8(a).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_parse_plain_and_quoted_arguments() {
+        assert_eq!(args("HGET user:1 name"), vec!["HGET", "user:1", "name"]);
+        assert_eq!(args(r#"SET greeting "hello world""#), vec!["SET", "greeting", "hello world"]);
+        assert_eq!(args(r#"SET quote 'it\'s here'"#), vec!["SET", "quote", "it's here"]);
+        assert_eq!(args(r#"SET esc "a\tb\n\x41\"""#), vec!["SET", "esc", "a\tb\nA\""]);
+        assert_eq!(args(r#"SET empty """#), vec!["SET", "empty", ""]);
+        assert!(args("   ").is_empty());
+    }
+
+    #[test]
+    fn test_parse_rejects_unbalanced_quotes() {
+        assert!(parse_command_line(r#"SET key "unterminated"#).is_err());
+        assert!(parse_command_line(r#"SET key "value"trailing"#).is_err());
+    }
+
+    #[test]
+    fn test_quote_arg_round_trips() {
+        for value in ["plain", "with space", r#"{"name":"John Doe"}"#, "", "tab\there"] {
+            let line = format!("SET key {}", quote_arg(value));
+            assert_eq!(args(&line)[2], value);
+        }
+    }
+
+    #[test]
+    fn test_policy_denies_dangerous_commands() {
+        let policy = CommandPolicy::default();
+        assert!(policy.check(&parse_command_line("flushall").unwrap()).is_err());
+        assert!(policy.check(&parse_command_line("CONFIG SET maxmemory 1").unwrap()).is_err());
+        assert!(policy.check(&parse_command_line("DEBUG SLEEP 10").unwrap()).is_err());
+        assert!(policy.check(&parse_command_line("SUBSCRIBE news").unwrap()).is_err());
+        assert!(policy.check(&parse_command_line("CONFIG GET maxmemory").unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_policy_denies_scripts() {
+        let policy = CommandPolicy::default();
+        assert!(policy.check(&parse_command_line(r#"EVAL "return redis.call('FLUSHALL')" 0"#).unwrap()).is_err(),
+            "Scripts should not get around the denied commands");
+        for line in ["evalsha abc 0", "EVAL_RO \"return 1\" 0", "FCALL flush 0", "SCRIPT LOAD \"return 1\"", "FUNCTION LOAD code"] {
+            assert!(policy.check(&parse_command_line(line).unwrap()).is_err(), "{} should be denied", line);
+        }
+        assert!(policy.check(&parse_command_line("SCRIPT EXISTS abc").unwrap()).is_ok());
+        assert!(CommandPolicy::new(Vec::new()).check(&parse_command_line("EVAL \"return 1\" 0").unwrap()).is_ok(),
+            "A custom policy can allow scripts");
+    }
+
+    #[test]
+    fn test_blocking_commands_need_their_own_connection() {
+        let dedicated = |line: &str| needs_dedicated_connection(&parse_command_line(line).unwrap());
+        assert!(dedicated("blpop jobs 0"));
+        assert!(dedicated("SELECT 2"));
+        assert!(dedicated("XREAD COUNT 10 BLOCK 5000 STREAMS events $"));
+        assert!(!dedicated("XREAD COUNT 10 STREAMS events 0"));
+        assert!(!dedicated("GET blpop"));
+    }
+
+    #[test]
+    fn test_parse_batch() {
+        let batch = parse_batch("MULTI\nINCR counter\nEXPIRE counter 60\nEXEC").unwrap();
+        assert!(batch.atomic);
+        assert_eq!(batch.commands.len(), 2);
+
+        let batch = parse_batch("GET a\n\nGET b").unwrap();
+        assert!(!batch.atomic);
+        assert_eq!(batch.commands.len(), 2);
+
+        assert!(parse_batch("MULTI\nINCR counter").is_err());
+        assert!(parse_batch("GET a\nEXEC").is_err());
+        assert!(parse_batch("").is_err());
+    }
+
+    #[test]
+    fn test_reply_rendering() {
+        let hgetall = parse_command_line("HGETALL user:1").unwrap();
+        let reply = Value::Bulk(vec![Value::Data(b"name".to_vec()), Value::Data(b"Ada".to_vec())]);
+        assert_eq!(reply_to_json(&hgetall, &reply), json!({
+            "type": "map",
+            "value": { "name": { "type": "bulk", "value": "Ada" } }
+        }));
+
+        let lrange = parse_command_line("LRANGE list 0 -1").unwrap();
+        assert_eq!(reply_to_json(&lrange, &reply)["type"], "array");
+        assert_eq!(reply_to_json(&lrange, &Value::Nil), json!({ "type": "nil" }));
+        assert_eq!(reply_to_text(&Value::Int(3)), "(integer) 3");
+        assert_eq!(reply_to_text(&reply), "1) \"name\"\n2) \"Ada\"");
+    }
+
+    #[test]
+    fn test_keys_and_scan_are_walked_with_scan() {
+        let keys = KeyScan::parse(&parse_command_line("KEYS user:*").unwrap()).unwrap();
+        assert_eq!(keys, KeyScan { cursor: 0, pattern: Some(b"user:*".to_vec()), key_type: None });
+        assert_eq!(keys.command(0, 100), parse_command_line("SCAN 0 MATCH user:* COUNT 100").unwrap());
+
+        let scan = KeyScan::parse(&parse_command_line("scan 17 count 10 type hash").unwrap()).unwrap();
+        assert_eq!(scan.command(42, 500), parse_command_line("SCAN 42 COUNT 500 TYPE hash").unwrap());
+        assert_eq!(scan.cursor, 17);
+
+        assert_eq!(KeyScan::parse(&parse_command_line("SCAN 0 MATCH").unwrap()), None);
+        assert_eq!(KeyScan::parse(&parse_command_line("SCAN next").unwrap()), None);
+        assert_eq!(KeyScan::parse(&parse_command_line("KEYS").unwrap()), None);
+        assert_eq!(KeyScan::parse(&parse_command_line("HSCAN h 0").unwrap()), None);
+    }
+}
@@ -1,5 +1,19 @@
 use super::*;
+use serde::Serialize;
+use serde_json::json;
+use crate::db::native_query::{values_to_result, values_to_rows};
 use crate::db::query_builder::{QueryBuilder, QueryOperation};
+use crate::db::redis_command::{display_command, needs_dedicated_connection, parse_batch, parse_command_line, quote_arg, reply_to_json, reply_to_text, to_cmd, CommandPolicy, KeyScan, RedisBatch};
+use crate::db::redis_pubsub::RedisSubscriber;
+use crate::db::result_set::{Column, DataType, ResultSet};
+use crate::db::row_stream::{collected, failed, RowStream};
+use crate::db::tls::{tls_error, TlsFiles, TlsSupport};
+use crate::config::config::{SourceOptions, TlsConfig, TlsVerify};
+use redis::{ClientTlsConfig, ConnectionAddr, IntoConnectionInfo, TlsCertificates};
+use redis::aio::{ConnectionLike, ConnectionManager};
+use futures::StreamExt;
+use std::time::Duration;
+use tokio::sync::Mutex;
 use crate::utils::errors::{QueryBuilderError, DatabaseError};
 
 pub fn build_query(builder: &QueryBuilder) -> Result<String, QueryBuilderError> {
@@ -16,63 +30,540 @@ fn build_get_query(builder: &QueryBuilder) -> Result<String, QueryBuilderError>
     if builder.table.as_str().is_empty() {
         return Err(QueryBuilderError::MissingField("key".to_string()));
     }
-    Ok(format!("GET {}", builder.table.as_str()))
+    Ok(format!("GET {}", quote_arg(builder.table.as_str())))
 }
 
 fn build_set_query(builder: &QueryBuilder) -> Result<String, QueryBuilderError> {
     if builder.table.as_str().is_empty() || builder.values.is_empty() {
         return Err(QueryBuilderError::MissingField("key or value".to_string()));
     }
-    Ok(format!("SET {} {}", builder.table.as_str(), serde_json::to_string(&builder.values[0]).map_err(|e| QueryBuilderError::InvalidQuery(e.to_string()))?))
+    let value = serde_json::to_string(&builder.values[0]).map_err(|e| QueryBuilderError::InvalidQuery(e.to_string()))?;
+    Ok(format!("SET {} {}", quote_arg(builder.table.as_str()), quote_arg(&value)))
 }
 
 fn build_del_query(builder: &QueryBuilder) -> Result<String, QueryBuilderError> {
     if builder.table.as_str().is_empty() {
         return Err(QueryBuilderError::MissingField("key".to_string()));
     }
-    Ok(format!("DEL {}", builder.table.as_str()))
+    Ok(format!("DEL {}", quote_arg(builder.table.as_str())))
 }
 
+/// `INFO` sections that are exposed as structured metrics by `RedisPool::server_metrics`.
+pub const INFO_SECTIONS: [&str; 5] = ["memory", "clients", "stats", "replication", "persistence"];
+
+/// Per-database statistics reported by the `keyspace` section of `INFO`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeyspaceStats {
+    pub db: i64,
+    pub keys: u64,
+    pub expires: u64,
+    pub avg_ttl: u64,
+}
+
+/// A single entry returned by `SLOWLOG GET`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SlowlogEntry {
+    pub id: i64,
+    pub timestamp: i64,
+    pub duration_micros: i64,
+    pub command: Vec<String>,
+    pub client_addr: Option<String>,
+    pub client_name: Option<String>,
+}
+
+/// A snapshot of the server state built from `INFO`, `SLOWLOG GET` and `CLIENT LIST`.
+/// `sections` maps each name in `INFO_SECTIONS` to its parsed fields.
+#[derive(Debug, Clone, Serialize)]
+pub struct RedisServerMetrics {
+    pub sections: HashMap<String, HashMap<String, Value>>,
+    pub keyspace: Vec<KeyspaceStats>,
+    pub slowlog: Vec<SlowlogEntry>,
+    pub clients: Vec<HashMap<String, Value>>,
+}
+
+impl RedisServerMetrics {
+    /// Flattens the numeric metrics into `section`/`metric`/`value` rows so they can be charted
+    /// like any other query result.
+    pub fn to_rows(&self) -> Vec<HashMap<String, Value>> {
+        let mut rows: Vec<HashMap<String, Value>> = Vec::new();
+
+        for (section, fields) in &self.sections {
+            for (metric, value) in fields {
+                if value.is_number() {
+                    rows.push(metric_row(section, metric, value.clone()));
+                }
+            }
+        }
+
+        for stats in &self.keyspace {
+            let section = format!("keyspace.db{}", stats.db);
+            rows.push(metric_row(&section, "keys", json!(stats.keys)));
+            rows.push(metric_row(&section, "expires", json!(stats.expires)));
+            rows.push(metric_row(&section, "avg_ttl", json!(stats.avg_ttl)));
+        }
+
+        rows.push(metric_row("clients", "client_list_len", json!(self.clients.len())));
+        rows.push(metric_row("slowlog", "entries", json!(self.slowlog.len())));
+
+        rows.sort_by(|a, b| {
+            (a["section"].as_str(), a["metric"].as_str()).cmp(&(b["section"].as_str(), b["metric"].as_str()))
+        });
+        rows
+    }
+}
+
+fn metric_row(section: &str, metric: &str, value: Value) -> HashMap<String, Value> {
+    [
+        ("section".to_string(), json!(section)),
+        ("metric".to_string(), json!(metric)),
+        ("value".to_string(), value),
+    ].into_iter().collect()
+}
+
+/// Resolves a logical database name as returned by `list_databases` (`db3`) or a bare index (`3`).
+/// An empty name or `default` resolves to `default_db`.
+pub fn parse_database_index(database: &str, default_db: i64) -> Result<i64, DatabaseError> {
+    let name = database.trim();
+    if name.is_empty() || name.eq_ignore_ascii_case("default") {
+        return Ok(default_db);
+    }
+    name.strip_prefix("db").unwrap_or(name)
+        .parse::<i64>()
+        .ok()
+        .filter(|db| *db >= 0)
+        .ok_or_else(|| DatabaseError::DatabaseNotFound(database.to_string()))
+}
+
+/// Converts an `INFO` field into a JSON number when possible, keeping it as a string otherwise.
+fn info_value(raw: &str) -> Value {
+    if let Ok(v) = raw.parse::<i64>() {
+        return json!(v);
+    }
+    match raw.parse::<f64>() {
+        Ok(v) if v.is_finite() => json!(v),
+        _ => Value::String(raw.to_string()),
+    }
+}
+
+/// Parses the output of `INFO` into a map of lowercase section names to their fields.
+pub fn parse_info(info: &str) -> HashMap<String, HashMap<String, Value>> {
+    let mut sections: HashMap<String, HashMap<String, Value>> = HashMap::new();
+    let mut current = String::from("default");
+
+    for line in info.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(header) = line.strip_prefix('#') {
+            current = header.trim().to_lowercase();
+            continue;
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            sections.entry(current.clone()).or_default().insert(key.to_string(), info_value(value));
+        }
+    }
+
+    sections
+}
+
+/// Parses the `keyspace` section of `INFO` (`db0:keys=1,expires=0,avg_ttl=0`).
+pub fn parse_keyspace(info: &str) -> Vec<KeyspaceStats> {
+    let mut stats: Vec<KeyspaceStats> = info.lines()
+        .filter_map(|line| {
+            let (name, fields) = line.trim().split_once(':')?;
+            let db = name.strip_prefix("db")?.parse::<i64>().ok()?;
+            let field = |key: &str| fields.split(',')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(k, _)| *k == key)
+                .and_then(|(_, v)| v.parse::<u64>().ok())
+                .unwrap_or(0);
+            Some(KeyspaceStats { db, keys: field("keys"), expires: field("expires"), avg_ttl: field("avg_ttl") })
+        })
+        .collect();
+    stats.sort_by_key(|s| s.db);
+    stats
+}
+
+/// Parses the output of `CLIENT LIST`, one `key=value` line per connected client.
+pub fn parse_client_list(list: &str) -> Vec<HashMap<String, Value>> {
+    list.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.split(' ')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(k, v)| (k.to_string(), info_value(v)))
+                .collect()
+        })
+        .collect()
+}
+
+/// Parses the reply of `SLOWLOG GET`. Entries that do not have the expected shape are skipped.
+pub fn parse_slowlog(reply: &redis::Value) -> Vec<SlowlogEntry> {
+    let entries = match reply {
+        redis::Value::Bulk(entries) => entries,
+        _ => return Vec::new(),
+    };
+
+    entries.iter().filter_map(|entry| {
+        let fields = match entry {
+            redis::Value::Bulk(fields) if fields.len() >= 4 => fields,
+            _ => return None,
+        };
+        let int = |v: &redis::Value| redis::from_redis_value::<i64>(v).ok();
+        let string = |v: &redis::Value| redis::from_redis_value::<String>(v).ok().filter(|s| !s.is_empty());
+        Some(SlowlogEntry {
+            id: int(&fields[0])?,
+            timestamp: int(&fields[1])?,
+            duration_micros: int(&fields[2])?,
+            command: redis::from_redis_value::<Vec<String>>(&fields[3]).unwrap_or_default(),
+            client_addr: fields.get(4).and_then(string),
+            client_name: fields.get(5).and_then(string),
+        })
+    }).collect()
+}
+
+/// The driver always sends the host as the server name and checks it whenever it verifies
+/// certificates.
+pub(crate) fn tls_client(connection_string: &str, tls: &TlsConfig) -> Result<redis::Client, DatabaseError> {
+    TlsSupport { verify_ca_only: false, server_name: false }.check("Redis", tls)?;
+    let files = TlsFiles::load(tls)?;
+
+    let mut info = connection_string.into_connection_info()
+        .map_err(|e| DatabaseError::ConfigurationError(e.to_string()))?;
+    let insecure = tls.verify == TlsVerify::Disabled;
+    info.addr = match info.addr {
+        ConnectionAddr::Tcp(host, port) | ConnectionAddr::TcpTls { host, port, .. } => {
+            ConnectionAddr::TcpTls { host, port, insecure, tls_params: None }
+        }
+        _ => return Err(DatabaseError::UnsupportedOperation("TLS is not available over Unix sockets".to_string())),
+    };
+
+    let certificates = TlsCertificates {
+        client_tls: files.identity().map(|(cert, key)| ClientTlsConfig { client_cert: cert.to_vec(), client_key: key.to_vec() }),
+        root_cert: files.ca,
+    };
+    redis::Client::build_with_tls(info, certificates).map_err(tls_error)
+}
+
+/// Maps a driver error, keeping the prefix of error replies such as `WRONGTYPE` or `LOADING`.
+pub(crate) fn redis_error(error: redis::RedisError) -> DatabaseError {
+    if error.is_connection_dropped() || error.is_connection_refusal() || error.is_io_error() || error.is_timeout() {
+        return DatabaseError::ConnectionError(error.to_string());
+    }
+    match error.code() {
+        Some(code) => {
+            let message = error.detail().map(str::to_string).unwrap_or_else(|| error.to_string());
+            DatabaseError::redis(code, message)
+        }
+        None => DatabaseError::QueryError(error.to_string()),
+    }
+}
+
+/// Sends console commands in one round trip, as a transaction when the batch is atomic.
+async fn send_batch(con: &mut impl ConnectionLike, batch: &RedisBatch) -> Result<Vec<redis::Value>, DatabaseError> {
+    if batch.commands.len() == 1 && !batch.atomic {
+        let reply = to_cmd(&batch.commands[0]).query_async(con).await
+            .map_err(redis_error)?;
+        return Ok(vec![reply]);
+    }
+
+    let mut pipe = redis::pipe();
+    if batch.atomic {
+        pipe.atomic();
+    }
+    for command in &batch.commands {
+        pipe.add_command(to_cmd(command));
+    }
+    pipe.query_async(con).await
+        .map_err(redis_error)
+}
+
+/// Reconnect backoff of the shared connections, the driver's defaults.
+const RECONNECT_EXPONENT_BASE: u64 = 2;
+const RECONNECT_FACTOR: u64 = 100;
+const RECONNECT_RETRIES: usize = 6;
+
 pub struct RedisPool {
     client: redis::Client,
+    policy: CommandPolicy,
+    connect_timeout: Option<Duration>,
+    /// Redis cannot cancel a running command, so a query that times out is only abandoned.
+    response_timeout: Option<Duration>,
+    /// One reconnecting connection per logical database, shared by all requests.
+    connections: Mutex<HashMap<i64, ConnectionManager>>,
 }
 
 impl RedisPool {
     pub async fn new(connection_string: &str) -> Result<Self, DatabaseError> {
-        let client = redis::Client::open(connection_string)
-            .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
-        Ok(RedisPool { client })
+        Self::open(connection_string, &SourceOptions::default()).await
     }
-}
 
-#[async_trait::async_trait]
-impl DatabasePool for RedisPool {
-    async fn execute(&self, query: &str, params: Vec<Value>) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
-        let mut con = self.client.get_multiplexed_async_connection().await
-            .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
+    /// Opens a client with the options of a configured source. With TLS enabled, `redis://`
+    /// connection strings are upgraded to TLS.
+    pub async fn open(connection_string: &str, options: &SourceOptions) -> Result<Self, DatabaseError> {
+        let client = match options.tls.as_ref().filter(|tls| tls.enabled) {
+            Some(tls) => tls_client(connection_string, tls)?,
+            None => redis::Client::open(connection_string)
+                .map_err(|e| DatabaseError::ConfigurationError(e.to_string()))?,
+        };
+        Ok(RedisPool {
+            client,
+            policy: CommandPolicy::default(),
+            connect_timeout: options.connect_timeout(),
+            response_timeout: options.query_timeout(),
+            connections: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Replaces the policy that decides which console commands may be executed.
+    pub fn with_command_policy(mut self, policy: CommandPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
 
-        let parts: Vec<&str> = query.split_whitespace().collect();
-        let command = parts[0];
-        let key = parts.get(1).ok_or_else(|| DatabaseError::QueryError("Missing key".to_string()))?;
+    /// The logical database selected by the connection string, e.g. `redis://localhost/2`.
+    pub fn default_database(&self) -> i64 {
+        self.client.get_connection_info().redis.db
+    }
+
+    /// Returns a client whose connections `SELECT` the given logical database.
+    pub(crate) fn client_for(&self, db: i64) -> Result<redis::Client, DatabaseError> {
+        let mut info = self.client.get_connection_info().clone();
+        info.redis.db = db;
+        redis::Client::open(info)
+            .map_err(|e| DatabaseError::ConnectionError(e.to_string()))
+    }
 
-        let result: redis::RedisResult<String> = match command {
-            "GET" => redis::cmd("GET").arg(key).query_async(&mut con).await,
+    /// Returns the shared connection that has `SELECT`ed the given logical database, opening
+    /// it on first use.
+    pub(crate) async fn connection(&self, db: i64) -> Result<ConnectionManager, DatabaseError> {
+        let mut connections = self.connections.lock().await;
+        if let Some(connection) = connections.get(&db) {
+            return Ok(connection.clone());
+        }
+
+        let client = self.client_for(db)?;
+        let connection = match (self.response_timeout, self.connect_timeout) {
+            (None, None) => client.get_connection_manager().await,
+            (response_timeout, connect_timeout) => client.get_tokio_connection_manager_with_backoff_and_timeouts(
+                RECONNECT_EXPONENT_BASE,
+                RECONNECT_FACTOR,
+                RECONNECT_RETRIES,
+                response_timeout.unwrap_or(Duration::MAX),
+                connect_timeout.unwrap_or(Duration::MAX),
+            ).await,
+        }.map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
+
+        connections.insert(db, connection.clone());
+        Ok(connection)
+    }
+
+    /// Opens a connection of its own, for commands that would block or alter a shared one.
+    async fn dedicated_connection(&self, db: i64) -> Result<redis::aio::MultiplexedConnection, DatabaseError> {
+        let client = self.client_for(db)?;
+        let connection = match self.connect_timeout {
+            Some(timeout) => client.get_multiplexed_async_connection_with_timeouts(Duration::MAX, timeout).await,
+            None => client.get_multiplexed_async_connection().await,
+        };
+        connection.map_err(|e| DatabaseError::ConnectionError(e.to_string()))
+    }
+
+    /// Executes a builder query against a specific logical database instead of the default one.
+    pub async fn execute_in(&self, db: i64, query: &str, params: Vec<Value>) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        let mut con = self.connection(db).await?;
+
+        let parts = parse_command_line(query)?;
+        let command = parts.first().map(|c| String::from_utf8_lossy(c).to_uppercase())
+            .ok_or_else(|| DatabaseError::QueryError("Missing command".to_string()))?;
+        let key = parts.get(1).map(|k| String::from_utf8_lossy(k).into_owned())
+            .ok_or_else(|| DatabaseError::QueryError("Missing key".to_string()))?;
+
+        let result: redis::RedisResult<String> = match command.as_str() {
+            "GET" => redis::cmd("GET").arg(&key).query_async(&mut con).await,
             "SET" => {
-                let value = params.get(0).ok_or_else(|| DatabaseError::QueryError("Missing value".to_string()))?;
-                redis::cmd("SET").arg(key).arg(serde_json::to_string(value).map_err(|e| DatabaseError::QueryError(e.to_string()))?).query_async(&mut con).await
+                let value = match params.first() {
+                    Some(value) => serde_json::to_string(value).map_err(|e| DatabaseError::QueryError(e.to_string()))?.into_bytes(),
+                    None => parts.get(2).cloned().ok_or_else(|| DatabaseError::QueryError("Missing value".to_string()))?,
+                };
+                redis::cmd("SET").arg(&key).arg(value).query_async(&mut con).await
             },
-            "DEL" => redis::cmd("DEL").arg(key).query_async(&mut con).await,
+            "DEL" => redis::cmd("DEL").arg(&key).query_async(&mut con).await,
             _ => return Err(DatabaseError::QueryError("Unsupported Redis command".to_string())),
         };
 
         match result {
             Ok(value) => Ok(vec![
                 [(
-                    key.to_string(),
+                    key,
                     serde_json::from_str(&value).unwrap_or(Value::String(value))
                 )].into_iter().collect()
             ]),
-            Err(e) => Err(DatabaseError::QueryError(e.to_string())),
+            Err(e) => Err(redis_error(e)),
+        }
+    }
+
+    /// Runs console input against a logical database: a single command, a pipeline with one
+    /// command per line, or a `MULTI`/`EXEC` transaction. Returns one row per command with the
+    /// command, its typed reply and the reply rendered like `redis-cli`.
+    /// A pipeline fails as a whole if any of its commands returns an error.
+    pub async fn run_console(&self, db: i64, input: &str) -> Result<Vec<Value>, DatabaseError> {
+        let batch = parse_batch(input)?;
+        for command in &batch.commands {
+            self.policy.check(command)?;
+        }
+
+        let replies = if batch.commands.iter().any(|command| needs_dedicated_connection(command)) {
+            send_batch(&mut self.dedicated_connection(db).await?, &batch).await?
+        } else {
+            send_batch(&mut self.connection(db).await?, &batch).await?
+        };
+
+        Ok(batch.commands.iter().zip(replies.iter())
+            .map(|(command, reply)| json!({
+                "command": display_command(command),
+                "reply": reply_to_json(command, reply),
+                "text": reply_to_text(reply),
+            }))
+            .collect())
+    }
+
+    /// Walks the keys of a `KEYS` or `SCAN` command with `SCAN`, yielding a `key` row per key.
+    fn scan_keys(&self, db: i64, scan: KeyScan, batch_size: usize) -> RowStream<'_> {
+        let start = scan.cursor;
+        let columns = vec![Column {
+            name: "key".to_string(),
+            source_type: "string".to_string(),
+            data_type: DataType::Text,
+            nullable: false,
+        }];
+        futures::stream::try_unfold((scan, Some(start), true), move |(scan, cursor, first)| {
+            let columns = columns.clone();
+            async move {
+                let Some(mut cursor) = cursor else {
+                    return Ok::<_, DatabaseError>(None);
+                };
+                let mut con = self.connection(db).await?;
+                loop {
+                    let (next, keys): (u64, Vec<Vec<u8>>) = to_cmd(&scan.command(cursor, batch_size)).query_async(&mut con).await
+                        .map_err(redis_error)?;
+                    // SCAN may return empty batches long before the end
+                    if keys.is_empty() && next != 0 {
+                        cursor = next;
+                        continue;
+                    }
+                    if keys.is_empty() && !first {
+                        return Ok(None);
+                    }
+                    let rows = keys.iter().map(|key| vec![json!(String::from_utf8_lossy(key))]).collect();
+                    let result = ResultSet { columns, rows, ..Default::default() };
+                    return Ok(Some((result, (scan, (next != 0).then_some(next), false))));
+                }
+            }
+        }).boxed()
+    }
+
+    /// Starts a subscriber on a dedicated connection to the same server.
+    pub fn subscriber(&self) -> RedisSubscriber {
+        RedisSubscriber::spawn(self.client.clone())
+    }
+
+    /// Turns on keyspace notifications (`notify-keyspace-events`) so key changes can be
+    /// observed with `redis_pubsub::keyspace_pattern`. `flags` defaults to `KEA` when empty.
+    pub async fn enable_keyspace_notifications(&self, flags: &str) -> Result<(), DatabaseError> {
+        let flags = if flags.is_empty() { "KEA" } else { flags };
+        let mut con = self.connection(self.default_database()).await?;
+        redis::cmd("CONFIG").arg("SET").arg("notify-keyspace-events").arg(flags)
+            .query_async::<_, ()>(&mut con).await
+            .map_err(redis_error)
+    }
+
+    /// Returns the raw output of `INFO <section>`.
+    async fn info_raw(&self, section: &str) -> Result<String, DatabaseError> {
+        let mut con = self.connection(self.default_database()).await?;
+        redis::cmd("INFO").arg(section).query_async(&mut con).await
+            .map_err(redis_error)
+    }
+
+    /// Returns the fields of a single `INFO` section.
+    pub async fn info(&self, section: &str) -> Result<HashMap<String, Value>, DatabaseError> {
+        let raw = self.info_raw(section).await?;
+        Ok(parse_info(&raw).remove(&section.to_lowercase()).unwrap_or_default())
+    }
+
+    /// Returns per-database key counts from `INFO keyspace`.
+    pub async fn keyspace(&self) -> Result<Vec<KeyspaceStats>, DatabaseError> {
+        let raw = self.info_raw("keyspace").await?;
+        Ok(parse_keyspace(&raw))
+    }
+
+    /// Returns the most recent `count` entries of the slow log.
+    pub async fn slowlog(&self, count: usize) -> Result<Vec<SlowlogEntry>, DatabaseError> {
+        let mut con = self.connection(self.default_database()).await?;
+        let reply: redis::Value = redis::cmd("SLOWLOG").arg("GET").arg(count).query_async(&mut con).await
+            .map_err(redis_error)?;
+        Ok(parse_slowlog(&reply))
+    }
+
+    /// Returns one row per connected client as reported by `CLIENT LIST`.
+    pub async fn client_list(&self) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        let mut con = self.connection(self.default_database()).await?;
+        let list: String = redis::cmd("CLIENT").arg("LIST").query_async(&mut con).await
+            .map_err(redis_error)?;
+        Ok(parse_client_list(&list))
+    }
+
+    /// Collects the `INFO_SECTIONS`, keyspace, slow log and client list in one snapshot.
+    pub async fn server_metrics(&self) -> Result<RedisServerMetrics, DatabaseError> {
+        let raw = self.info_raw("everything").await?;
+        let mut parsed = parse_info(&raw);
+        let sections = INFO_SECTIONS.iter()
+            .map(|name| (name.to_string(), parsed.remove(*name).unwrap_or_default()))
+            .collect();
+
+        Ok(RedisServerMetrics {
+            sections,
+            keyspace: parse_keyspace(&raw),
+            slowlog: self.slowlog(128).await?,
+            clients: self.client_list().await?,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl DatabasePool for RedisPool {
+    async fn execute(&self, query: &str, params: Vec<Value>) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        self.execute_in(self.default_database(), query, params).await
+    }
+
+    async fn execute_native(&self, query: &NativeQuery) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+        match query {
+            NativeQuery::Redis { db, command } => {
+                let replies = self.run_console(db.unwrap_or_else(|| self.default_database()), command).await?;
+                Ok(values_to_rows(replies))
+            }
+            other => Err(other.unsupported()),
+        }
+    }
+
+    async fn query_native(&self, query: &NativeQuery) -> Result<ResultSet, DatabaseError> {
+        match query {
+            NativeQuery::Redis { db, command } => {
+                let replies = self.run_console(db.unwrap_or_else(|| self.default_database()), command).await?;
+                Ok(values_to_result(replies))
+            }
+            other => Err(other.unsupported()),
+        }
+    }
+
+    /// A single allowed `KEYS` or `SCAN` command is walked with `SCAN`; other input runs on the console.
+    fn stream_native<'a>(&'a self, query: &'a NativeQuery, batch_size: usize) -> RowStream<'a> {
+        let NativeQuery::Redis { db, command } = query else {
+            return failed(query.unsupported());
+        };
+        let scan = parse_batch(command).ok()
+            .filter(|batch| batch.commands.len() == 1 && self.policy.check(&batch.commands[0]).is_ok())
+            .and_then(|batch| KeyScan::parse(&batch.commands[0]));
+        match scan {
+            Some(scan) => self.scan_keys(db.unwrap_or_else(|| self.default_database()), scan, batch_size),
+            None => collected(self.query_native(query), batch_size),
         }
     }
 }
@@ -89,35 +580,82 @@ impl Database for RedisPool {
     }
 
     async fn execute_query(&self, query: &str) -> Result<Vec<Value>, DatabaseError> {
-        let mut con = self.client.get_multiplexed_async_connection().await
-            .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
+        self.run_console(self.default_database(), query).await
+    }
 
-        let result: redis::RedisResult<String> = redis::cmd(query).query_async(&mut con).await;
+    async fn list_databases(&self) -> Result<Vec<String>, DatabaseError> {
+        // `CONFIG GET` is often disabled on managed services, so fall back to the databases
+        // that currently hold keys according to `INFO keyspace`.
+        let mut con = self.connection(self.default_database()).await?;
+        let configured: redis::RedisResult<HashMap<String, String>> = redis::cmd("CONFIG").arg("GET").arg("databases")
+            .query_async(&mut con).await;
 
-        match result {
-            Ok(value) => Ok(vec![Value::String(value)]),
-            Err(e) => Err(DatabaseError::QueryError(e.to_string())),
+        let mut databases: Vec<i64> = match configured.ok().and_then(|c| c.get("databases").and_then(|v| v.parse::<i64>().ok())) {
+            Some(count) => (0..count).collect(),
+            None => self.keyspace().await?.into_iter().map(|s| s.db).collect(),
+        };
+
+        if !databases.contains(&self.default_database()) {
+            databases.push(self.default_database());
+            databases.sort();
         }
-    }
 
-    async fn list_databases(&self) -> Result<Vec<String>, DatabaseError> {
-        // Redis doesn't have a concept of multiple databases in the same way as relational databases
-        Ok(vec!["default".to_string()])
+        Ok(databases.into_iter().map(|db| format!("db{}", db)).collect())
     }
 
-    async fn list_collections(&self, _database: &str) -> Result<Vec<String>, DatabaseError> {
+    async fn list_collections(&self, database: &str) -> Result<Vec<String>, DatabaseError> {
         // Redis doesn't have collections, but we can list all keys
-        let mut con = self.client.get_multiplexed_async_connection().await
-            .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
+        let db = parse_database_index(database, self.default_database())?;
+        let mut con = self.connection(db).await?;
 
         let keys: Vec<String> = redis::cmd("KEYS").arg("*").query_async(&mut con).await
-            .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+            .map_err(redis_error)?;
 
         Ok(keys)
     }
 
-    async fn get_schema(&self, _database: &str, _collection: &str) -> Result<Value, DatabaseError> {
-        // Redis doesn't have a fixed schema
-        Ok(Value::Null)
+    async fn get_schema(&self, database: &str, key: &str) -> Result<Value, DatabaseError> {
+        // Redis doesn't have a fixed schema, so describe the key itself
+        let db = parse_database_index(database, self.default_database())?;
+        let mut con = self.connection(db).await?;
+
+        let key_type: String = redis::cmd("TYPE").arg(key).query_async(&mut con).await
+            .map_err(redis_error)?;
+        if key_type == "none" {
+            return Err(DatabaseError::CollectionNotFound(key.to_string()));
+        }
+
+        let length_command = match key_type.as_str() {
+            "string" => Some("STRLEN"),
+            "list" => Some("LLEN"),
+            "set" => Some("SCARD"),
+            "zset" => Some("ZCARD"),
+            "hash" => Some("HLEN"),
+            "stream" => Some("XLEN"),
+            _ => None,
+        };
+        let length: Option<i64> = match length_command {
+            Some(command) => redis::cmd(command).arg(key).query_async(&mut con).await.ok(),
+            None => None,
+        };
+        let ttl: i64 = redis::cmd("TTL").arg(key).query_async(&mut con).await
+            .map_err(redis_error)?;
+        let encoding: Option<String> = redis::cmd("OBJECT").arg("ENCODING").arg(key).query_async(&mut con).await.ok();
+
+        Ok(json!({
+            "database": format!("db{}", db),
+            "key": key,
+            "type": key_type,
+            "length": length,
+            "ttl": ttl,
+            "encoding": encoding,
+        }))
+    }
+
+    async fn health_check(&self) -> Result<(), DatabaseError> {
+        let mut con = self.connection(self.default_database()).await?;
+        let _: String = redis::cmd("PING").query_async(&mut con).await
+            .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
+        Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,60 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::redis_custom::{parse_client_list, parse_database_index, parse_info, parse_keyspace, parse_slowlog, KeyspaceStats};
+    use serde_json::json;
+
+    const INFO: &str = "# Memory\r\nused_memory:1048576\r\nused_memory_human:1.00M\r\nmem_fragmentation_ratio:1.25\r\n\r\n# Clients\r\nconnected_clients:3\r\n\r\n# Keyspace\r\ndb0:keys=12,expires=2,avg_ttl=3000\r\ndb3:keys=1,expires=0,avg_ttl=0\r\n";
+
+    #[test]
+    fn test_parse_info_sections() {
+        let sections = parse_info(INFO);
+        assert_eq!(sections["memory"]["used_memory"], json!(1048576));
+        assert_eq!(sections["memory"]["used_memory_human"], json!("1.00M"));
+        assert_eq!(sections["memory"]["mem_fragmentation_ratio"], json!(1.25));
+        assert_eq!(sections["clients"]["connected_clients"], json!(3));
+    }
+
+    #[test]
+    fn test_parse_keyspace() {
+        let keyspace = parse_keyspace(INFO);
+        assert_eq!(keyspace, vec![
+            KeyspaceStats { db: 0, keys: 12, expires: 2, avg_ttl: 3000 },
+            KeyspaceStats { db: 3, keys: 1, expires: 0, avg_ttl: 0 },
+        ]);
+    }
+
+    #[test]
+    fn test_parse_client_list() {
+        let list = "id=3 addr=127.0.0.1:50000 name=worker db=0 cmd=client|list\nid=4 addr=127.0.0.1:50001 name= db=2 cmd=get\n";
+        let clients = parse_client_list(list);
+        assert_eq!(clients.len(), 2);
+        assert_eq!(clients[0]["name"], json!("worker"));
+        assert_eq!(clients[1]["db"], json!(2));
+    }
+
+    #[test]
+    fn test_parse_slowlog() {
+        use redis::Value;
+        let reply = Value::Bulk(vec![Value::Bulk(vec![
+            Value::Int(14),
+            Value::Int(1309448221),
+            Value::Int(15),
+            Value::Bulk(vec![Value::Data(b"KEYS".to_vec()), Value::Data(b"*".to_vec())]),
+            Value::Data(b"127.0.0.1:58217".to_vec()),
+            Value::Data(b"".to_vec()),
+        ])]);
+        let entries = parse_slowlog(&reply);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].duration_micros, 15);
+        assert_eq!(entries[0].command, vec!["KEYS", "*"]);
+        assert_eq!(entries[0].client_name, None);
+    }
+
+    #[test]
+    fn test_parse_database_index() {
+        assert_eq!(parse_database_index("db3", 0).unwrap(), 3);
+        assert_eq!(parse_database_index("5", 0).unwrap(), 5);
+        assert_eq!(parse_database_index("default", 2).unwrap(), 2);
+        assert!(parse_database_index("users", 0).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! Redis Pub/Sub support.
+//! A `RedisSubscriber` owns a dedicated connection on a background thread, keeps track of the
+//! channels and patterns requested by its users and republishes every received message on a
+//! broadcast channel. Subscriptions are added and removed on the live connection; only when the
+//! connection drops does it reconnect with backoff and resubscribe.
+use std::collections::HashMap;
+use std::time::Duration;
+use log::{info, warn};
+use serde::Serialize;
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::{broadcast, mpsc};
+use crate::utils::errors::DatabaseError;
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+/// How long the subscription thread waits for a message before it looks for subscription changes.
+const COMMAND_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Returns the keyspace notification pattern for keys matching `key_pattern` in database `db`.
+pub fn keyspace_pattern(db: i64, key_pattern: &str) -> String {
+    format!("__keyspace@{}__:{}", db, key_pattern)
+}
+
+/// A key change decoded from a `__keyspace@<db>__:<key>` notification.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KeyspaceEvent {
+    pub db: i64,
+    pub key: String,
+    pub event: String,
+}
+
+/// A message received on a subscribed channel or pattern.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PubSubMessage {
+    pub channel: String,
+    pub pattern: Option<String>,
+    pub payload: String,
+    pub keyspace: Option<KeyspaceEvent>,
+}
+
+impl PubSubMessage {
+    pub fn new(channel: &str, pattern: Option<String>, payload: String) -> Self {
+        let keyspace = parse_keyspace_channel(channel).map(|(db, key)| KeyspaceEvent {
+            db,
+            key,
+            event: payload.clone(),
+        });
+        PubSubMessage { channel: channel.to_string(), pattern, payload, keyspace }
+    }
+
+    fn from_msg(msg: &redis::Msg) -> Self {
+        let pattern = if msg.from_pattern() { msg.get_pattern::<String>().ok() } else { None };
+        let payload = msg.get_payload::<String>()
+            .unwrap_or_else(|_| String::from_utf8_lossy(msg.get_payload_bytes()).into_owned());
+        Self::new(msg.get_channel_name(), pattern, payload)
+    }
+}
+
+/// Splits a keyspace notification channel into its database index and key.
+pub fn parse_keyspace_channel(channel: &str) -> Option<(i64, String)> {
+    let rest = channel.strip_prefix("__keyspace@")?;
+    let (db, key) = rest.split_once("__:")?;
+    Some((db.parse().ok()?, key.to_string()))
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum SubscriptionCommand {
+    Subscribe(Vec<String>),
+    PSubscribe(Vec<String>),
+    Unsubscribe(Vec<String>),
+    PUnsubscribe(Vec<String>),
+}
+
+/// Handle to the background subscription thread. Cloning the handle shares the same connection.
+#[derive(Clone)]
+pub struct RedisSubscriber {
+    commands: mpsc::UnboundedSender<SubscriptionCommand>,
+    events: broadcast::Sender<PubSubMessage>,
+}
+
+impl RedisSubscriber {
+    /// Spawns the subscription thread, which stops once every handle is dropped. No connection is
+    /// opened until the first subscription.
+    pub fn spawn(client: redis::Client) -> Self {
+        let (commands, commands_rx) = mpsc::unbounded_channel();
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let thread_events = events.clone();
+        std::thread::spawn(move || run(client, commands_rx, thread_events));
+        RedisSubscriber { commands, events }
+    }
+
+    /// Receives every message delivered to any subscription of this subscriber.
+    pub fn events(&self) -> broadcast::Receiver<PubSubMessage> {
+        self.events.subscribe()
+    }
+
+    pub fn subscribe(&self, channels: Vec<String>) -> Result<(), DatabaseError> {
+        self.send(SubscriptionCommand::Subscribe(channels))
+    }
+
+    pub fn psubscribe(&self, patterns: Vec<String>) -> Result<(), DatabaseError> {
+        self.send(SubscriptionCommand::PSubscribe(patterns))
+    }
+
+    /// Releases channels obtained with `subscribe`. A channel stays subscribed on the server
+    /// until every `subscribe` call for it has been matched by an `unsubscribe`.
+    pub fn unsubscribe(&self, channels: Vec<String>) -> Result<(), DatabaseError> {
+        self.send(SubscriptionCommand::Unsubscribe(channels))
+    }
+
+    pub fn punsubscribe(&self, patterns: Vec<String>) -> Result<(), DatabaseError> {
+        self.send(SubscriptionCommand::PUnsubscribe(patterns))
+    }
+
+    fn send(&self, command: SubscriptionCommand) -> Result<(), DatabaseError> {
+        self.commands.send(command)
+            .map_err(|_| DatabaseError::ConnectionError("Redis subscriber thread has stopped".to_string()))
+    }
+}
+
+/// Reference counts of the channels and patterns the server connection should listen to.
+#[derive(Default)]
+pub(crate) struct Subscriptions {
+    channels: HashMap<String, usize>,
+    patterns: HashMap<String, usize>,
+}
+
+impl Subscriptions {
+    fn is_empty(&self) -> bool {
+        self.channels.is_empty() && self.patterns.is_empty()
+    }
+
+    /// Applies a command and returns the change to make on the server: the same command reduced
+    /// to the names whose subscription starts or ends, or `None` when there are none.
+    pub(crate) fn apply(&mut self, command: SubscriptionCommand) -> Option<SubscriptionCommand> {
+        let change = match command {
+            SubscriptionCommand::Subscribe(names) => SubscriptionCommand::Subscribe(acquire(&mut self.channels, names)),
+            SubscriptionCommand::PSubscribe(names) => SubscriptionCommand::PSubscribe(acquire(&mut self.patterns, names)),
+            SubscriptionCommand::Unsubscribe(names) => SubscriptionCommand::Unsubscribe(release(&mut self.channels, names)),
+            SubscriptionCommand::PUnsubscribe(names) => SubscriptionCommand::PUnsubscribe(release(&mut self.patterns, names)),
+        };
+        match &change {
+            SubscriptionCommand::Subscribe(names)
+            | SubscriptionCommand::PSubscribe(names)
+            | SubscriptionCommand::Unsubscribe(names)
+            | SubscriptionCommand::PUnsubscribe(names) if names.is_empty() => None,
+            _ => Some(change),
+        }
+    }
+}
+
+/// Counts a use of each name and returns the names that were not in use before.
+fn acquire(counts: &mut HashMap<String, usize>, names: Vec<String>) -> Vec<String> {
+    let mut started = Vec::new();
+    for name in names {
+        let count = counts.entry(name.clone()).or_insert(0);
+        if *count == 0 {
+            started.push(name);
+        }
+        *count += 1;
+    }
+    started
+}
+
+/// Releases a use of each name and returns the names that are no longer in use.
+fn release(counts: &mut HashMap<String, usize>, names: Vec<String>) -> Vec<String> {
+    let mut ended = Vec::new();
+    for name in names {
+        if let Some(count) = counts.get_mut(&name) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&name);
+                ended.push(name);
+            }
+        }
+    }
+    ended
+}
+
+/// Sends a subscription change on the live connection. Messages arriving before the server
+/// confirms it are kept by `redis::PubSub` and returned by later `get_message` calls.
+fn send_change(pubsub: &mut redis::PubSub, change: SubscriptionCommand) -> redis::RedisResult<()> {
+    match change {
+        SubscriptionCommand::Subscribe(names) => pubsub.subscribe(names),
+        SubscriptionCommand::PSubscribe(names) => pubsub.psubscribe(names),
+        SubscriptionCommand::Unsubscribe(names) => pubsub.unsubscribe(names),
+        SubscriptionCommand::PUnsubscribe(names) => pubsub.punsubscribe(names),
+    }
+}
+
+/// Subscribes a fresh connection to the full set, then relays its messages and applies
+/// subscription changes on it. Returns `Ok` once every `RedisSubscriber` handle is dropped, or
+/// the error that ended the connection. `backoff` is reset once the connection is subscribed.
+fn listen(
+    connection: &mut redis::Connection,
+    subscriptions: &mut Subscriptions,
+    commands: &mut mpsc::UnboundedReceiver<SubscriptionCommand>,
+    events: &broadcast::Sender<PubSubMessage>,
+    backoff: &mut Duration,
+) -> redis::RedisResult<()> {
+    let mut pubsub = connection.as_pubsub();
+    pubsub.set_read_timeout(Some(COMMAND_POLL_INTERVAL))?;
+    if !subscriptions.channels.is_empty() {
+        pubsub.subscribe(subscriptions.channels.keys().collect::<Vec<_>>())?;
+    }
+    if !subscriptions.patterns.is_empty() {
+        pubsub.psubscribe(subscriptions.patterns.keys().collect::<Vec<_>>())?;
+    }
+    info!(
+        "Redis pub/sub listening on {} channel(s) and {} pattern(s)",
+        subscriptions.channels.len(),
+        subscriptions.patterns.len()
+    );
+    *backoff = INITIAL_BACKOFF;
+
+    loop {
+        match pubsub.get_message() {
+            // Nobody listening is not an error, the message is simply dropped.
+            Ok(message) => { let _ = events.send(PubSubMessage::from_msg(&message)); }
+            Err(e) if e.is_timeout() => {}
+            Err(e) => return Err(e),
+        }
+        loop {
+            match commands.try_recv() {
+                Ok(command) => {
+                    if let Some(change) = subscriptions.apply(command) {
+                        send_change(&mut pubsub, change)?;
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+}
+
+/// The subscription loop, run on its own thread. Subscriptions change on the live connection;
+/// only a lost connection is replaced, by a fresh one subscribed to the full set.
+fn run(
+    client: redis::Client,
+    mut commands: mpsc::UnboundedReceiver<SubscriptionCommand>,
+    events: broadcast::Sender<PubSubMessage>,
+) {
+    let mut subscriptions = Subscriptions::default();
+    let mut backoff = INITIAL_BACKOFF;
+
+    loop {
+        if subscriptions.is_empty() {
+            match commands.blocking_recv() {
+                Some(command) => {
+                    subscriptions.apply(command);
+                    continue;
+                }
+                None => return,
+            }
+        }
+
+        let result = client.get_connection()
+            .and_then(|mut connection| listen(&mut connection, &mut subscriptions, &mut commands, &events, &mut backoff));
+        match result {
+            Ok(()) => return,
+            Err(e) => {
+                warn!("Redis pub/sub connection lost: {}. Resubscribing in {:?}", e, backoff);
+                std::thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                while let Ok(command) = commands.try_recv() {
+                    subscriptions.apply(command);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,32 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::redis_pubsub::{SubscriptionCommand, Subscriptions};
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_only_new_and_released_names_reach_the_server() {
+        let mut subscriptions = Subscriptions::default();
+        assert_eq!(
+            subscriptions.apply(SubscriptionCommand::Subscribe(names(&["a", "b"]))),
+            Some(SubscriptionCommand::Subscribe(names(&["a", "b"])))
+        );
+        assert_eq!(
+            subscriptions.apply(SubscriptionCommand::Subscribe(names(&["b", "c"]))),
+            Some(SubscriptionCommand::Subscribe(names(&["c"]))),
+            "Channels already subscribed should not be subscribed again"
+        );
+        assert_eq!(subscriptions.apply(SubscriptionCommand::Unsubscribe(names(&["b"]))), None, "b is still in use");
+        assert_eq!(
+            subscriptions.apply(SubscriptionCommand::Unsubscribe(names(&["a", "b", "unknown"]))),
+            Some(SubscriptionCommand::Unsubscribe(names(&["a", "b"])))
+        );
+        assert_eq!(subscriptions.apply(SubscriptionCommand::PUnsubscribe(names(&["c"]))), None, "Patterns are counted apart");
+        assert_eq!(
+            subscriptions.apply(SubscriptionCommand::PSubscribe(names(&["c"]))),
+            Some(SubscriptionCommand::PSubscribe(names(&["c"])))
+        );
+    }
+}
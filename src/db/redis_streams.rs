@@ -0,0 +1,306 @@
+//! Redis Streams support: discovering streams, inspecting consumer groups, paging through
+//! entries and tailing a stream live with `XREAD BLOCK`.
+use std::time::Duration;
+use log::warn;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tokio::task::JoinHandle;
+use crate::db::redis_custom::{redis_error, RedisPool};
+use crate::utils::errors::DatabaseError;
+
+const SCAN_COUNT: usize = 1000;
+const TAIL_BLOCK_MS: u64 = 5000;
+const TAIL_BATCH_SIZE: usize = 100;
+const TAIL_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StreamEntry {
+    pub id: String,
+    pub fields: Map<String, Value>,
+}
+
+/// A page of entries. `next` is the exclusive start (or end, when reading in reverse) of the
+/// following page and is `None` once the range is exhausted.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StreamPage {
+    pub entries: Vec<StreamEntry>,
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StreamConsumerInfo {
+    pub name: String,
+    pub pending: i64,
+    pub idle_ms: i64,
+    /// Milliseconds since the last successful read, reported by Redis 7.2 and later.
+    pub inactive_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StreamGroupInfo {
+    pub name: String,
+    pub consumers: i64,
+    pub pending: i64,
+    pub last_delivered_id: String,
+    /// `entries-read` and `lag` are reported by Redis 7.0 and later.
+    pub entries_read: Option<i64>,
+    pub lag: Option<i64>,
+    pub consumer_details: Vec<StreamConsumerInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StreamInfo {
+    pub key: String,
+    pub length: i64,
+    pub last_generated_id: Option<String>,
+    pub first_entry: Option<StreamEntry>,
+    pub last_entry: Option<StreamEntry>,
+    pub groups: Vec<StreamGroupInfo>,
+}
+
+impl StreamInfo {
+    /// Returns `(group, consumer)` pairs that hold pending entries but have been idle for at
+    /// least `idle_ms`, which usually means a worker is stuck.
+    pub fn stalled_consumers(&self, idle_ms: i64) -> Vec<(String, String)> {
+        self.groups.iter()
+            .flat_map(|group| group.consumer_details.iter()
+                .filter(move |c| c.pending > 0 && c.idle_ms >= idle_ms)
+                .map(move |c| (group.name.clone(), c.name.clone())))
+            .collect()
+    }
+}
+
+fn as_string(value: &redis::Value) -> Option<String> {
+    match value {
+        redis::Value::Nil => None,
+        value => redis::from_redis_value(value).ok(),
+    }
+}
+
+fn as_i64(value: &redis::Value) -> Option<i64> {
+    match value {
+        redis::Value::Nil => None,
+        value => redis::from_redis_value(value).ok(),
+    }
+}
+
+/// Splits a flat `[field, value, ...]` reply into pairs.
+fn pairs(value: &redis::Value) -> Vec<(String, &redis::Value)> {
+    match value {
+        redis::Value::Bulk(items) => items.chunks(2)
+            .filter(|pair| pair.len() == 2)
+            .filter_map(|pair| Some((as_string(&pair[0])?, &pair[1])))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn field<'a>(pairs: &'a [(String, &'a redis::Value)], name: &str) -> Option<&'a redis::Value> {
+    pairs.iter().find(|(k, _)| k == name).map(|(_, v)| *v)
+}
+
+fn parse_entry(value: &redis::Value) -> Option<StreamEntry> {
+    match value {
+        redis::Value::Bulk(parts) if parts.len() == 2 => Some(StreamEntry {
+            id: as_string(&parts[0])?,
+            fields: pairs(&parts[1]).into_iter()
+                .map(|(k, v)| (k, as_string(v).map(Value::String).unwrap_or(Value::Null)))
+                .collect(),
+        }),
+        _ => None,
+    }
+}
+
+/// Parses the reply of `XRANGE`/`XREVRANGE`. Deleted entries are skipped.
+pub fn parse_entries(reply: &redis::Value) -> Vec<StreamEntry> {
+    match reply {
+        redis::Value::Bulk(items) => items.iter().filter_map(parse_entry).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Parses the reply of `XREAD` into `(stream, entries)` pairs. A timed out read yields nothing.
+pub fn parse_xread(reply: &redis::Value) -> Vec<(String, Vec<StreamEntry>)> {
+    match reply {
+        redis::Value::Bulk(streams) => streams.iter()
+            .filter_map(|stream| match stream {
+                redis::Value::Bulk(parts) if parts.len() == 2 => Some((as_string(&parts[0])?, parse_entries(&parts[1]))),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Parses the reply of `XINFO GROUPS`. Consumer details are filled in separately.
+pub fn parse_groups(reply: &redis::Value) -> Vec<StreamGroupInfo> {
+    let groups = match reply {
+        redis::Value::Bulk(groups) => groups,
+        _ => return Vec::new(),
+    };
+
+    groups.iter().filter_map(|group| {
+        let fields = pairs(group);
+        Some(StreamGroupInfo {
+            name: field(&fields, "name").and_then(as_string)?,
+            consumers: field(&fields, "consumers").and_then(as_i64).unwrap_or(0),
+            pending: field(&fields, "pending").and_then(as_i64).unwrap_or(0),
+            last_delivered_id: field(&fields, "last-delivered-id").and_then(as_string).unwrap_or_default(),
+            entries_read: field(&fields, "entries-read").and_then(as_i64),
+            lag: field(&fields, "lag").and_then(as_i64),
+            consumer_details: Vec::new(),
+        })
+    }).collect()
+}
+
+/// Parses the reply of `XINFO CONSUMERS`.
+pub fn parse_consumers(reply: &redis::Value) -> Vec<StreamConsumerInfo> {
+    let consumers = match reply {
+        redis::Value::Bulk(consumers) => consumers,
+        _ => return Vec::new(),
+    };
+
+    consumers.iter().filter_map(|consumer| {
+        let fields = pairs(consumer);
+        Some(StreamConsumerInfo {
+            name: field(&fields, "name").and_then(as_string)?,
+            pending: field(&fields, "pending").and_then(as_i64).unwrap_or(0),
+            idle_ms: field(&fields, "idle").and_then(as_i64).unwrap_or(0),
+            inactive_ms: field(&fields, "inactive").and_then(as_i64),
+        })
+    }).collect()
+}
+
+/// Parses the reply of `XINFO STREAM`. Groups are filled in separately.
+pub fn parse_stream_info(key: &str, reply: &redis::Value) -> StreamInfo {
+    let fields = pairs(reply);
+    StreamInfo {
+        key: key.to_string(),
+        length: field(&fields, "length").and_then(as_i64).unwrap_or(0),
+        last_generated_id: field(&fields, "last-generated-id").and_then(as_string),
+        first_entry: field(&fields, "first-entry").and_then(parse_entry),
+        last_entry: field(&fields, "last-entry").and_then(parse_entry),
+        groups: Vec::new(),
+    }
+}
+
+impl RedisPool {
+    /// Lists the keys of type `stream` matching `pattern` in a logical database.
+    pub async fn list_streams(&self, db: i64, pattern: &str) -> Result<Vec<String>, DatabaseError> {
+        let mut con = self.connection(db).await?;
+        let mut cursor: u64 = 0;
+        let mut streams = Vec::new();
+
+        loop {
+            let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN").arg(cursor)
+                .arg("MATCH").arg(pattern)
+                .arg("COUNT").arg(SCAN_COUNT)
+                .arg("TYPE").arg("stream")
+                .query_async(&mut con).await
+                .map_err(redis_error)?;
+            streams.extend(keys);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+
+        streams.sort();
+        streams.dedup();
+        Ok(streams)
+    }
+
+    /// Describes a stream together with its consumer groups and their consumers.
+    pub async fn stream_info(&self, db: i64, key: &str) -> Result<StreamInfo, DatabaseError> {
+        let mut con = self.connection(db).await?;
+        let reply: redis::Value = redis::cmd("XINFO").arg("STREAM").arg(key).query_async(&mut con).await
+            .map_err(redis_error)?;
+        let mut info = parse_stream_info(key, &reply);
+
+        let reply: redis::Value = redis::cmd("XINFO").arg("GROUPS").arg(key).query_async(&mut con).await
+            .map_err(redis_error)?;
+        info.groups = parse_groups(&reply);
+
+        for group in info.groups.iter_mut() {
+            let reply: redis::Value = redis::cmd("XINFO").arg("CONSUMERS").arg(key).arg(&group.name)
+                .query_async(&mut con).await
+                .map_err(redis_error)?;
+            group.consumer_details = parse_consumers(&reply);
+        }
+
+        Ok(info)
+    }
+
+    /// Reads up to `count` entries with `XRANGE`, or `XREVRANGE` when `reverse` is set.
+    /// `from` is the page cursor: `None` starts at the oldest (or newest) entry, otherwise pass
+    /// the `next` value of the previous page.
+    pub async fn stream_range(&self, db: i64, key: &str, from: Option<&str>, count: usize, reverse: bool) -> Result<StreamPage, DatabaseError> {
+        let mut con = self.connection(db).await?;
+        let mut cmd = if reverse {
+            let mut cmd = redis::cmd("XREVRANGE");
+            cmd.arg(key).arg(from.unwrap_or("+")).arg("-");
+            cmd
+        } else {
+            let mut cmd = redis::cmd("XRANGE");
+            cmd.arg(key).arg(from.unwrap_or("-")).arg("+");
+            cmd
+        };
+        let reply: redis::Value = cmd.arg("COUNT").arg(count)
+            .query_async(&mut con).await
+            .map_err(redis_error)?;
+
+        let entries = parse_entries(&reply);
+        let next = match entries.last() {
+            Some(last) if entries.len() == count => Some(format!("({}", last.id)),
+            _ => None,
+        };
+        Ok(StreamPage { entries, next })
+    }
+
+    /// Tails a stream with `XREAD BLOCK` on a dedicated connection, starting after `from`
+    /// (only new entries when `None`). `on_entries` is called for every non-empty batch and
+    /// the task stops when it returns `false`. Connection errors are retried.
+    pub fn tail_stream<F>(&self, db: i64, key: String, from: Option<String>, on_entries: F) -> Result<JoinHandle<()>, DatabaseError>
+    where
+        F: Fn(Vec<StreamEntry>) -> bool + Send + 'static,
+    {
+        let client = self.client_for(db)?;
+        Ok(tokio::spawn(async move {
+            let mut last_id = from.unwrap_or_else(|| "$".to_string());
+            loop {
+                let mut con = match client.get_multiplexed_async_connection().await {
+                    Ok(con) => con,
+                    Err(e) => {
+                        warn!("Tailing stream {} failed to connect: {}", key, e);
+                        tokio::time::sleep(TAIL_RETRY_DELAY).await;
+                        continue;
+                    }
+                };
+
+                loop {
+                    let reply: redis::RedisResult<redis::Value> = redis::cmd("XREAD")
+                        .arg("COUNT").arg(TAIL_BATCH_SIZE)
+                        .arg("BLOCK").arg(TAIL_BLOCK_MS)
+                        .arg("STREAMS").arg(&key).arg(&last_id)
+                        .query_async(&mut con).await;
+                    let reply = match reply {
+                        Ok(reply) => reply,
+                        Err(e) => {
+                            warn!("Tailing stream {} failed: {}", key, e);
+                            tokio::time::sleep(TAIL_RETRY_DELAY).await;
+                            break;
+                        }
+                    };
+
+                    for (_, entries) in parse_xread(&reply) {
+                        let Some(last) = entries.last() else { continue };
+                        last_id = last.id.clone();
+                        if !on_entries(entries) {
+                            return;
+                        }
+                    }
+                }
+            }
+        }))
+    }
+}
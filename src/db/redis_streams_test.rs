@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::redis_streams::{parse_consumers, parse_entries, parse_groups, parse_stream_info, parse_xread, StreamGroupInfo};
+    use redis::Value;
+
+    fn data(s: &str) -> Value {
+        Value::Data(s.as_bytes().to_vec())
+    }
+
+    fn entry(id: &str, fields: &[(&str, &str)]) -> Value {
+        Value::Bulk(vec![
+            data(id),
+            Value::Bulk(fields.iter().flat_map(|(k, v)| vec![data(k), data(v)]).collect()),
+        ])
+    }
+
+    #[test]
+    fn test_parse_entries() {
+        let reply = Value::Bulk(vec![entry("1-0", &[("job", "resize")]), entry("2-0", &[("job", "crop")])]);
+        let entries = parse_entries(&reply);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].id, "2-0");
+        assert_eq!(entries[1].fields["job"], "crop");
+    }
+
+    #[test]
+    fn test_parse_xread_timeout_and_batch() {
+        assert!(parse_xread(&Value::Nil).is_empty());
+
+        let reply = Value::Bulk(vec![Value::Bulk(vec![data("jobs"), Value::Bulk(vec![entry("3-0", &[("job", "scan")])])])]);
+        let streams = parse_xread(&reply);
+        assert_eq!(streams[0].0, "jobs");
+        assert_eq!(streams[0].1[0].id, "3-0");
+    }
+
+    #[test]
+    fn test_parse_groups_and_consumers() {
+        let groups = Value::Bulk(vec![Value::Bulk(vec![
+            data("name"), data("workers"),
+            data("consumers"), Value::Int(2),
+            data("pending"), Value::Int(5),
+            data("last-delivered-id"), data("2-0"),
+            data("entries-read"), Value::Int(2),
+            data("lag"), Value::Int(1),
+        ])]);
+        assert_eq!(parse_groups(&groups), vec![StreamGroupInfo {
+            name: "workers".to_string(),
+            consumers: 2,
+            pending: 5,
+            last_delivered_id: "2-0".to_string(),
+            entries_read: Some(2),
+            lag: Some(1),
+            consumer_details: Vec::new(),
+        }]);
+
+        let consumers = Value::Bulk(vec![Value::Bulk(vec![
+            data("name"), data("worker-1"),
+            data("pending"), Value::Int(5),
+            data("idle"), Value::Int(600000),
+        ])]);
+        let consumers = parse_consumers(&consumers);
+        assert_eq!(consumers[0].name, "worker-1");
+        assert_eq!(consumers[0].inactive_ms, None);
+
+        let mut info = parse_stream_info("jobs", &Value::Bulk(vec![
+            data("length"), Value::Int(2),
+            data("last-generated-id"), data("2-0"),
+            data("first-entry"), entry("1-0", &[("job", "resize")]),
+            data("last-entry"), Value::Nil,
+        ]));
+        assert_eq!(info.length, 2);
+        assert_eq!(info.first_entry.as_ref().map(|e| e.id.as_str()), Some("1-0"));
+        assert_eq!(info.last_entry, None);
+
+        info.groups = parse_groups(&groups);
+        info.groups[0].consumer_details = consumers;
+        assert_eq!(info.stalled_consumers(60000), vec![("workers".to_string(), "worker-1".to_string())]);
+        assert!(info.stalled_consumers(3600000).is_empty());
+    }
+}
@@ -0,0 +1,246 @@
+//! Query results with their column metadata. Columns keep the order the backend returned them
+//! in, duplicate names (e.g. from a join) stay apart, and each column carries the backend's own
+//! type name next to a normalized `DataType`.
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Column types shared by all backends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataType {
+    Boolean,
+    Integer,
+    Float,
+    Decimal,
+    Text,
+    Binary,
+    Date,
+    Time,
+    Timestamp,
+    /// Objects, arrays, and columns whose values have different types.
+    Json,
+    /// No value of the column told its type, e.g. because all were null.
+    Unknown,
+}
+
+impl DataType {
+    /// The type of a JSON value. `null` is `Unknown`.
+    pub fn of_json(value: &Value) -> Self {
+        match value {
+            Value::Null => DataType::Unknown,
+            Value::Bool(_) => DataType::Boolean,
+            Value::Number(n) if n.is_f64() => DataType::Float,
+            Value::Number(_) => DataType::Integer,
+            Value::String(_) => DataType::Text,
+            Value::Array(_) | Value::Object(_) => DataType::Json,
+        }
+    }
+
+    /// The type of a column holding values of both types.
+    pub fn merge(self, other: DataType) -> DataType {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (DataType::Unknown, t) | (t, DataType::Unknown) => t,
+            (DataType::Integer, DataType::Float) | (DataType::Float, DataType::Integer) => DataType::Float,
+            _ => DataType::Json,
+        }
+    }
+}
+
+/// The JSON type name of a value, used as the source type of JSON backends.
+pub fn json_type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Column {
+    pub name: String,
+    /// The type as the backend names it, e.g. `int4`, `objectId` or `string`.
+    pub source_type: String,
+    pub data_type: DataType,
+    /// Whether the column may hold nulls. Backends that cannot tell report `true`.
+    pub nullable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ResultSet {
+    pub columns: Vec<Column>,
+    /// One value per column, in column order.
+    pub rows: Vec<Vec<Value>>,
+    /// Rows written by an insert, update or delete, when the backend reports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub affected_rows: Option<u64>,
+    /// How long the query took, measured by `DatabaseManager`.
+    #[serde(default)]
+    pub elapsed_ms: u64,
+    /// Whether the query matched more rows than were read, e.g. past a MongoDB cursor's first batch.
+    #[serde(default)]
+    pub truncated: bool,
+    /// The number of rows the query matched, when the backend reports it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_rows: Option<u64>,
+    /// How many times the query was sent again after a transient failure, see `RetryPolicy`.
+    #[serde(default)]
+    pub retries: u32,
+}
+
+impl ResultSet {
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// The position of the first column named `name`.
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column.name == name)
+    }
+
+    /// Builds a result from rows keyed by column name. Their order is lost, so columns are
+    /// sorted by name and typed from their JSON values.
+    pub fn from_maps(rows: Vec<HashMap<String, Value>>) -> Self {
+        let mut names: Vec<String> = rows.iter().flat_map(|row| row.keys().cloned()).collect();
+        names.sort();
+        names.dedup();
+
+        let mut builder = ResultSetBuilder::default();
+        for name in &names {
+            builder.column(name);
+        }
+        for mut row in rows {
+            builder.push_row(names.iter().filter_map(|name| row.remove(name).map(|value| Cell::json(name.clone(), value))));
+        }
+        builder.finish()
+    }
+
+    /// The rows keyed by column name. Of columns sharing a name, the first one is kept.
+    pub fn into_maps(self) -> Vec<HashMap<String, Value>> {
+        let columns = self.columns;
+        self.rows.into_iter().map(|row| {
+            let mut map = HashMap::with_capacity(columns.len());
+            for (column, value) in columns.iter().zip(row) {
+                map.entry(column.name.clone()).or_insert(value);
+            }
+            map
+        }).collect()
+    }
+
+    /// Splits the rows into results of at most `batch_size` rows with the same columns. There is
+    /// always a first batch, which keeps the counts and flags so they are reported once.
+    pub fn into_batches(self, batch_size: usize) -> Vec<ResultSet> {
+        let batch_size = batch_size.max(1);
+        let ResultSet { columns, mut rows, affected_rows, elapsed_ms, truncated, total_rows, retries } = self;
+        let mut batches = Vec::with_capacity(rows.len().div_ceil(batch_size).max(1));
+        loop {
+            let rest = rows.split_off(rows.len().min(batch_size));
+            batches.push(ResultSet { columns: columns.clone(), rows, ..Default::default() });
+            if rest.is_empty() {
+                break;
+            }
+            rows = rest;
+        }
+        batches[0].affected_rows = affected_rows;
+        batches[0].elapsed_ms = elapsed_ms;
+        batches[0].truncated = truncated;
+        batches[0].total_rows = total_rows;
+        batches[0].retries = retries;
+        batches
+    }
+}
+
+/// A value along with its types, for `ResultSetBuilder`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    pub name: String,
+    pub value: Value,
+    pub source_type: String,
+    pub data_type: DataType,
+}
+
+impl Cell {
+    /// A value typed by its JSON type.
+    pub fn json(name: impl Into<String>, value: Value) -> Self {
+        Cell {
+            name: name.into(),
+            source_type: json_type_name(&value).to_string(),
+            data_type: DataType::of_json(&value),
+            value,
+        }
+    }
+}
+
+/// Builds a `ResultSet` from rows of named values, such as documents, whose fields vary from
+/// row to row. A column is added the first time a name appears; rows without it hold null.
+#[derive(Debug, Default)]
+pub struct ResultSetBuilder {
+    columns: Vec<Column>,
+    positions: HashMap<String, usize>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl ResultSetBuilder {
+    /// Adds a column if there is none named `name` yet, returning its position.
+    fn column(&mut self, name: &str) -> usize {
+        if let Some(position) = self.positions.get(name) {
+            return *position;
+        }
+        self.columns.push(Column {
+            name: name.to_string(),
+            source_type: String::new(),
+            data_type: DataType::Unknown,
+            nullable: false,
+        });
+        self.positions.insert(name.to_string(), self.columns.len() - 1);
+        self.columns.len() - 1
+    }
+
+    pub fn push_row(&mut self, cells: impl IntoIterator<Item = Cell>) {
+        let mut row = vec![Value::Null; self.columns.len()];
+        for cell in cells {
+            let position = self.column(&cell.name);
+            if position >= row.len() {
+                row.resize(position + 1, Value::Null);
+            }
+            let column = &mut self.columns[position];
+            if !cell.value.is_null() {
+                if column.source_type.is_empty() {
+                    column.source_type = cell.source_type;
+                }
+                column.data_type = column.data_type.merge(cell.data_type);
+            }
+            row[position] = cell.value;
+        }
+        self.rows.push(row);
+    }
+
+    pub fn finish(mut self) -> ResultSet {
+        let width = self.columns.len();
+        for row in &mut self.rows {
+            if row.len() < width {
+                row.resize(width, Value::Null);
+            }
+        }
+        // Columns missing from some rows hold nulls there
+        let mut present = vec![0; width];
+        for row in &self.rows {
+            for (count, value) in present.iter_mut().zip(row) {
+                if !value.is_null() {
+                    *count += 1;
+                }
+            }
+        }
+        for (column, count) in self.columns.iter_mut().zip(present) {
+            column.nullable = count < self.rows.len();
+            if column.source_type.is_empty() {
+                column.source_type = "null".to_string();
+            }
+        }
+        ResultSet { columns: self.columns, rows: self.rows, ..Default::default() }
+    }
+}
@@ -0,0 +1,71 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::result_set::{Cell, DataType, ResultSet, ResultSetBuilder};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    fn row(fields: Value) -> HashMap<String, Value> {
+        serde_json::from_value(fields).unwrap()
+    }
+
+    #[test]
+    fn test_result_from_maps() {
+        let result = ResultSet::from_maps(vec![
+            row(json!({"name": "a", "id": 1, "score": 1.5})),
+            row(json!({"name": null, "id": 2, "score": 2})),
+            row(json!({"id": 3})),
+        ]);
+        let names: Vec<_> = result.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["id", "name", "score"], "Columns should be sorted by name");
+        assert_eq!(result.rows[2], vec![json!(3), Value::Null, Value::Null]);
+
+        let id = &result.columns[0];
+        assert_eq!((id.data_type, id.source_type.as_str(), id.nullable), (DataType::Integer, "number", false));
+        let name = &result.columns[1];
+        assert_eq!((name.data_type, name.source_type.as_str(), name.nullable), (DataType::Text, "string", true));
+        assert_eq!(result.columns[2].data_type, DataType::Float, "Integers and floats merge to floats");
+        assert_eq!(result.row_count(), 3);
+        assert_eq!(result.column_index("score"), Some(2));
+    }
+
+    #[test]
+    fn test_builder_keeps_column_order() {
+        let mut builder = ResultSetBuilder::default();
+        builder.push_row(vec![Cell::json("b", json!(1)), Cell::json("a", json!("x"))]);
+        builder.push_row(vec![Cell::json("c", json!(null)), Cell::json("a", json!(true))]);
+        let result = builder.finish();
+
+        let names: Vec<_> = result.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["b", "a", "c"], "Columns should be in the order they first appear");
+        assert_eq!(result.rows, vec![
+            vec![json!(1), json!("x"), Value::Null],
+            vec![Value::Null, json!(true), Value::Null],
+        ]);
+        assert_eq!(result.columns[1].data_type, DataType::Json, "Mixed types become JSON");
+        assert_eq!(result.columns[1].source_type, "string", "The first type seen is kept");
+        assert_eq!((result.columns[2].data_type, result.columns[2].source_type.as_str()), (DataType::Unknown, "null"));
+        assert!(result.columns[0].nullable);
+        assert!(!result.columns[1].nullable);
+    }
+
+    #[test]
+    fn test_result_batches_and_maps() {
+        let mut result = ResultSet::from_maps((0..5).map(|i| row(json!({"id": i}))).collect());
+        result.truncated = true;
+        let batches = result.clone().into_batches(2);
+        assert_eq!(batches.iter().map(ResultSet::row_count).collect::<Vec<_>>(), vec![2, 2, 1]);
+        assert!(batches[0].truncated && !batches[1].truncated);
+
+        let empty = ResultSet::default().into_batches(10);
+        assert_eq!(empty.len(), 1, "An empty result has one batch");
+
+        let mut joined = result.clone();
+        joined.columns.push(joined.columns[0].clone());
+        for row in &mut joined.rows {
+            row.push(json!("duplicate"));
+        }
+        let maps = joined.into_maps();
+        assert_eq!(maps[4]["id"], json!(4), "The first of duplicate columns is kept");
+        assert_eq!(maps.len(), 5);
+    }
+}
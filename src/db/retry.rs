@@ -0,0 +1,119 @@
+//! Retries of reads failing with a transient error, such as a connection reset, a failover, an
+//! Elasticsearch 429 or 503, or a MongoDB error with a retryable read code. Only idempotent reads may be
+//! retried: a write whose reply was lost may have been applied already.
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use log::warn;
+use serde::Serialize;
+use crate::config::config::RetryConfig;
+use crate::utils::errors::DatabaseError;
+
+/// How a source retries reads. The default policy makes a single attempt.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts in total, the first one included.
+    pub max_attempts: u32,
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy { max_attempts: 1, backoff: Duration::ZERO, max_backoff: Duration::ZERO, jitter: false }
+    }
+}
+
+impl From<&RetryConfig> for RetryPolicy {
+    fn from(config: &RetryConfig) -> Self {
+        RetryPolicy {
+            max_attempts: config.max_attempts.max(1),
+            backoff: Duration::from_millis(config.backoff_ms),
+            max_backoff: Duration::from_millis(config.max_backoff_ms.max(config.backoff_ms)),
+            jitter: config.jitter,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay before retry number `retry`, counting from 1. `random` in `[0, 1)` picks the
+    /// delay between half and all of the backoff when the policy has jitter.
+    pub fn delay(&self, retry: u32, random: f64) -> Duration {
+        let doublings = retry.saturating_sub(1).min(31);
+        let delay = self.backoff.saturating_mul(1 << doublings).min(self.max_backoff);
+        if self.jitter {
+            delay.mul_f64(0.5 + random.clamp(0.0, 1.0) / 2.0)
+        } else {
+            delay
+        }
+    }
+
+    /// Runs `attempt` until it succeeds, fails with an error that is not retryable, or the
+    /// attempts run out. Returns the last result and how many times `attempt` was retried.
+    pub async fn run<T, F, Fut>(&self, mut attempt: F) -> (Result<T, DatabaseError>, u32)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, DatabaseError>>,
+    {
+        let mut retries = 0;
+        loop {
+            match attempt().await {
+                Err(e) if e.is_retryable() && retries + 1 < self.max_attempts => {
+                    retries += 1;
+                    let delay = self.delay(retries, rand::random());
+                    warn!("Retrying read in {:?} after a transient error: {}", delay, e);
+                    tokio::time::sleep(delay).await;
+                }
+                result => return (result, retries),
+            }
+        }
+    }
+}
+
+/// Retries of every source since the server started. Kept outside `DatabaseManager`, which is
+/// rebuilt whenever sources reconnect.
+pub static RETRY_METRICS: RetryMetrics = RetryMetrics::new();
+
+/// Counts retried reads.
+#[derive(Debug, Default)]
+pub struct RetryMetrics {
+    retries: AtomicU64,
+    recovered: AtomicU64,
+    exhausted: AtomicU64,
+}
+
+/// A snapshot of `RetryMetrics`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct RetryStats {
+    /// Attempts made after a transient failure.
+    pub retries: u64,
+    /// Reads that succeeded after being retried.
+    pub recovered: u64,
+    /// Reads that were retried and still failed with a transient error on their last attempt.
+    pub exhausted: u64,
+}
+
+impl RetryMetrics {
+    pub const fn new() -> Self {
+        RetryMetrics { retries: AtomicU64::new(0), recovered: AtomicU64::new(0), exhausted: AtomicU64::new(0) }
+    }
+
+    /// Records a read that was retried `retries` times.
+    pub fn record<T>(&self, result: &Result<T, DatabaseError>, retries: u32) {
+        self.retries.fetch_add(u64::from(retries), Ordering::Relaxed);
+        match result {
+            Ok(_) if retries > 0 => { self.recovered.fetch_add(1, Ordering::Relaxed); }
+            Err(e) if e.is_retryable() && retries > 0 => { self.exhausted.fetch_add(1, Ordering::Relaxed); }
+            _ => {}
+        }
+    }
+
+    pub fn stats(&self) -> RetryStats {
+        RetryStats {
+            retries: self.retries.load(Ordering::Relaxed),
+            recovered: self.recovered.load(Ordering::Relaxed),
+            exhausted: self.exhausted.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,141 @@
+#[cfg(test)]
+mod tests {
+    use crate::config::config::RetryConfig;
+    use crate::db::DatabasePool;
+    use crate::db::native_query::NativeQuery;
+    use crate::db::query_builder::{DatabaseManager, DatabaseType, QueryBuilder, QueryOperation, Table};
+    use crate::db::retry::{RetryMetrics, RetryPolicy, RetryStats};
+    use crate::utils::errors::DatabaseError;
+    use async_trait::async_trait;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Fails its first `failures` calls with errors made by `error`.
+    struct FlakyPool {
+        failures: u32,
+        error: fn() -> DatabaseError,
+        calls: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl DatabasePool for FlakyPool {
+        async fn execute(&self, _query: &str, _params: Vec<Value>) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err((self.error)());
+            }
+            Ok(vec![[("value".to_string(), json!("a"))].into_iter().collect()])
+        }
+
+        async fn execute_native(&self, _query: &NativeQuery) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+            self.execute("", Vec::new()).await
+        }
+    }
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy { max_attempts, backoff: Duration::from_millis(1), max_backoff: Duration::from_millis(2), jitter: false }
+    }
+
+    fn flaky_manager(failures: u32, error: fn() -> DatabaseError, max_attempts: u32) -> (DatabaseManager, Arc<AtomicU32>) {
+        flaky_source(DatabaseType::Redis, failures, error, max_attempts)
+    }
+
+    fn flaky_source(database_type: DatabaseType, failures: u32, error: fn() -> DatabaseError, max_attempts: u32) -> (DatabaseManager, Arc<AtomicU32>) {
+        let calls = Arc::new(AtomicU32::new(0));
+        let mut manager = DatabaseManager::new();
+        manager.add_source("source", database_type, Box::new(FlakyPool { failures, error, calls: Arc::clone(&calls) }));
+        manager.set_retry_policy("source", policy(max_attempts));
+        (manager, calls)
+    }
+
+    fn get() -> QueryBuilder {
+        QueryBuilder::new(DatabaseType::Redis).table(Table::Custom("key".to_string()))
+    }
+
+    #[test]
+    fn test_delay_doubles_up_to_the_maximum() {
+        let policy = RetryPolicy::from(&RetryConfig { max_attempts: 5, backoff_ms: 100, max_backoff_ms: 300, jitter: false });
+        let delays: Vec<_> = (1..=4).map(|retry| policy.delay(retry, 0.0)).collect();
+        assert_eq!(delays, [100, 200, 300, 300].map(Duration::from_millis));
+
+        let jittered = RetryPolicy { jitter: true, ..policy };
+        assert_eq!(jittered.delay(2, 0.0), Duration::from_millis(100), "Jitter should keep at least half of the delay");
+        assert_eq!(jittered.delay(2, 0.5), Duration::from_millis(150));
+        assert_eq!(RetryPolicy::default().max_attempts, 1, "Reads should not be retried without a policy");
+    }
+
+    #[tokio::test]
+    async fn test_reads_are_retried_after_transient_errors() {
+        let (manager, calls) = flaky_manager(2, || DatabaseError::elasticsearch(503, "unavailable"), 3);
+        let result = manager.query(&get()).await.unwrap();
+        assert_eq!(result.retries, 2, "The retries should be reported with the result");
+        assert_eq!(result.rows, vec![vec![json!("a")]]);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let (manager, calls) = flaky_manager(5, || DatabaseError::ConnectionError("reset".to_string()), 3);
+        assert!(matches!(manager.execute(&get()).await, Err(DatabaseError::ConnectionError(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 3, "Attempts should stop at the policy's maximum");
+    }
+
+    #[tokio::test]
+    async fn test_aggregations_are_retried() {
+        let (manager, calls) = flaky_source(DatabaseType::MongoDB, 1, || DatabaseError::ConnectionError("reset".to_string()), 3);
+        let aggregate = QueryBuilder::new(DatabaseType::MongoDB)
+            .table(Table::Custom("orders".to_string()))
+            .operation(QueryOperation::Aggregate);
+        assert_eq!(manager.query(&aggregate).await.unwrap().retries, 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_errors_and_writes_are_not_retried() {
+        let (manager, calls) = flaky_manager(1, || DatabaseError::postgres("42601", "syntax error"), 3);
+        assert!(manager.execute(&get()).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1, "Permanent errors should not be retried");
+
+        let (manager, calls) = flaky_manager(1, || DatabaseError::ConnectionError("reset".to_string()), 3);
+        let set = get().operation(QueryOperation::Insert).value(json!("b"));
+        assert!(manager.execute(&set).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1, "Writes should not be retried");
+    }
+
+    #[tokio::test]
+    async fn test_native_reads_are_retried() {
+        let reset = || DatabaseError::ConnectionError("reset".to_string());
+        let (manager, calls) = flaky_manager(1, reset, 3);
+        let get = NativeQuery::Redis { db: None, command: "GET key".to_string() };
+        let result = manager.query_native_with_timeout(&get, None).await.unwrap();
+        assert_eq!(result.retries, 1, "Native reads should be retried like builder selects");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let (manager, calls) = flaky_manager(1, reset, 3);
+        let incr = NativeQuery::Redis { db: None, command: "INCR key".to_string() };
+        assert!(manager.execute_native(&incr).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1, "Native writes should be sent once");
+    }
+
+    #[tokio::test]
+    async fn test_policies_belong_to_sources() {
+        let (mut manager, _) = flaky_manager(1, || DatabaseError::ConnectionError("reset".to_string()), 3);
+        manager.set_retry_policy("other", policy(1));
+        assert_eq!(manager.query(&get()).await.unwrap().retries, 1);
+
+        let calls = Arc::new(AtomicU32::new(0));
+        let failing = FlakyPool { failures: 1, error: || DatabaseError::ConnectionError("reset".to_string()), calls: Arc::clone(&calls) };
+        manager.add_source("replica", DatabaseType::Redis, Box::new(failing));
+        assert!(manager.query(&get()).await.is_err(), "The policy of the replaced source should not apply");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_metrics_count_retried_reads() {
+        let metrics = RetryMetrics::new();
+        metrics.record::<()>(&Ok(()), 0);
+        metrics.record::<()>(&Ok(()), 2);
+        metrics.record::<()>(&Err(DatabaseError::ConnectionError("reset".to_string())), 1);
+        metrics.record::<()>(&Err(DatabaseError::Cancelled), 1);
+        assert_eq!(metrics.stats(), RetryStats { retries: 4, recovered: 1, exhausted: 1 });
+    }
+}
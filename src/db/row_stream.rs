@@ -0,0 +1,47 @@
+//! Results read in batches of rows, so large results can be sent while they are read instead of
+//! being collected in memory first. Pools read from the backend's own cursor where there is one:
+//! PostgreSQL rows as they arrive (`query_raw`), MongoDB cursors (`getMore`), Elasticsearch
+//! `search_after` and Redis `SCAN`.
+use std::future::Future;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use crate::db::result_set::ResultSet;
+use crate::utils::errors::DatabaseError;
+
+/// The number of rows per batch when the client does not ask for another size.
+pub const DEFAULT_BATCH_SIZE: usize = 500;
+
+/// Batches of rows, each with the columns of the result. The first batch carries the counts and
+/// flags of the result, so a result always has at least one batch. The stream ends after the
+/// last batch or the first error; dropping it early stops the query.
+pub type RowStream<'a> = BoxStream<'a, Result<ResultSet, DatabaseError>>;
+
+/// Splits a result that is already in memory into batches of at most `batch_size` rows.
+pub fn batched<'a>(result: ResultSet, batch_size: usize) -> RowStream<'a> {
+    stream::iter(result.into_batches(batch_size).into_iter().map(Ok)).boxed()
+}
+
+/// Runs a query to completion and splits its rows into batches, for results without a cursor.
+pub fn collected<'a>(
+    result: impl Future<Output = Result<ResultSet, DatabaseError>> + Send + 'a,
+    batch_size: usize,
+) -> RowStream<'a> {
+    stream::once(result)
+        .map_ok(move |result| batched(result, batch_size))
+        .try_flatten()
+        .boxed()
+}
+
+/// Reads a stream to its end and joins its batches back into one result. The batches must have
+/// the same columns, as PostgreSQL batches do.
+pub async fn concatenated(mut batches: RowStream<'_>) -> Result<ResultSet, DatabaseError> {
+    let mut result = batches.try_next().await?.unwrap_or_default();
+    while let Some(batch) = batches.try_next().await? {
+        result.rows.extend(batch.rows);
+    }
+    Ok(result)
+}
+
+/// A stream failing with `error` right away.
+pub fn failed<'a>(error: DatabaseError) -> RowStream<'a> {
+    stream::once(async move { Err(error) }).boxed()
+}
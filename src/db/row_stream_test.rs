@@ -0,0 +1,42 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::result_set::ResultSet;
+    use crate::db::row_stream::{batched, concatenated, failed};
+    use crate::utils::errors::DatabaseError;
+    use futures::TryStreamExt;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn result(count: usize) -> ResultSet {
+        let rows: Vec<HashMap<String, serde_json::Value>> =
+            (0..count).map(|i| [("id".to_string(), json!(i))].into_iter().collect()).collect();
+        ResultSet { total_rows: Some(count as u64), ..ResultSet::from_maps(rows) }
+    }
+
+    #[tokio::test]
+    async fn test_rows_are_split_into_batches() {
+        let batches: Vec<_> = batched(result(5), 2).try_collect().await.unwrap();
+        assert_eq!(batches.iter().map(ResultSet::row_count).collect::<Vec<_>>(), vec![2, 2, 1]);
+        assert_eq!(batches[2].rows[0][0], json!(4), "Rows should keep their order");
+        assert_eq!(batches[2].columns, batches[0].columns, "Every batch has the columns");
+        assert_eq!(batches[0].total_rows, Some(5));
+        assert_eq!(batches[1].total_rows, None, "Counts are reported once");
+
+        let batches: Vec<_> = batched(result(4), 2).try_collect().await.unwrap();
+        assert_eq!(batches.len(), 2);
+        let batches: Vec<_> = batched(result(0), 2).try_collect().await.unwrap();
+        assert_eq!(batches.len(), 1, "An empty result still has a batch");
+        let batches: Vec<_> = batched(result(2), 0).try_collect().await.unwrap();
+        assert_eq!(batches.len(), 2, "Batches hold at least one row");
+
+        let result: Result<Vec<_>, _> = failed(DatabaseError::Cancelled).try_collect().await;
+        assert!(matches!(result, Err(DatabaseError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_concatenated() {
+        let joined = concatenated(batched(result(5), 2)).await.unwrap();
+        assert_eq!(joined, result(5));
+        assert!(matches!(concatenated(failed(DatabaseError::Cancelled)).await, Err(DatabaseError::Cancelled)));
+    }
+}
@@ -0,0 +1,75 @@
+//! Client TLS of a source. The certificate files are read when the source is opened and each
+//! driver applies them in the form it expects. Settings a driver cannot honour are rejected
+//! instead of being silently ignored.
+use std::fmt::Display;
+use crate::config::config::{TlsConfig, TlsVerify};
+use crate::utils::errors::DatabaseError;
+
+/// The PEM files of a `TlsConfig`.
+pub struct TlsFiles {
+    pub ca: Option<Vec<u8>>,
+    pub cert: Option<Vec<u8>>,
+    /// `None` when the key is in the certificate file.
+    pub key: Option<Vec<u8>>,
+}
+
+impl TlsFiles {
+    pub fn load(tls: &TlsConfig) -> Result<Self, DatabaseError> {
+        if tls.key_file.is_some() && tls.cert_file.is_none() {
+            return Err(DatabaseError::ConfigurationError("TLS key_file is set without cert_file".to_string()));
+        }
+        Ok(TlsFiles {
+            ca: tls.ca_file.as_deref().map(read).transpose()?,
+            cert: tls.cert_file.as_deref().map(read).transpose()?,
+            key: tls.key_file.as_deref().filter(|key| Some(*key) != tls.cert_file.as_deref()).map(read).transpose()?,
+        })
+    }
+
+    /// The client certificate and key, for mutual TLS.
+    pub fn identity(&self) -> Option<(&[u8], &[u8])> {
+        self.cert.as_deref().map(|cert| (cert, self.key.as_deref().unwrap_or(cert)))
+    }
+
+    /// The client certificate followed by its key, in one PEM buffer.
+    pub fn identity_pem(&self) -> Option<Vec<u8>> {
+        let mut pem = self.cert.clone()?;
+        if let Some(key) = &self.key {
+            if !pem.ends_with(b"\n") {
+                pem.push(b'\n');
+            }
+            pem.extend_from_slice(key);
+        }
+        Some(pem)
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, DatabaseError> {
+    std::fs::read(path).map_err(|e| DatabaseError::ConfigurationError(format!("Could not read {}: {}", path, e)))
+}
+
+/// The TLS settings a driver can apply besides certificates and `verify = "full"` or `"none"`.
+pub struct TlsSupport {
+    pub verify_ca_only: bool,
+    pub server_name: bool,
+}
+
+impl TlsSupport {
+    pub fn check(&self, backend: &str, tls: &TlsConfig) -> Result<(), DatabaseError> {
+        if tls.verify == TlsVerify::CaOnly && !self.verify_ca_only {
+            return Err(DatabaseError::UnsupportedOperation(format!(
+                "{} cannot skip host name verification; use verify = \"full\" or \"none\"", backend
+            )));
+        }
+        if tls.server_name.is_some() && !self.server_name {
+            return Err(DatabaseError::UnsupportedOperation(format!(
+                "{} always sends the host as the TLS server name; remove server_name", backend
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Wraps a driver error about certificates or keys.
+pub fn tls_error(error: impl Display) -> DatabaseError {
+    DatabaseError::ConfigurationError(format!("Invalid TLS configuration: {}", error))
+}
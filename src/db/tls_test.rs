@@ -0,0 +1,123 @@
+#[cfg(test)]
+mod tests {
+    use crate::config::config::{TlsConfig, TlsVerify};
+    use crate::db::elasticsearch_custom::with_tls;
+    use crate::db::mongodb_custom::mongo_tls;
+    use crate::db::postgres_custom::PostgresTls;
+    use crate::db::redis_custom::tls_client;
+    use crate::db::tls::{TlsFiles, TlsSupport};
+    use crate::utils::errors::DatabaseError;
+    use elasticsearch::http::transport::{SingleNodeConnectionPool, TransportBuilder};
+    use mongodb::options::Tls;
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+    use std::path::PathBuf;
+
+    /// PEM files of a certificate authority and of a `localhost` certificate it issued.
+    struct TestCertificates {
+        ca: String,
+        cert: String,
+        key: String,
+    }
+
+    impl TestCertificates {
+        fn generate(prefix: &str) -> Self {
+            let ca_key = KeyPair::generate().unwrap();
+            let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+            ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            let ca = ca_params.self_signed(&ca_key).unwrap();
+
+            let key = KeyPair::generate().unwrap();
+            let cert = CertificateParams::new(vec!["localhost".to_string()]).unwrap()
+                .signed_by(&key, &ca, &ca_key).unwrap();
+            TestCertificates {
+                ca: write_pem(&format!("{}_ca.pem", prefix), &ca.pem()),
+                cert: write_pem(&format!("{}_client.pem", prefix), &cert.pem()),
+                key: write_pem(&format!("{}_client.key", prefix), &key.serialize_pem()),
+            }
+        }
+
+        fn tls(&self) -> TlsConfig {
+            TlsConfig { ca_file: Some(self.ca.clone()), cert_file: Some(self.cert.clone()), key_file: Some(self.key.clone()), ..Default::default() }
+        }
+    }
+
+    fn write_pem(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, content).expect("Failed to write test certificate");
+        path.display().to_string()
+    }
+
+    fn read(path: &str) -> Vec<u8> {
+        std::fs::read(path).unwrap()
+    }
+
+    #[test]
+    fn test_load_certificate_files() {
+        let generated = TestCertificates::generate("source_watch_load");
+        let files = TlsFiles::load(&generated.tls()).expect("Failed to load certificates");
+        let (ca, cert, key) = (read(&generated.ca), read(&generated.cert), read(&generated.key));
+        assert_eq!(files.ca.as_deref(), Some(&ca[..]));
+        assert_eq!(files.identity(), Some((&cert[..], &key[..])));
+        assert_eq!(files.identity_pem(), Some([cert.clone(), key].concat()));
+
+        let combined = TlsConfig { cert_file: Some(generated.cert.clone()), key_file: Some(generated.cert), ..Default::default() };
+        let files = TlsFiles::load(&combined).unwrap();
+        assert_eq!(files.identity(), Some((&cert[..], &cert[..])), "The key should be read from the certificate file");
+        assert_eq!(files.identity_pem(), Some(cert));
+
+        let missing = TlsConfig { ca_file: Some("/nonexistent/ca.pem".to_string()), ..Default::default() };
+        match TlsFiles::load(&missing) {
+            Err(DatabaseError::ConfigurationError(message)) => assert!(message.contains("/nonexistent/ca.pem"), "{}", message),
+            _ => panic!("Expected an error naming the missing file"),
+        }
+        let key_only = TlsConfig { key_file: Some("client.key".to_string()), ..Default::default() };
+        assert!(TlsFiles::load(&key_only).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_settings_are_rejected() {
+        let support = TlsSupport { verify_ca_only: false, server_name: false };
+        let insecure = TlsConfig { verify: TlsVerify::Disabled, ..Default::default() };
+        assert!(support.check("Redis", &insecure).is_ok());
+
+        let ca_only = TlsConfig { verify: TlsVerify::CaOnly, ..Default::default() };
+        assert!(matches!(support.check("Redis", &ca_only), Err(DatabaseError::UnsupportedOperation(_))));
+        let sni = TlsConfig { server_name: Some("db.internal".to_string()), ..Default::default() };
+        assert!(matches!(support.check("Redis", &sni), Err(DatabaseError::UnsupportedOperation(_))));
+
+        let full_support = TlsSupport { verify_ca_only: true, server_name: true };
+        assert!(full_support.check("PostgreSQL", &ca_only).is_ok());
+        assert!(full_support.check("PostgreSQL", &sni).is_ok());
+    }
+
+    #[test]
+    fn test_connectors_accept_generated_certificates() {
+        let generated = TestCertificates::generate("source_watch_connectors");
+        let tls = generated.tls();
+        assert!(TlsConfig::default().enabled, "A tls table should enable TLS unless it says otherwise");
+
+        PostgresTls::new(&tls).expect("PostgreSQL should accept the certificates");
+        PostgresTls::new(&TlsConfig { verify: TlsVerify::CaOnly, ..tls.clone() }).unwrap();
+        tls_client("redis://localhost:6380", &tls).expect("Redis should accept the certificates");
+        let pool = SingleNodeConnectionPool::new("https://localhost:9200".parse().unwrap());
+        with_tls(TransportBuilder::new(pool), &tls).expect("Elasticsearch should accept the certificates")
+            .build().expect("Elasticsearch should build a client with the certificates");
+
+        let combined = TlsConfig { key_file: None, cert_file: Some(write_pem(
+            "source_watch_connectors_combined.pem",
+            &String::from_utf8([read(&generated.cert), read(&generated.key)].concat()).unwrap(),
+        )), ..tls.clone() };
+        match mongo_tls(&combined).expect("MongoDB should accept the certificates") {
+            Tls::Enabled(options) => {
+                assert_eq!(options.ca_file_path, Some(PathBuf::from(&generated.ca)));
+                assert_eq!(options.cert_key_file_path, combined.cert_file.map(PathBuf::from));
+                assert_eq!(options.allow_invalid_hostnames, Some(false));
+            }
+            Tls::Disabled => panic!("TLS should be enabled"),
+        }
+
+        let garbage = TlsConfig { ca_file: Some(write_pem("source_watch_garbage_ca.pem", "CA\n")), ..Default::default() };
+        assert!(PostgresTls::new(&garbage).is_err(), "Invalid certificates should be rejected");
+        assert!(tls_client("redis://localhost:6380", &TlsConfig { cert_file: garbage.ca_file.clone(), ..Default::default() }).is_err());
+    }
+}
@@ -0,0 +1,8 @@
+//! SourceWatch: browse, query and monitor databases from one place. The binary serves the
+//! `ws` protocol; everything else is usable as a library.
+pub mod config;
+pub mod db;
+pub mod logging;
+pub mod models;
+pub mod utils;
+pub mod ws;
@@ -1,4 +1,3 @@
-use env_logger::Env;
 use log::{error, info, warn};
 
 pub fn log_info(message: &str) {
@@ -1,7 +1,8 @@
 use env_logger::Env;
 
-mod logger;
+pub mod logger;
 
-pub fn init() {
-    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+/// Initializes logging with `level` as the default filter. `RUST_LOG` takes precedence.
+pub fn init(level: &str) {
+    env_logger::Builder::from_env(Env::default().default_filter_or(level)).init();
 }
\ No newline at end of file
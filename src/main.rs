@@ -1,29 +1,71 @@
-use crate::ws::handler::ws_handler;
 use axum::{
     routing::get,
     Router,
 };
-use log::{info, error};
-use std::sync::{Arc};
-use ws::handler::AppState;
-
-mod config;
-mod db;
-mod logging;
-mod models;
-mod utils;
-mod ws;
+use log::{info, error, warn};
+use source_watch::config::layered::{CliArgs, CliCommand, LayeredConfig, USAGE};
+use source_watch::ws::handler::ws_handler;
+use source_watch::ws::state::AppState;
+use source_watch::{config, logging, ws};
+use std::sync::Arc;
 
 #[tokio::main]
 async fn main() {
-    logging::init();
+    let cli = match CliArgs::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if cli.help {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let dotenv = config::config::load_dotenv();
+    let env: Vec<(String, String)> = std::env::vars().collect();
+    let layered = match LayeredConfig::load(&cli, &env) {
+        Ok(layered) => layered,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if cli.command == CliCommand::PrintConfig {
+        println!("{}", layered.redacted());
+        return;
+    }
+
+    let config = layered.config;
+    logging::init(&config.server.log_level);
     info!("Starting the SourceWatch application...");
+    if let Err(e) = dotenv {
+        error!("{}", e);
+    }
+    for warning in &layered.warnings {
+        warn!("{}", warning);
+    }
+    if layered.files.is_empty() {
+        warn!("No configuration file found, starting without sources");
+    } else {
+        info!("Loaded configuration from {}", layered.files.join(", "));
+    }
 
-    let state = ws::state::init_app_state().await;
+    let state = ws::state::init_app_state(&config).await;
+    ws::monitor::spawn_monitor(Arc::clone(&state), ws::monitor::MONITOR_INTERVAL);
+    ws::config_watcher::spawn_config_watcher(Arc::clone(&state), cli, ws::config_watcher::CONFIG_POLL_INTERVAL);
+    ws::supervisor::spawn_supervisor(Arc::clone(&state));
     let app = create_app(state);
 
-    let addr = "0.0.0.0:6262";
-    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+    let addr = &config.server.bind;
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to listen on {}: {}", addr, e);
+            std::process::exit(1);
+        }
+    };
     println!("Listening on {}", addr);
     axum::serve(listener, app.into_make_service())
         .await
@@ -33,5 +75,5 @@ async fn main() {
 fn create_app(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/ws", get(ws_handler))
-        .with_state(Arc::from(state))
+        .with_state(state)
 }
\ No newline at end of file
@@ -1,13 +1,69 @@
+//! Errors of queries and sources. Every error has a stable `ErrorCode` and tells whether it is
+//! worth retrying; errors reported by a backend keep the backend's own code (SQLSTATE, MongoDB
+//! error code, Elasticsearch HTTP status or Redis error prefix).
 use std::fmt;
-use cdrs_tokio::error::Error;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use crate::db::query_builder::DatabaseType;
 
-#[derive(Debug)]
-pub struct AppError {
+/// What went wrong, independent of the backend and of the message wording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The connection could not be opened or was lost, e.g. reset by the server.
+    Connection,
+    /// The source is misconfigured, e.g. an invalid URL or unreadable certificate. Sending the
+    /// query again cannot help until the configuration is fixed.
+    Configuration,
+    /// The server cannot serve the query right now, e.g. during a failover or shutdown.
+    Unavailable,
+    RateLimited,
+    Timeout,
+    Cancelled,
+    NotFound,
+    InvalidQuery,
+    Unsupported,
+    Conversion,
+    /// The query conflicted with another one, e.g. a deadlock or a version conflict.
+    Conflict,
+    ConstraintViolation,
+    PermissionDenied,
+    QueryFailed,
+}
+
+impl ErrorCode {
+    /// Whether the same request may succeed when sent again.
+    pub fn is_retryable(self) -> bool {
+        matches!(self, ErrorCode::Connection | ErrorCode::Unavailable | ErrorCode::RateLimited)
+    }
+}
+
+/// The code a backend gave an error.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendCode {
+    /// A PostgreSQL SQLSTATE, e.g. `23505`.
+    Sqlstate(String),
+    /// A MongoDB server error code, e.g. `11000`.
+    MongoCode(i32),
+    /// The HTTP status of an Elasticsearch response.
+    HttpStatus(u16),
+    /// The prefix of a Redis error reply, e.g. `WRONGTYPE`.
+    RedisCode(String),
+}
+
+/// An error reported by a backend, classified from its code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackendError {
+    pub backend: DatabaseType,
+    pub code: ErrorCode,
+    pub backend_code: BackendCode,
+    pub retryable: bool,
     pub message: String,
 }
 
-impl fmt::Display for AppError {
+impl fmt::Display for BackendError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.message)
     }
@@ -17,6 +73,8 @@ impl fmt::Display for AppError {
 pub enum DatabaseError {
     #[error("Connection error: {0}")]
     ConnectionError(String),
+    #[error("Invalid source configuration: {0}")]
+    ConfigurationError(String),
     #[error("Query execution error: {0}")]
     QueryError(String),
     #[error("Database not found: {0}")]
@@ -33,8 +91,208 @@ pub enum DatabaseError {
     MissingField(String),
     #[error("Invalid query: {0}")]
     InvalidQuery(String),
+    #[error("Query timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("Query was cancelled")]
+    Cancelled,
+    #[error(transparent)]
+    Builder(#[from] QueryBuilderError),
+    #[error("{0}")]
+    Backend(BackendError),
+}
+
+impl DatabaseError {
+    /// A PostgreSQL error with its SQLSTATE. A `query_canceled` error caused by the source's
+    /// `statement_timeout` is a timeout rather than a cancellation.
+    pub fn postgres(sqlstate: &str, message: impl Into<String>) -> Self {
+        let message = message.into();
+        let (mut code, retryable) = classify_sqlstate(sqlstate);
+        if sqlstate == QUERY_CANCELED && message.contains("statement timeout") {
+            code = ErrorCode::Timeout;
+        }
+        DatabaseError::Backend(BackendError {
+            backend: DatabaseType::PostgreSQL,
+            code,
+            backend_code: BackendCode::Sqlstate(sqlstate.to_string()),
+            retryable,
+            message,
+        })
+    }
+
+    /// A MongoDB error with its server code.
+    pub fn mongo(code: i32, message: impl Into<String>) -> Self {
+        let (error_code, retryable) = classify_mongo_code(code);
+        DatabaseError::Backend(BackendError {
+            backend: DatabaseType::MongoDB,
+            code: error_code,
+            backend_code: BackendCode::MongoCode(code),
+            retryable,
+            message: message.into(),
+        })
+    }
+
+    /// An Elasticsearch error response with its HTTP status.
+    pub fn elasticsearch(status: u16, message: impl Into<String>) -> Self {
+        let code = classify_http_status(status);
+        DatabaseError::Backend(BackendError {
+            backend: DatabaseType::Elasticsearch,
+            code,
+            backend_code: BackendCode::HttpStatus(status),
+            retryable: code.is_retryable(),
+            message: message.into(),
+        })
+    }
+
+    /// A Redis error reply with its prefix.
+    pub fn redis(prefix: &str, message: impl Into<String>) -> Self {
+        let code = classify_redis_code(prefix);
+        DatabaseError::Backend(BackendError {
+            backend: DatabaseType::Redis,
+            code,
+            backend_code: BackendCode::RedisCode(prefix.to_string()),
+            retryable: code.is_retryable(),
+            message: message.into(),
+        })
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            DatabaseError::ConnectionError(_) => ErrorCode::Connection,
+            DatabaseError::ConfigurationError(_) => ErrorCode::Configuration,
+            DatabaseError::QueryError(_) => ErrorCode::QueryFailed,
+            DatabaseError::DatabaseNotFound(_) | DatabaseError::CollectionNotFound(_) => ErrorCode::NotFound,
+            DatabaseError::UnsupportedOperation(_) => ErrorCode::Unsupported,
+            DatabaseError::InvalidOperation
+            | DatabaseError::MissingField(_)
+            | DatabaseError::InvalidQuery(_)
+            | DatabaseError::Builder(_) => ErrorCode::InvalidQuery,
+            DatabaseError::ConversionError(_) => ErrorCode::Conversion,
+            DatabaseError::Timeout(_) => ErrorCode::Timeout,
+            DatabaseError::Cancelled => ErrorCode::Cancelled,
+            DatabaseError::Backend(error) => error.code,
+        }
+    }
+
+    /// Whether the same request may succeed when sent again.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            DatabaseError::Backend(error) => error.retryable,
+            error => error.code().is_retryable(),
+        }
+    }
+
+    /// The backend that reported the error, if it came from one.
+    pub fn backend(&self) -> Option<&DatabaseType> {
+        match self {
+            DatabaseError::Backend(error) => Some(&error.backend),
+            _ => None,
+        }
+    }
+
+    /// The error in the form sent to clients.
+    pub fn to_info(&self) -> ErrorInfo {
+        ErrorInfo {
+            code: self.code(),
+            message: self.to_string(),
+            retryable: self.is_retryable(),
+            backend: self.backend().cloned(),
+            backend_code: match self {
+                DatabaseError::Backend(error) => Some(error.backend_code.clone()),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// A serializable description of a `DatabaseError`, e.g.
+/// `{"code": "constraint_violation", "message": "...", "retryable": false, "backend": "postgresql", "sqlstate": "23505"}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorInfo {
+    pub code: ErrorCode,
+    pub message: String,
+    pub retryable: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<DatabaseType>,
+    #[serde(default, flatten, skip_serializing_if = "Option::is_none")]
+    pub backend_code: Option<BackendCode>,
+}
+
+/// The SQLSTATE of a statement cancelled by a cancel request or by `statement_timeout`.
+const QUERY_CANCELED: &str = "57014";
+
+/// Classifies a PostgreSQL SQLSTATE. Returns the code and whether the query may be retried.
+pub fn classify_sqlstate(sqlstate: &str) -> (ErrorCode, bool) {
+    let code = match sqlstate {
+        // serialization_failure and deadlock_detected succeed when run again
+        "40001" | "40P01" => return (ErrorCode::Conflict, true),
+        // admin_shutdown, crash_shutdown and cannot_connect_now happen during failovers
+        "57P01" | "57P02" | "57P03" => ErrorCode::Unavailable,
+        QUERY_CANCELED => ErrorCode::Cancelled,
+        "42501" => ErrorCode::PermissionDenied,
+        "42P01" | "3D000" | "3F000" => ErrorCode::NotFound,
+        _ => match sqlstate.get(..2) {
+            Some("08") => ErrorCode::Connection,
+            Some("53") => ErrorCode::Unavailable,
+            Some("23") => ErrorCode::ConstraintViolation,
+            Some("28") => ErrorCode::PermissionDenied,
+            Some("0A") => ErrorCode::Unsupported,
+            Some("22") | Some("42") => ErrorCode::InvalidQuery,
+            _ => ErrorCode::QueryFailed,
+        },
+    };
+    (code, code.is_retryable())
 }
 
+/// MongoDB codes of errors that happen while a replica set fails over or a node shuts down. Reads
+/// failing with them are retried by the drivers, see the retryable reads specification. Only
+/// these make an error retryable: the `RetryableWriteError` and `TransientTransactionError`
+/// labels are about writes and transactions, which are never retried here.
+pub const MONGO_RETRYABLE_CODES: [i32; 13] = [6, 7, 89, 91, 134, 189, 262, 9001, 10107, 11600, 11602, 13435, 13436];
+
+/// Classifies a MongoDB server error code. Returns the code and whether the command may be retried.
+pub fn classify_mongo_code(code: i32) -> (ErrorCode, bool) {
+    if MONGO_RETRYABLE_CODES.contains(&code) {
+        return (ErrorCode::Unavailable, true);
+    }
+    let error_code = match code {
+        112 => ErrorCode::Conflict,
+        11000 | 11001 => ErrorCode::ConstraintViolation,
+        13 | 18 => ErrorCode::PermissionDenied,
+        26 => ErrorCode::NotFound,
+        50 => ErrorCode::Timeout,
+        237 | 11601 => ErrorCode::Cancelled,
+        59 | 115 => ErrorCode::Unsupported,
+        2 | 9 | 14 | 40 | 17287 => ErrorCode::InvalidQuery,
+        _ => ErrorCode::QueryFailed,
+    };
+    (error_code, error_code.is_retryable())
+}
+
+/// Classifies the HTTP status of an Elasticsearch error response.
+pub fn classify_http_status(status: u16) -> ErrorCode {
+    match status {
+        429 => ErrorCode::RateLimited,
+        502..=504 => ErrorCode::Unavailable,
+        400 => ErrorCode::InvalidQuery,
+        401 | 403 => ErrorCode::PermissionDenied,
+        404 => ErrorCode::NotFound,
+        408 => ErrorCode::Timeout,
+        409 => ErrorCode::Conflict,
+        _ => ErrorCode::QueryFailed,
+    }
+}
+
+/// Classifies the prefix of a Redis error reply.
+pub fn classify_redis_code(prefix: &str) -> ErrorCode {
+    match prefix {
+        // The server is loading, busy with a script, or in the middle of a failover
+        "LOADING" | "BUSY" | "TRYAGAIN" | "CLUSTERDOWN" | "MASTERDOWN" | "READONLY" => ErrorCode::Unavailable,
+        "NOAUTH" | "NOPERM" | "WRONGPASS" => ErrorCode::PermissionDenied,
+        "NOSCRIPT" => ErrorCode::NotFound,
+        "WRONGTYPE" => ErrorCode::InvalidQuery,
+        _ => ErrorCode::QueryFailed,
+    }
+}
 
 #[derive(Error, Debug)]
 pub enum QueryBuilderError {
@@ -46,6 +304,4 @@ pub enum QueryBuilderError {
     UnsupportedDatabaseType,
     #[error("Invalid query: {0}")]
     InvalidQuery(String),
-    #[error("Database error: {0}")]
-    DatabaseError(String),
-}
\ No newline at end of file
+}
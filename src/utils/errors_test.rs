@@ -0,0 +1,81 @@
+#[cfg(test)]
+mod tests {
+    use crate::db::query_builder::DatabaseType;
+    use crate::utils::errors::{classify_http_status, classify_mongo_code, classify_redis_code, classify_sqlstate, DatabaseError, ErrorCode, QueryBuilderError};
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[test]
+    fn test_classify_sqlstate() {
+        assert_eq!(classify_sqlstate("40001"), (ErrorCode::Conflict, true), "Serialization failures should be retried");
+        assert_eq!(classify_sqlstate("40P01"), (ErrorCode::Conflict, true), "Deadlocks should be retried");
+        assert_eq!(classify_sqlstate("57P01"), (ErrorCode::Unavailable, true), "Shutdowns should be retried");
+        assert_eq!(classify_sqlstate("08006"), (ErrorCode::Connection, true));
+        assert_eq!(classify_sqlstate("23505"), (ErrorCode::ConstraintViolation, false));
+        assert_eq!(classify_sqlstate("42P01"), (ErrorCode::NotFound, false));
+        assert_eq!(classify_sqlstate("42601"), (ErrorCode::InvalidQuery, false));
+        assert_eq!(classify_sqlstate("57014"), (ErrorCode::Cancelled, false));
+        let timed_out = DatabaseError::postgres("57014", "canceling statement due to statement timeout");
+        assert_eq!(timed_out.code(), ErrorCode::Timeout, "A statement_timeout should be reported as a timeout");
+        assert_eq!(DatabaseError::postgres("57014", "canceling statement due to user request").code(), ErrorCode::Cancelled);
+        assert_eq!(classify_sqlstate("XX000"), (ErrorCode::QueryFailed, false));
+    }
+
+    #[test]
+    fn test_classify_mongo_code() {
+        assert_eq!(classify_mongo_code(91), (ErrorCode::Unavailable, true), "ShutdownInProgress should be retried");
+        assert_eq!(classify_mongo_code(10107), (ErrorCode::Unavailable, true), "NotWritablePrimary should be retried");
+        assert_eq!(classify_mongo_code(112), (ErrorCode::Conflict, false), "WriteConflict is not a read retry code");
+        assert_eq!(classify_mongo_code(11000), (ErrorCode::ConstraintViolation, false));
+        assert_eq!(classify_mongo_code(13), (ErrorCode::PermissionDenied, false));
+        assert_eq!(classify_mongo_code(50), (ErrorCode::Timeout, false));
+        assert_eq!(classify_mongo_code(1), (ErrorCode::QueryFailed, false));
+
+        assert!(DatabaseError::mongo(91, "shutdown in progress").is_retryable());
+        assert!(!DatabaseError::mongo(1, "internal error").is_retryable());
+    }
+
+    #[test]
+    fn test_classify_http_status_and_redis_code() {
+        assert_eq!(classify_http_status(429), ErrorCode::RateLimited);
+        assert_eq!(classify_http_status(503), ErrorCode::Unavailable);
+        assert_eq!(classify_http_status(404), ErrorCode::NotFound);
+        assert_eq!(classify_http_status(500), ErrorCode::QueryFailed);
+        assert!(DatabaseError::elasticsearch(429, "too many requests").is_retryable());
+        assert!(!DatabaseError::elasticsearch(400, "bad request").is_retryable());
+
+        assert_eq!(classify_redis_code("LOADING"), ErrorCode::Unavailable);
+        assert_eq!(classify_redis_code("WRONGTYPE"), ErrorCode::InvalidQuery);
+        assert_eq!(classify_redis_code("ERR"), ErrorCode::QueryFailed);
+    }
+
+    #[test]
+    fn test_error_info() {
+        let error = DatabaseError::postgres("23505", "duplicate key value violates unique constraint");
+        assert_eq!(error.backend(), Some(&DatabaseType::PostgreSQL));
+        assert_eq!(error.to_string(), "duplicate key value violates unique constraint");
+        assert_eq!(serde_json::to_value(error.to_info()).unwrap(), json!({
+            "code": "constraint_violation",
+            "message": "duplicate key value violates unique constraint",
+            "retryable": false,
+            "backend": "postgresql",
+            "sqlstate": "23505",
+        }));
+
+        let timeout = serde_json::to_value(DatabaseError::Timeout(Duration::from_secs(1)).to_info()).unwrap();
+        assert_eq!(timeout, json!({"code": "timeout", "message": "Query timed out after 1s", "retryable": false}),
+            "Errors of no backend should have no backend fields");
+    }
+
+    #[test]
+    fn test_builder_errors_convert() {
+        let error: DatabaseError = QueryBuilderError::MissingField("table".to_string()).into();
+        assert_eq!(error.code(), ErrorCode::InvalidQuery);
+        assert_eq!(error.to_string(), "Missing required field: table", "The builder's message should be kept");
+        assert!(DatabaseError::ConnectionError("reset".to_string()).is_retryable());
+        let misconfigured = DatabaseError::ConfigurationError("Invalid TLS configuration".to_string());
+        assert_eq!(misconfigured.code(), ErrorCode::Configuration);
+        assert!(!misconfigured.is_retryable(), "Setup errors should not be retried");
+        assert!(!DatabaseError::QueryError("failed".to_string()).is_retryable());
+    }
+}
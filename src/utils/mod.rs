@@ -1,2 +1,3 @@
-mod datetime;
+pub mod datetime;
 pub mod errors;
+mod errors_test;
@@ -0,0 +1,188 @@
+//! Hot reload of the configuration files. The files are polled for changes; after a change the
+//! layered configuration is loaded and validated again, then the sources are updated in place:
+//! new and changed sources are opened, the pools of changed sources are swapped for the new ones,
+//! and removed sources stop being listed. Replaced and removed pools are closed in the background
+//! once the queries running on them finish.
+//! Clients are sent the new source list. A configuration that fails to load is ignored and the
+//! running configuration is kept, as is a file that is deleted.
+//!
+//! The Redis and Elasticsearch pools used for pub/sub and monitoring are the ones opened at
+//! startup. A configuration that changes or removes their sources is ignored too, with an error
+//! saying the server must be restarted to apply it.
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use futures::future::join_all;
+use log::{error, info, warn};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use crate::config::layered::{candidate_files, CliArgs, LayeredConfig};
+use crate::db::connection_manager::ConnectionStatus;
+use crate::db::db_manager::{build_db_manager, open_pool, source_specs, SourceSpec};
+use crate::ws::protocol::ServerMessage;
+use crate::ws::state::AppState;
+
+pub const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long the replaced pool of a changed source, or the pool of a removed one, may keep serving
+/// in-flight queries before it is closed.
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The source changes between two configurations.
+#[derive(Default)]
+pub struct ReloadPlan {
+    pub added: Vec<SourceSpec>,
+    pub removed: Vec<String>,
+    /// Sources whose type, URL or options changed, in their new form.
+    pub changed: Vec<SourceSpec>,
+}
+
+impl ReloadPlan {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// The sources in `pinned` the plan changes or removes.
+    pub fn restart_required<'a>(&'a self, pinned: &[String]) -> Vec<&'a str> {
+        self.changed.iter().map(|spec| &spec.name).chain(&self.removed)
+            .filter(|name| pinned.contains(name))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+pub fn plan_reload(current: &[SourceSpec], next: &[SourceSpec]) -> ReloadPlan {
+    let mut plan = ReloadPlan::default();
+    for spec in next {
+        match current.iter().find(|current| current.name == spec.name) {
+            None => plan.added.push(spec.clone()),
+            Some(current) if current != spec => plan.changed.push(spec.clone()),
+            Some(_) => {}
+        }
+    }
+    plan.removed = current.iter()
+        .filter(|current| !next.iter().any(|spec| spec.name == current.name))
+        .map(|current| current.name.clone())
+        .collect();
+    plan
+}
+
+/// Applies `plan`, after which the registered sources match `next`. New pools are opened before
+/// the old ones are let go, so queries keep being served during the reload. A changed source that
+/// fails to open keeps its old pool and is flagged as failed, so the supervisor reconnects it with
+/// the new settings. Returns the tasks closing the replaced and removed pools.
+pub async fn apply_reload(state: &AppState, plan: &ReloadPlan, next: &[SourceSpec]) -> Vec<JoinHandle<()>> {
+    let mut closing = Vec::new();
+    for name in &plan.removed {
+        info!("Closing source {}", name);
+        match state.connections.retire_connection(name, DRAIN_TIMEOUT).await {
+            Ok(task) => closing.push(task),
+            Err(e) => warn!("Error while closing {}: {}", name, e),
+        }
+    }
+
+    let opened = join_all(plan.changed.iter().chain(&plan.added).map(|spec| async move {
+        info!("Opening source {}", spec.name);
+        (spec, open_pool(spec).await)
+    })).await;
+    for (spec, result) in opened {
+        let registered_type = state.connections.connection_info(&spec.name).await.map(|info| info.database_type);
+        if registered_type.as_ref().is_some_and(|database_type| *database_type != spec.database_type) {
+            // A source that changed type is a different source under the same name
+            if let Ok(task) = state.connections.retire_connection(&spec.name, DRAIN_TIMEOUT).await {
+                closing.push(task);
+            }
+        }
+
+        let registered = state.connections.connection_info(&spec.name).await.is_some();
+        let outcome = match result {
+            Ok(opened) if registered => state.connections.replace_handle(&spec.name, opened.handle, DRAIN_TIMEOUT).await
+                .map(|task| closing.push(task)),
+            Ok(opened) => state.connections.add_connection(&spec.name, spec.database_type.clone(), opened.handle).await,
+            Err(e) if registered => {
+                warn!("Could not reconnect {} with its new settings, keeping the running pool: {}", spec.name, e);
+                state.connections.set_status(&spec.name, ConnectionStatus::Failed, Some(e.to_string())).await
+            }
+            Err(e) => {
+                warn!("Could not connect to {}: {}", spec.name, e);
+                state.connections.add_failed_connection(&spec.name, spec.database_type.clone(), e.to_string()).await
+            }
+        };
+        if let Err(e) = outcome {
+            warn!("Could not register {}: {}", spec.name, e);
+        }
+    }
+
+    let db_manager = build_db_manager(&state.connections, next).await;
+    *state.db_manager.write().await = db_manager;
+    closing
+}
+
+/// Loads the configuration again and applies it. A configuration that fails to load is logged and ignored.
+pub async fn reload(state: &AppState, cli: &CliArgs) {
+    let env: Vec<(String, String)> = std::env::vars().collect();
+    let config = match LayeredConfig::load(cli, &env) {
+        Ok(layered) => layered.config,
+        Err(e) => {
+            warn!("Ignoring configuration changes, keeping the running configuration: {}", e);
+            return;
+        }
+    };
+
+    let next = source_specs(&config);
+    // Held until the reload is applied, so reloads and reconnects don't interleave
+    let mut specs = state.sources.write().await;
+    let plan = plan_reload(&specs, &next);
+    if plan.is_empty() {
+        return;
+    }
+    let pinned = plan.restart_required(&state.pinned_sources);
+    if !pinned.is_empty() {
+        error!(
+            "Ignoring configuration changes, restart the server to apply them: {} serve pub/sub or monitoring",
+            pinned.join(", ")
+        );
+        return;
+    }
+
+    info!(
+        "Reloading configuration: {} added, {} removed, {} changed",
+        plan.added.len(), plan.removed.len(), plan.changed.len()
+    );
+    // The old pools are closed in the background
+    let _ = apply_reload(state, &plan, &next).await;
+    *specs = next;
+
+    let sources = state.connections.list_connections().await;
+    let _ = state.tx.send(ServerMessage::Sources { sources }.to_text());
+}
+
+fn modified_at(files: &[String]) -> Vec<Option<SystemTime>> {
+    files.iter()
+        .map(|file| std::fs::metadata(Path::new(file)).and_then(|metadata| metadata.modified()).ok())
+        .collect()
+}
+
+/// Polls the configuration files selected by `cli` every `interval` and reloads the configuration
+/// when one of them changes.
+pub fn spawn_config_watcher(state: Arc<AppState>, cli: CliArgs, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let env: Vec<(String, String)> = std::env::vars().collect();
+        let files: Vec<String> = candidate_files(&cli, &env).into_iter().map(|(file, _)| file).collect();
+        let mut last_modified = modified_at(&files);
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            ticker.tick().await;
+            let modified = modified_at(&files);
+            let deleted = modified.iter().zip(&last_modified).any(|(now, before)| now.is_none() && before.is_some());
+            // Editors may briefly remove a file while saving it
+            if modified == last_modified || deleted {
+                continue;
+            }
+            last_modified = modified;
+
+            reload(&state, &cli).await;
+        }
+    })
+}
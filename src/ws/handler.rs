@@ -1,10 +1,25 @@
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::extract::State;
 use axum::response::IntoResponse;
-use futures::{SinkExt, StreamExt};
+use futures::{SinkExt, StreamExt, TryStreamExt};
+use log::warn;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use crate::db::cancel::with_timeout;
+use crate::db::redis_pubsub::{keyspace_pattern, PubSubMessage};
+use crate::db::row_stream::{RowStream, DEFAULT_BATCH_SIZE};
+use crate::utils::errors::DatabaseError;
+use crate::ws::protocol::{ClientMessage, ServerMessage, TailedStream};
+use crate::ws::queries::GENERATED_ID_PREFIX;
 use crate::ws::state::AppState;
 
+/// How many batches of streamed queries may wait for a slow client before reading pauses.
+const STREAM_BUFFER: usize = 8;
+
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     State(state): State<Arc<AppState>>,
@@ -12,9 +27,58 @@ pub async fn ws_handler(
     ws.on_upgrade(|socket| handle_socket(socket, state))
 }
 
+/// The Redis channels and patterns a single client has subscribed to, and the streams it tails.
+#[derive(Default)]
+struct ClientSubscriptions {
+    channels: HashSet<String>,
+    patterns: HashSet<String>,
+    /// Tails by database and stream name.
+    stream_tails: HashMap<(i64, String), JoinHandle<()>>,
+}
+
+impl ClientSubscriptions {
+    fn matches(&self, message: &PubSubMessage) -> bool {
+        match &message.pattern {
+            Some(pattern) => self.patterns.contains(pattern),
+            None => self.channels.contains(&message.channel),
+        }
+    }
+
+    /// Inserts the names into `set` and returns the ones that were not already present.
+    fn add(set: &mut HashSet<String>, names: Vec<String>) -> Vec<String> {
+        names.into_iter().filter(|name| set.insert(name.clone())).collect()
+    }
+
+    /// Removes the names from `set` and returns the ones that were present.
+    fn remove(set: &mut HashSet<String>, names: Vec<String>) -> Vec<String> {
+        names.into_iter().filter(|name| set.remove(name)).collect()
+    }
+
+    fn to_message(&self) -> ServerMessage {
+        let mut channels: Vec<String> = self.channels.iter().cloned().collect();
+        let mut patterns: Vec<String> = self.patterns.iter().cloned().collect();
+        channels.sort();
+        patterns.sort();
+        ServerMessage::RedisSubscriptions { channels, patterns }
+    }
+
+    fn tails_message(&self) -> ServerMessage {
+        let mut streams: Vec<TailedStream> = self.stream_tails.keys()
+            .map(|(db, stream)| TailedStream { db: *db, stream: stream.clone() })
+            .collect();
+        streams.sort();
+        ServerMessage::RedisStreamTails { streams }
+    }
+}
+
 async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
     let (mut sender, mut receiver) = socket.split();
     let mut rx = state.tx.subscribe();
+    let mut pubsub_rx = state.pubsub.as_ref().map(|pubsub| pubsub.events());
+    let (direct_tx, mut direct_rx) = mpsc::unbounded_channel::<String>();
+    let (rows_tx, mut rows_rx) = mpsc::channel::<String>(STREAM_BUFFER);
+    let subscriptions = Arc::new(Mutex::new(ClientSubscriptions::default()));
+    let client = state.queries.next_id();
 
     // Increment the client count
     {
@@ -24,8 +88,23 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
     }
 
     // Task for sending messages to the client
+    let send_subscriptions = Arc::clone(&subscriptions);
     let mut send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
+        loop {
+            let msg = tokio::select! {
+                msg = rx.recv() => match msg {
+                    Ok(msg) => msg,
+                    Err(_) => break,
+                },
+                Some(msg) = direct_rx.recv() => msg,
+                Some(msg) = rows_rx.recv() => msg,
+                message = next_pubsub_message(&mut pubsub_rx) => {
+                    if !send_subscriptions.lock().unwrap().matches(&message) {
+                        continue;
+                    }
+                    ServerMessage::RedisMessage(message).to_text()
+                }
+            };
             if sender.send(Message::Text(msg)).await.is_err() {
                 break;
             }
@@ -34,12 +113,27 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
 
     // Task for receiving messages from the client
     let tx = state.tx.clone();
+    let receive_state = Arc::clone(&state);
+    let receive_subscriptions = Arc::clone(&subscriptions);
     let mut receive_task = tokio::spawn(async move {
         while let Some(msg) = receiver.next().await {
             match msg {
                 Ok(Message::Text(text)) => {
                     println!("Received message: {}", text);
-                    let _ = tx.send(text);
+                    match ClientMessage::parse(&text) {
+                        Ok(Some(request)) => {
+                            let reply = handle_client_message(request, client, &receive_state, &receive_subscriptions, &direct_tx, &rows_tx).await;
+                            if let Some(reply) = reply {
+                                let _ = direct_tx.send(reply.to_text());
+                            }
+                        }
+                        Ok(None) => {
+                            let _ = tx.send(text);
+                        }
+                        Err(e) => {
+                            let _ = direct_tx.send(ServerMessage::error(format!("Invalid request: {}", e)).to_text());
+                        }
+                    }
                 }
                 Ok(Message::Binary(_)) => {
                     println!("Received binary data");
@@ -68,6 +162,19 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
         _ = (&mut receive_task) => send_task.abort(),
     }
 
+    // Cancel the client's queries
+    state.queries.cancel_all(client);
+
+    // Release the client's Redis subscriptions and stop its stream tails
+    let subscriptions = std::mem::take(&mut *subscriptions.lock().unwrap());
+    for tail in subscriptions.stream_tails.values() {
+        tail.abort();
+    }
+    if let Some(pubsub) = state.pubsub.as_ref() {
+        let _ = pubsub.unsubscribe(subscriptions.channels.into_iter().collect());
+        let _ = pubsub.punsubscribe(subscriptions.patterns.into_iter().collect());
+    }
+
     // Decrement the client count
     {
         let mut client_count = state.client_count.lock().unwrap();
@@ -75,3 +182,181 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
         println!("Client disconnected! Total clients: {}", *client_count);
     }
 }
+
+/// Sends the batches of a streamed query, waiting whenever the client's buffer is full so rows
+/// are read no faster than the client receives them. Returns the number of rows sent.
+async fn send_rows(mut rows: RowStream<'_>, id: &Option<String>, rows_tx: &mpsc::Sender<String>) -> Result<usize, DatabaseError> {
+    let mut row_count = 0;
+    while let Some(batch) = rows.try_next().await? {
+        row_count += batch.row_count();
+        let message = ServerMessage::QueryRows { id: id.clone(), batch }.to_text();
+        if rows_tx.send(message).await.is_err() {
+            // The client is gone; dropping the stream stops the query
+            return Err(DatabaseError::Cancelled);
+        }
+    }
+    Ok(row_count)
+}
+
+/// Waits for the next pub/sub message. Never resolves when pub/sub is not configured.
+async fn next_pubsub_message(rx: &mut Option<broadcast::Receiver<PubSubMessage>>) -> PubSubMessage {
+    loop {
+        let Some(receiver) = rx.as_mut() else {
+            return std::future::pending().await;
+        };
+        match receiver.recv().await {
+            Ok(message) => return message,
+            Err(RecvError::Lagged(skipped)) => warn!("Client lagged behind, skipped {} pub/sub messages", skipped),
+            Err(RecvError::Closed) => *rx = None,
+        }
+    }
+}
+
+/// Handles a request of `client`, returning the reply to send, if any.
+/// Queries run in the background and send their result when they complete; streamed queries
+/// send their batches through `rows_tx`, which holds them back while the client is slow.
+async fn handle_client_message(
+    message: ClientMessage,
+    client: u64,
+    state: &Arc<AppState>,
+    subscriptions: &Mutex<ClientSubscriptions>,
+    direct_tx: &mpsc::UnboundedSender<String>,
+    rows_tx: &mpsc::Sender<String>,
+) -> Option<ServerMessage> {
+    let reply = match message {
+        ClientMessage::RedisStreamTail { stream, db, from } => {
+            let Some(redis) = state.redis.as_ref() else {
+                return Some(ServerMessage::error("Redis is not configured"));
+            };
+            let tx = direct_tx.clone();
+            let name = stream.clone();
+            let tail = redis.tail_stream(db, stream.clone(), from, move |entries| {
+                tx.send(ServerMessage::RedisStreamEntries { stream: name.clone(), db, entries }.to_text()).is_ok()
+            });
+            match tail {
+                Ok(tail) => {
+                    let mut subscriptions = subscriptions.lock().unwrap();
+                    if let Some(previous) = subscriptions.stream_tails.insert((db, stream), tail) {
+                        previous.abort();
+                    }
+                    subscriptions.tails_message()
+                }
+                Err(e) => ServerMessage::error(e.to_string()),
+            }
+        }
+        ClientMessage::RedisStreamUntail { stream, db } => {
+            let mut subscriptions = subscriptions.lock().unwrap();
+            if let Some(tail) = subscriptions.stream_tails.remove(&(db, stream)) {
+                tail.abort();
+            }
+            subscriptions.tails_message()
+        }
+        ClientMessage::Query { id, query, timeout_ms, stream, batch_size } => {
+            let (query_id, started) = match id {
+                Some(id) if id.starts_with(GENERATED_ID_PREFIX) => {
+                    let e = DatabaseError::InvalidQuery(format!("Query ids starting with {} are reserved", GENERATED_ID_PREFIX));
+                    return Some(ServerMessage::query_error(Some(id), &e));
+                }
+                Some(id) => (id, None),
+                None => {
+                    let id = state.queries.generated_id();
+                    (id.clone(), Some(ServerMessage::QueryStarted { id }))
+                }
+            };
+            let handle = match state.queries.start(client, &query_id) {
+                Ok(handle) => handle,
+                Err(e) => return Some(ServerMessage::query_error(Some(query_id), &e)),
+            };
+            let state = Arc::clone(state);
+            let tx = direct_tx.clone();
+            let rows_tx = rows_tx.clone();
+            tokio::spawn(async move {
+                let timeout = timeout_ms.map(Duration::from_millis);
+                // A copy sharing the pools, so reloads and reconnects don't wait for the query
+                let db_manager = state.db_manager.read().await.clone();
+                let id = Some(query_id);
+                if stream {
+                    let started = Instant::now();
+                    let rows = db_manager.stream_native(&query, batch_size.unwrap_or(DEFAULT_BATCH_SIZE));
+                    let sent = async { send_rows(rows?, &id, &rows_tx).await };
+                    let reply = match state.queries.run(handle, with_timeout(timeout, sent)).await {
+                        Ok(row_count) => {
+                            let elapsed_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+                            ServerMessage::QueryEnd { id, row_count, elapsed_ms }
+                        }
+                        Err(e) => ServerMessage::query_error(id, &e),
+                    };
+                    // After the batches, so the end cannot overtake them
+                    let _ = rows_tx.send(reply.to_text()).await;
+                    return;
+                }
+                let result = state.queries.run(handle, db_manager.query_native_with_timeout(&query, timeout)).await;
+                let reply = match result {
+                    Ok(result) => ServerMessage::QueryResult { id, result },
+                    Err(e) => ServerMessage::query_error(id, &e),
+                };
+                let _ = tx.send(reply.to_text());
+            });
+            return started;
+        }
+        ClientMessage::CancelQuery { id } => {
+            if state.queries.cancel(client, &id) {
+                return None;
+            }
+            ServerMessage::error(format!("No query with id {} is running", id))
+        }
+        ClientMessage::ListSources => ServerMessage::Sources { sources: state.connections.list_connections().await },
+        message => handle_pubsub_message(message, state, subscriptions).await,
+    };
+    Some(reply)
+}
+
+async fn handle_pubsub_message(
+    message: ClientMessage,
+    state: &AppState,
+    subscriptions: &Mutex<ClientSubscriptions>,
+) -> ServerMessage {
+    let Some(pubsub) = state.pubsub.as_ref() else {
+        return ServerMessage::error("Redis pub/sub is not configured");
+    };
+
+    let result = match message {
+        ClientMessage::RedisSubscribe { channels } => {
+            let added = ClientSubscriptions::add(&mut subscriptions.lock().unwrap().channels, channels);
+            pubsub.subscribe(added)
+        }
+        ClientMessage::RedisPsubscribe { patterns } => {
+            let added = ClientSubscriptions::add(&mut subscriptions.lock().unwrap().patterns, patterns);
+            pubsub.psubscribe(added)
+        }
+        ClientMessage::RedisUnsubscribe { channels } => {
+            let removed = ClientSubscriptions::remove(&mut subscriptions.lock().unwrap().channels, channels);
+            pubsub.unsubscribe(removed)
+        }
+        ClientMessage::RedisPunsubscribe { patterns } => {
+            let removed = ClientSubscriptions::remove(&mut subscriptions.lock().unwrap().patterns, patterns);
+            pubsub.punsubscribe(removed)
+        }
+        ClientMessage::RedisKeyspace { db, pattern, enable_notifications } => {
+            if enable_notifications {
+                let Some(redis) = state.redis.as_ref() else {
+                    return ServerMessage::error("Redis is not configured");
+                };
+                if let Err(e) = redis.enable_keyspace_notifications("").await {
+                    return ServerMessage::error(e.to_string());
+                }
+            }
+            let pattern = keyspace_pattern(db, &pattern);
+            let added = ClientSubscriptions::add(&mut subscriptions.lock().unwrap().patterns, vec![pattern]);
+            pubsub.psubscribe(added)
+        }
+        ClientMessage::RedisStreamTail { .. } | ClientMessage::RedisStreamUntail { .. } | ClientMessage::Query { .. } | ClientMessage::CancelQuery { .. } | ClientMessage::ListSources => {
+            return ServerMessage::error("Not a pub/sub request");
+        }
+    };
+
+    match result {
+        Ok(()) => subscriptions.lock().unwrap().to_message(),
+        Err(e) => ServerMessage::error(e.to_string()),
+    }
+}
@@ -1,21 +1,26 @@
-use axum::{routing::get, Router};
-use futures::{SinkExt, StreamExt};
-use std::net::SocketAddr;
-use std::sync::Arc;
-use tokio::sync::broadcast;
-use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
-
-use crate::{ws_handler, AppState};
-
 #[cfg(test)]
 mod test {
-    use super::*;
+    use axum::{routing::get, Router};
+    use futures::{SinkExt, StreamExt};
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use tokio::sync::broadcast;
+    use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
+    use crate::db::query_builder::DatabaseType;
+    use crate::ws::handler::ws_handler;
 
     mod test_utils {
         use super::*;
         use tokio::net::TcpStream;
         use tokio_tungstenite::MaybeTlsStream;
         use tokio_tungstenite::WebSocketStream;
+        use crate::db::connection_manager::ConnectionManager;
+        use crate::db::native_query::NativeQuery;
+        use crate::db::query_builder::{DatabaseManager, DatabaseType};
+        use crate::db::DatabasePool;
+        use crate::utils::errors::DatabaseError;
+        use crate::ws::queries::RunningQueries;
+        use std::collections::HashMap;
         use crate::ws::state::AppState;
 
         pub struct TestApp {
@@ -27,6 +32,30 @@ mod test {
             pub fn client_count(&self) -> usize {
                 *self.state.client_count.lock().unwrap()
             }
+
+            pub async fn add_pool(&self, db_type: DatabaseType, pool: Box<dyn DatabasePool>) {
+                self.state.db_manager.write().await.add_pool(db_type, pool);
+            }
+
+            /// Whether the pools could be rebuilt within `timeout`, as a reload would.
+            pub async fn db_manager_writable_within(&self, timeout: std::time::Duration) -> bool {
+                tokio::time::timeout(timeout, self.state.db_manager.write()).await.is_ok()
+            }
+        }
+
+        /// A pool whose native queries take half a second.
+        pub struct SlowPool;
+
+        #[async_trait::async_trait]
+        impl DatabasePool for SlowPool {
+            async fn execute(&self, _query: &str, _params: Vec<serde_json::Value>) -> Result<Vec<HashMap<String, serde_json::Value>>, DatabaseError> {
+                Ok(Vec::new())
+            }
+
+            async fn execute_native(&self, _query: &NativeQuery) -> Result<Vec<HashMap<String, serde_json::Value>>, DatabaseError> {
+                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                Ok(vec![[("one".to_string(), serde_json::json!(1))].into_iter().collect()])
+            }
         }
 
         pub async fn spawn_app() -> TestApp {
@@ -34,6 +63,14 @@ mod test {
             let state = Arc::new(AppState {
                 tx,
                 client_count: std::sync::Mutex::new(0),
+                db_manager: tokio::sync::RwLock::new(DatabaseManager::new()),
+                connections: ConnectionManager::new(),
+                sources: tokio::sync::RwLock::new(Vec::new()),
+                redis: None,
+                pubsub: None,
+                elasticsearch: None,
+                pinned_sources: Vec::new(),
+                queries: RunningQueries::new(),
             });
 
             let app = Router::new()
@@ -90,7 +127,7 @@ mod test {
         }
     }
 
-    use test_utils::{spawn_app, TestClient};
+    use test_utils::{spawn_app, SlowPool, TestClient};
 
     #[tokio::test]
     async fn test_websocket_connection_successful() {
@@ -126,6 +163,24 @@ mod test {
         );
     }
 
+    #[tokio::test]
+    async fn test_invalid_requests_are_not_broadcast() {
+        let app = spawn_app().await;
+        let mut client1 = TestClient::new(&app.addr).await;
+        let mut client2 = TestClient::new(&app.addr).await;
+
+        client1.send(r#"{"type": "query", "qeury": {"language": "sql", "sql": "SELECT secret FROM users"}}"#).await;
+        let reply: serde_json::Value = serde_json::from_str(&client1.receive().await).unwrap();
+        assert_eq!(reply["type"], "error");
+        assert!(reply["message"].as_str().unwrap().contains("missing field `query`"), "Unexpected reply {}", reply);
+
+        let received = tokio::time::timeout(std::time::Duration::from_millis(200), client2.receive()).await;
+        assert!(received.is_err(), "Other clients should not receive the invalid request");
+
+        client1.send(r#"{"type": "chat", "text": "hi"}"#).await;
+        assert_eq!(client2.receive().await, r#"{"type": "chat", "text": "hi"}"#, "Unknown types should still be broadcast");
+    }
+
     #[tokio::test]
     async fn test_client_count_tracking() {
         let app = spawn_app().await;
@@ -203,4 +258,87 @@ mod test {
             "Server should handle valid messages after receiving malformed ones"
         );
     }
+
+    #[tokio::test]
+    async fn test_redis_subscribe_without_pubsub_returns_error() {
+        let app = spawn_app().await;
+        let mut client = TestClient::new(&app.addr).await;
+
+        client.send(r#"{"type":"redis_subscribe","channels":["events"]}"#).await;
+        let received: serde_json::Value = serde_json::from_str(&client.receive().await).unwrap();
+
+        assert_eq!(received["type"], "error", "Subscribing should fail when pub/sub is not configured");
+    }
+
+    #[tokio::test]
+    async fn test_generated_query_ids_are_reserved() {
+        let app = spawn_app().await;
+        let mut client = TestClient::new(&app.addr).await;
+
+        client.send(r#"{"type":"query","query":{"language":"sql","sql":"SELECT 1"}}"#).await;
+        let started: serde_json::Value = serde_json::from_str(&client.receive().await).unwrap();
+        assert_eq!(started["type"], "query_started");
+        let generated = started["id"].as_str().unwrap().to_string();
+        assert!(generated.starts_with("q-"), "{}", generated);
+        client.receive().await;
+
+        client.send(&format!(r#"{{"type":"query","id":"{}","query":{{"language":"sql","sql":"SELECT 1"}}}}"#, generated)).await;
+        let received: serde_json::Value = serde_json::from_str(&client.receive().await).unwrap();
+        assert_eq!(received["type"], "query_error", "Clients should not be able to take generated ids");
+        assert!(received["message"].as_str().unwrap().contains("reserved"));
+    }
+
+    #[tokio::test]
+    async fn test_native_query_without_pool_returns_query_error() {
+        let app = spawn_app().await;
+        let mut client = TestClient::new(&app.addr).await;
+
+        client.send(r#"{"type":"query","id":"q1","query":{"language":"sql","sql":"SELECT 1"}}"#).await;
+        let received: serde_json::Value = serde_json::from_str(&client.receive().await).unwrap();
+
+        assert_eq!(received["type"], "query_error", "Queries should fail when no pool is configured");
+        assert_eq!(received["id"], "q1", "The error should carry the query id");
+        assert_eq!(received["code"], "unsupported", "The error should carry its code");
+        assert_eq!(received["retryable"], false);
+        assert!(received["message"].as_str().unwrap().contains("is configured"), "The error should carry its message");
+    }
+
+    #[tokio::test]
+    async fn test_running_queries_do_not_hold_up_pool_rebuilds() {
+        let app = spawn_app().await;
+        app.add_pool(DatabaseType::PostgreSQL, Box::new(SlowPool)).await;
+        let mut client = TestClient::new(&app.addr).await;
+
+        client.send(r#"{"type":"query","id":"q1","query":{"language":"sql","sql":"SELECT 1"}}"#).await;
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(app.db_manager_writable_within(std::time::Duration::from_millis(100)).await, "The query should not lock the pools");
+
+        let received: serde_json::Value = serde_json::from_str(&client.receive().await).unwrap();
+        assert_eq!(received["type"], "query_result", "The query should finish on the pool it started on");
+        assert_eq!(received["rows"], serde_json::json!([[1]]));
+    }
+
+    #[tokio::test]
+    async fn test_streamed_query_without_pool_returns_query_error() {
+        let app = spawn_app().await;
+        let mut client = TestClient::new(&app.addr).await;
+
+        client.send(r#"{"type":"query","id":"q2","stream":true,"batch_size":10,"query":{"language":"redis","command":"SCAN 0"}}"#).await;
+        let received: serde_json::Value = serde_json::from_str(&client.receive().await).unwrap();
+
+        assert_eq!(received["type"], "query_error", "Streamed queries should fail when no pool is configured");
+        assert_eq!(received["id"], "q2", "The error should carry the query id");
+    }
+
+    #[tokio::test]
+    async fn test_list_sources_without_configuration() {
+        let app = spawn_app().await;
+        let mut client = TestClient::new(&app.addr).await;
+
+        client.send(r#"{"type":"list_sources"}"#).await;
+        let received: serde_json::Value = serde_json::from_str(&client.receive().await).unwrap();
+
+        assert_eq!(received["type"], "sources");
+        assert_eq!(received["sources"], serde_json::json!([]), "No sources should be listed without a configuration");
+    }
 }
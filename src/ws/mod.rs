@@ -1,3 +1,11 @@
+pub mod config_watcher;
+mod config_watcher_test;
 pub mod handler;
 mod handler_test;
-pub(crate) mod state;
+pub mod monitor;
+pub mod protocol;
+pub mod queries;
+mod queries_test;
+pub mod state;
+pub mod supervisor;
+mod supervisor_test;
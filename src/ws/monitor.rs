@@ -0,0 +1,69 @@
+//! Periodic monitoring broadcast. Every interval the server metrics of the Redis and
+//! Elasticsearch sources are collected and sent to all connected clients as a `monitoring` message.
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use log::warn;
+use serde::Serialize;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use crate::db::elasticsearch_cluster::ElasticsearchClusterMetrics;
+use crate::db::redis_custom::RedisServerMetrics;
+use crate::db::retry::{RetryStats, RETRY_METRICS};
+use crate::ws::protocol::ServerMessage;
+use crate::ws::state::AppState;
+
+pub const MONITOR_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The metrics of every monitored source. Sources that are not configured are omitted and
+/// sources whose metrics could not be collected are listed in `errors`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MonitoringSnapshot {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redis: Option<RedisServerMetrics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elasticsearch: Option<ElasticsearchClusterMetrics>,
+    /// Retries of reads after transient failures, on every source.
+    pub retries: RetryStats,
+    pub errors: BTreeMap<String, String>,
+}
+
+pub async fn collect_snapshot(state: &AppState) -> MonitoringSnapshot {
+    let mut snapshot = MonitoringSnapshot { retries: RETRY_METRICS.stats(), ..Default::default() };
+
+    if let Some(redis) = state.redis.as_ref() {
+        match redis.server_metrics().await {
+            Ok(metrics) => snapshot.redis = Some(metrics),
+            Err(e) => { snapshot.errors.insert("redis".to_string(), e.to_string()); }
+        }
+    }
+
+    if let Some(elasticsearch) = state.elasticsearch.as_ref() {
+        match elasticsearch.cluster_metrics().await {
+            Ok(metrics) => snapshot.elasticsearch = Some(metrics),
+            Err(e) => { snapshot.errors.insert("elasticsearch".to_string(), e.to_string()); }
+        }
+    }
+
+    snapshot
+}
+
+/// Broadcasts a snapshot every `interval` while at least one client is connected.
+pub fn spawn_monitor(state: Arc<AppState>, interval: Duration) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            ticker.tick().await;
+            if state.tx.receiver_count() == 0 {
+                continue;
+            }
+
+            let snapshot = collect_snapshot(&state).await;
+            for (source, error) in &snapshot.errors {
+                warn!("Could not collect {} metrics: {}", source, error);
+            }
+            let _ = state.tx.send(ServerMessage::Monitoring(Box::new(snapshot)).to_text());
+        }
+    })
+}
@@ -0,0 +1,151 @@
+//! JSON messages exchanged with WebSocket clients.
+//! Every message carries a `type` tag. Text frames without the `type` of a `ClientMessage`
+//! are broadcast to all connected clients unchanged; a request that does not parse is answered
+//! with an `Error` to its sender only.
+use serde::{Deserialize, Serialize};
+use crate::db::connection_manager::ConnectionInfo;
+use crate::db::native_query::NativeQuery;
+use crate::db::result_set::ResultSet;
+use crate::db::redis_pubsub::PubSubMessage;
+use crate::db::redis_streams::StreamEntry;
+use crate::ws::monitor::MonitoringSnapshot;
+use crate::utils::errors::{DatabaseError, ErrorInfo};
+
+/// Requests sent by a client.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    RedisSubscribe { channels: Vec<String> },
+    RedisPsubscribe { patterns: Vec<String> },
+    RedisUnsubscribe { channels: Vec<String> },
+    RedisPunsubscribe { patterns: Vec<String> },
+    /// Streams changes (set, expire, del, ...) of keys matching `pattern` in database `db`.
+    /// `enable_notifications` turns on `notify-keyspace-events` on the server first.
+    RedisKeyspace {
+        #[serde(default)]
+        db: i64,
+        pattern: String,
+        #[serde(default)]
+        enable_notifications: bool,
+    },
+    /// Streams new entries of `stream` as they are added, starting after `from` when given.
+    RedisStreamTail {
+        stream: String,
+        #[serde(default)]
+        db: i64,
+        #[serde(default)]
+        from: Option<String>,
+    },
+    RedisStreamUntail {
+        stream: String,
+        #[serde(default)]
+        db: i64,
+    },
+    /// Runs a native query. `id` is echoed in the reply so clients can match results to queries,
+    /// and names the query in `CancelQuery`; queries sent without one get an id in `QueryStarted`.
+    /// Ids starting with `q-` are reserved for those. A query still running after `timeout_ms` is cancelled on the server.
+    /// With `stream`, rows are sent in `QueryRows` batches of at most `batch_size` rows as they
+    /// are read, followed by `QueryEnd`, instead of in a single `QueryResult`.
+    Query {
+        #[serde(default)]
+        id: Option<String>,
+        query: NativeQuery,
+        #[serde(default)]
+        timeout_ms: Option<u64>,
+        #[serde(default)]
+        stream: bool,
+        #[serde(default)]
+        batch_size: Option<usize>,
+    },
+    /// Cancels a running query of this client. The query replies with a `QueryError`.
+    CancelQuery { id: String },
+    /// Lists the configured sources and their connection status.
+    ListSources,
+}
+
+/// The `type` of every `ClientMessage`.
+pub const CLIENT_MESSAGE_TYPES: [&str; 10] = [
+    "redis_subscribe", "redis_psubscribe", "redis_unsubscribe", "redis_punsubscribe", "redis_keyspace",
+    "redis_stream_tail", "redis_stream_untail", "query", "cancel_query", "list_sources",
+];
+
+/// The `type` tag of a frame.
+#[derive(Deserialize)]
+struct TypeTag {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+impl ClientMessage {
+    /// Parses a text frame. Frames without the `type` of a request are `Ok(None)`.
+    pub fn parse(text: &str) -> Result<Option<Self>, serde_json::Error> {
+        let is_request = serde_json::from_str::<TypeTag>(text)
+            .is_ok_and(|tag| CLIENT_MESSAGE_TYPES.contains(&tag.kind.as_str()));
+        if !is_request {
+            return Ok(None);
+        }
+        serde_json::from_str(text).map(Some)
+    }
+}
+
+/// A stream tailed by a client. Streams of the same name in different databases are tailed apart.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct TailedStream {
+    pub db: i64,
+    pub stream: String,
+}
+
+/// Messages sent to a client.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    RedisMessage(PubSubMessage),
+    /// The client's channel and pattern subscriptions after a subscription change.
+    RedisSubscriptions { channels: Vec<String>, patterns: Vec<String> },
+    RedisStreamEntries { stream: String, db: i64, entries: Vec<StreamEntry> },
+    /// The streams the client is tailing after a tail change.
+    RedisStreamTails { streams: Vec<TailedStream> },
+    /// The id given to a query sent without one.
+    QueryStarted { id: String },
+    /// The result of a query, with its `columns` next to the `id`.
+    QueryResult {
+        id: Option<String>,
+        #[serde(flatten)]
+        result: ResultSet,
+    },
+    /// A batch of rows of a streamed query, with the columns of the result.
+    QueryRows {
+        id: Option<String>,
+        #[serde(flatten)]
+        batch: ResultSet,
+    },
+    /// The end of a streamed query, after its last batch.
+    QueryEnd { id: Option<String>, row_count: usize, elapsed_ms: u64 },
+    /// A failed query, with the error's `code`, `message`, whether it is `retryable` and the
+    /// backend's own code when a backend reported it.
+    QueryError {
+        id: Option<String>,
+        #[serde(flatten)]
+        error: ErrorInfo,
+    },
+    Sources { sources: Vec<ConnectionInfo> },
+    /// A source whose status changed, e.g. after a failed health check or a reconnect.
+    SourceStatus { source: ConnectionInfo },
+    /// Server metrics, broadcast periodically to all clients.
+    Monitoring(Box<MonitoringSnapshot>),
+    Error { message: String },
+}
+
+impl ServerMessage {
+    pub fn error(message: impl Into<String>) -> Self {
+        ServerMessage::Error { message: message.into() }
+    }
+
+    pub fn query_error(id: Option<String>, error: &DatabaseError) -> Self {
+        ServerMessage::QueryError { id, error: error.to_info() }
+    }
+
+    pub fn to_text(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|e| format!(r#"{{"type":"error","message":"{}"}}"#, e))
+    }
+}
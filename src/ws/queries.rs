@@ -0,0 +1,107 @@
+//! Queries running on behalf of clients, tracked by client and query id so a client can cancel
+//! its own queries. Cancelling drops the query future, which stops its work on the server (see
+//! `db::cancel`).
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+use crate::utils::errors::DatabaseError;
+
+type QueryKey = (u64, String);
+
+/// Prefix of the ids given to queries sent without one, e.g. `q-7`. Clients cannot use it, so
+/// generated ids never collide with their own.
+pub const GENERATED_ID_PREFIX: &str = "q-";
+
+/// A running query: a token telling it apart from later queries with the same id, and the
+/// sender that cancels it.
+type Entry = (u64, oneshot::Sender<()>);
+
+#[derive(Default)]
+pub struct RunningQueries {
+    next_id: AtomicU64,
+    queries: Mutex<HashMap<QueryKey, Entry>>,
+}
+
+impl RunningQueries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A new id, used for clients and for queries sent without one.
+    pub fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// An id for a query sent without one.
+    pub fn generated_id(&self) -> String {
+        format!("{}{}", GENERATED_ID_PREFIX, self.next_id())
+    }
+
+    /// Registers a query of a client so it can be cancelled, before it is run with `run`.
+    /// Fails if the client is already running a query with the same id.
+    pub fn start(&self, client: u64, id: &str) -> Result<QueryHandle, DatabaseError> {
+        let key = (client, id.to_string());
+        let mut queries = self.queries.lock().unwrap();
+        if queries.contains_key(&key) {
+            return Err(DatabaseError::InvalidQuery(format!("A query with id {} is already running", id)));
+        }
+        let token = self.next_id();
+        let (cancel_tx, cancelled) = oneshot::channel();
+        queries.insert(key.clone(), (token, cancel_tx));
+        Ok(QueryHandle { key, token, cancelled })
+    }
+
+    /// Runs a started query until it completes or is cancelled, dropping the query future
+    /// on cancel.
+    pub async fn run<T>(&self, handle: QueryHandle, query: impl Future<Output = Result<T, DatabaseError>>) -> Result<T, DatabaseError> {
+        let QueryHandle { key, token, cancelled } = handle;
+        let _registration = Registration { queries: self, key, token };
+
+        // The sender is also dropped when the client disconnects
+        tokio::select! {
+            result = query => result,
+            _ = cancelled => Err(DatabaseError::Cancelled),
+        }
+    }
+
+    /// Cancels a query of a client. Returns whether it was running.
+    pub fn cancel(&self, client: u64, id: &str) -> bool {
+        match self.queries.lock().unwrap().remove(&(client, id.to_string())) {
+            Some((_, cancel)) => {
+                let _ = cancel.send(());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancels every query of a client, e.g. when it disconnects.
+    pub fn cancel_all(&self, client: u64) {
+        self.queries.lock().unwrap().retain(|(owner, _), _| *owner != client);
+    }
+}
+
+/// A registered query, see `RunningQueries::start`.
+pub struct QueryHandle {
+    key: QueryKey,
+    token: u64,
+    cancelled: oneshot::Receiver<()>,
+}
+
+/// Removes a query from `RunningQueries` when it ends, however it ends.
+struct Registration<'a> {
+    queries: &'a RunningQueries,
+    key: QueryKey,
+    token: u64,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        let mut queries = self.queries.queries.lock().unwrap();
+        if queries.get(&self.key).is_some_and(|(token, _)| *token == self.token) {
+            queries.remove(&self.key);
+        }
+    }
+}
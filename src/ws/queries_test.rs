@@ -0,0 +1,51 @@
+#[cfg(test)]
+mod tests {
+    use crate::utils::errors::DatabaseError;
+    use crate::ws::queries::RunningQueries;
+    use std::time::Duration;
+
+    async fn slow_query() -> Result<&'static str, DatabaseError> {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        Ok("done")
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_queries_stop() {
+        let queries = RunningQueries::new();
+        let handle = queries.start(1, "q1").unwrap();
+        assert!(queries.cancel(1, "q1"));
+        let result = tokio::time::timeout(Duration::from_secs(1), queries.run(handle, slow_query())).await
+            .expect("A cancelled query should stop right away");
+        assert!(matches!(result, Err(DatabaseError::Cancelled)));
+        assert!(!queries.cancel(1, "q1"), "A cancelled query is no longer running");
+
+        let stale = queries.start(1, "q1").unwrap();
+        assert!(queries.cancel(1, "q1"));
+        let _reused = queries.start(1, "q1").expect("The id of a cancelled query can be reused");
+        assert!(matches!(queries.run(stale, slow_query()).await, Err(DatabaseError::Cancelled)));
+        assert!(queries.cancel(1, "q1"), "The end of the cancelled query must not unregister the new one");
+
+        let handle = queries.start(1, "q2").unwrap();
+        assert_eq!(queries.run(handle, async { Ok("done") }).await.unwrap(), "done");
+        assert!(!queries.cancel(1, "q2"), "A finished query is no longer running");
+    }
+
+    #[tokio::test]
+    async fn test_query_ids_are_per_client() {
+        let queries = RunningQueries::new();
+        let _first = queries.start(1, "q1").unwrap();
+        assert!(matches!(queries.start(1, "q1"), Err(DatabaseError::InvalidQuery(_))));
+        let other = queries.start(2, "q1").expect("Another client may use the same id");
+        assert!(!queries.cancel(2, "q2"));
+
+        queries.cancel_all(1);
+        assert!(!queries.cancel(1, "q1"));
+        let handle = queries.start(1, "q1").expect("The id should be free again");
+        assert!(queries.cancel(2, "q1"));
+        assert!(matches!(queries.run(other, slow_query()).await, Err(DatabaseError::Cancelled)));
+        assert!(queries.cancel(1, "q1"), "Cancelling another client's query must not touch this one");
+        drop(handle);
+        assert_ne!(queries.next_id(), queries.next_id());
+        assert!(queries.generated_id().starts_with("q-"), "Generated ids should not collide with client ids");
+    }
+}
@@ -1,50 +1,85 @@
 use std::sync::{Arc, Mutex};
-use tokio::sync::broadcast;
-use crate::db::db_manager::{DatabaseManager, initialize_db_manager};
+use tokio::sync::{broadcast, RwLock};
+use crate::config::config::Config;
+use crate::db::connection_manager::ConnectionManager;
+use crate::db::db_manager::{initialize_sources, SourceSpec};
+use crate::db::elasticsearch_custom::ElasticsearchPool;
+use crate::db::query_builder::DatabaseManager;
+use crate::db::redis_custom::RedisPool;
+use crate::db::redis_pubsub::RedisSubscriber;
+use crate::ws::queries::RunningQueries;
 
 /// The `AppState` struct holds the application state.
 /// It contains a broadcast channel sender, a mutex-wrapped client count, and a `DatabaseManager`.
 /// The `tx` field is a broadcast channel sender that is used to send messages to all connected clients.
 /// The `client_count` field is a mutex-wrapped integer that keeps track of the number of connected clients.
 /// The `db_manager` field is a `DatabaseManager` that is used to interact with the database.
+/// It is swapped when the configuration is reloaded; queries run on a snapshot of it, and the queries still running on a replaced pool are drained through its `TrackedConnection`.
+/// The `connections` field lists every configured source by name, with its connection status.
+/// The `sources` field holds the configuration of those sources, used to reopen them; it changes when the configuration is reloaded.
+/// The `redis` and `pubsub` fields give access to Redis introspection and Pub/Sub; they are `None` when Redis is unavailable.
+/// The `elasticsearch` field gives access to cluster monitoring; it is `None` when Elasticsearch is unavailable.
+/// The `pinned_sources` field names the sources whose pools back `redis`, `pubsub` and `elasticsearch`; changing or removing them takes a restart.
+/// The `queries` field tracks the queries clients are running, so they can be cancelled.
 /// The `AppState` struct is used to share state between different parts of the application.
 /// Using `Arc<AppState>` allows multiple parts of the application to have read-only access to the state.
 /// Usage:
 /// ```rust
-/// use std::sync::Arc;
-/// use tokio::sync::broadcast;
-/// use tokio::sync::Mutex;
-/// use crate::db::db_manager::DatabaseManager;
-/// use crate::ws::state::AppState;
+/// use std::sync::{Arc, Mutex};
+/// use tokio::sync::{broadcast, RwLock};
+/// use source_watch::db::connection_manager::ConnectionManager;
+/// use source_watch::db::query_builder::DatabaseManager;
+/// use source_watch::ws::queries::RunningQueries;
+/// use source_watch::ws::state::AppState;
 ///
 /// let (tx, _) = broadcast::channel(100);
-/// let db_manager = DatabaseManager::new();
-/// let client_count = Mutex::new(0);
-///
 /// let app_state = Arc::new(AppState {
-///    tx,
-///   client_count,
-///  db_manager,
+///     tx,
+///     client_count: Mutex::new(0),
+///     db_manager: RwLock::new(DatabaseManager::new()),
+///     connections: ConnectionManager::new(),
+///     sources: RwLock::new(Vec::new()),
+///     redis: None,
+///     pubsub: None,
+///     elasticsearch: None,
+///     pinned_sources: Vec::new(),
+///     queries: RunningQueries::new(),
 /// });
 ///
-/// assert_eq!(app_state.client_count.lock().unwrap(), 0);
+/// assert_eq!(*app_state.client_count.lock().unwrap(), 0);
 /// ```
 pub struct AppState {
     pub tx: broadcast::Sender<String>,
     pub client_count: Mutex<usize>,
-    pub db_manager: DatabaseManager,
+    pub db_manager: RwLock<DatabaseManager>,
+    pub connections: ConnectionManager,
+    pub sources: RwLock<Vec<SourceSpec>>,
+    pub redis: Option<Arc<RedisPool>>,
+    pub pubsub: Option<RedisSubscriber>,
+    pub elasticsearch: Option<Arc<ElasticsearchPool>>,
+    pub pinned_sources: Vec<String>,
+    pub queries: RunningQueries,
 }
 
 /// The `init_app_state` function initializes the application state.
-/// It creates a broadcast channel sender, initializes the database manager, and returns an `Arc<AppState>`.
+/// It creates a broadcast channel sender, opens the sources in `config`, and returns an `Arc<AppState>`.
+/// Sources that fail to connect are listed as failed instead of stopping startup.
 /// The `init_app_state` function is used to set up the application state before starting the server.
-pub async fn init_app_state() -> Arc<AppState> {
+pub async fn init_app_state(config: &Config) -> Arc<AppState> {
     let (tx, _) = broadcast::channel(100);
-    let db_manager = initialize_db_manager().await.expect("Failed to initialize database manager");
+    let sources = initialize_sources(config).await;
+    let pubsub = sources.redis.as_ref().map(|redis| redis.subscriber());
 
     Arc::new(AppState {
         tx,
         client_count: Mutex::new(0),
-        db_manager,
+        db_manager: RwLock::new(sources.db_manager),
+        connections: sources.connections,
+        sources: RwLock::new(sources.specs),
+        redis: sources.redis,
+        pubsub,
+        elasticsearch: sources.elasticsearch,
+        pinned_sources: sources.pinned,
+        queries: RunningQueries::new(),
     })
-}
\ No newline at end of file
+}
@@ -0,0 +1,150 @@
+//! Background supervisor of the registered sources. Each source is health checked every
+//! `HEALTH_CHECK_INTERVAL`; failed sources are reopened with exponential backoff. Status changes
+//! are broadcast to all clients as `source_status` messages.
+//!
+//! Reopened Redis and Elasticsearch sources serve queries; pub/sub and monitoring keep using the
+//! pools opened at startup, which connect to the same servers since their settings cannot be
+//! reloaded.
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use futures::future::join_all;
+use log::{info, warn};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior};
+use crate::db::connection_manager::ConnectionStatus;
+use crate::db::db_manager::{build_db_manager, open_pool};
+use crate::db::health::{backoff_delay, check, status_for, HEALTH_CHECK_INTERVAL, HEALTH_CHECK_TIMEOUT};
+use crate::utils::errors::DatabaseError;
+use crate::ws::config_watcher::DRAIN_TIMEOUT;
+use crate::ws::protocol::ServerMessage;
+use crate::ws::state::AppState;
+
+/// How often the supervisor looks for sources that are due for a check or a reconnect.
+const SUPERVISOR_TICK: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+pub(crate) struct Schedule {
+    /// `None` until the first check, which runs right away.
+    pub(crate) next_check: Option<Instant>,
+    pub(crate) failed_attempts: u32,
+    /// A reconnect running in the background, so a source that hangs while opening does not
+    /// hold up the checks of the others.
+    pub(crate) reconnect: Option<JoinHandle<Result<(), DatabaseError>>>,
+}
+
+async fn is_failed(state: &AppState, name: &str) -> bool {
+    state.connections.connection_info(name).await.is_some_and(|info| info.status == ConnectionStatus::Failed)
+}
+
+/// Reopens a failed source and swaps the new pool in. The configured sources are not locked
+/// while the source opens, so a reload can go ahead meanwhile; opening gives up after the
+/// source's `connect_timeout`, or `HEALTH_CHECK_TIMEOUT` without one.
+async fn reconnect(state: &AppState, name: &str) -> Result<(), DatabaseError> {
+    let spec = state.sources.read().await.iter().find(|spec| spec.name == name).cloned()
+        .ok_or_else(|| DatabaseError::DatabaseNotFound(format!("No configured source named {}", name)))?;
+    // A reload may have reopened the source while we waited for the lock
+    if !is_failed(state, name).await {
+        return Ok(());
+    }
+
+    let timeout = spec.options.connect_timeout().unwrap_or(HEALTH_CHECK_TIMEOUT);
+    let opened = tokio::time::timeout(timeout, open_pool(&spec)).await
+        .map_err(|_| DatabaseError::Timeout(timeout))??;
+
+    let specs = state.sources.read().await;
+    // Or changed or reopened it while it was opening
+    if !specs.contains(&spec) || !is_failed(state, name).await {
+        let _ = opened.handle.disconnect().await;
+        return Ok(());
+    }
+    state.connections.replace_handle(name, opened.handle, DRAIN_TIMEOUT).await?;
+    let db_manager = build_db_manager(&state.connections, &specs).await;
+    *state.db_manager.write().await = db_manager;
+    info!("Reconnected to {}", name);
+    Ok(())
+}
+
+/// Checks a source. Returns the check latency.
+async fn check_source(state: &AppState, name: &str) -> Result<Duration, DatabaseError> {
+    let handle = state.connections.get_connection(name).await
+        .ok_or_else(|| DatabaseError::DatabaseNotFound(format!("No connection named {}", name)))?;
+    check(handle.as_ref()).await
+}
+
+/// Checks every due source once, recording the results and broadcasting status changes.
+/// Failed sources are reconnected in the background and checked on the first tick after their
+/// reconnect finishes.
+pub(crate) async fn supervise_due(state: &Arc<AppState>, schedules: &mut HashMap<String, Schedule>) {
+    let now = Instant::now();
+    let sources = state.connections.list_connections().await;
+    schedules.retain(|name, schedule| {
+        let kept = sources.iter().any(|source| source.name == *name);
+        if let (false, Some(task)) = (kept, &schedule.reconnect) {
+            task.abort();
+        }
+        kept
+    });
+
+    let mut results = Vec::new();
+    let mut due = Vec::new();
+    for source in sources {
+        if matches!(source.status, ConnectionStatus::Connecting | ConnectionStatus::Disconnecting) {
+            continue;
+        }
+        let schedule = schedules.entry(source.name.clone()).or_default();
+        if let Some(task) = schedule.reconnect.take_if(|task| task.is_finished()) {
+            match task.await {
+                Ok(Ok(())) => due.push(source),
+                Ok(Err(e)) => results.push((source, Err(e))),
+                Err(e) => results.push((source, Err(DatabaseError::ConnectionError(e.to_string())))),
+            }
+        } else if schedule.reconnect.is_some() || schedule.next_check.is_some_and(|next| next > now) {
+            continue;
+        } else if source.status == ConnectionStatus::Failed {
+            let (state, name) = (Arc::clone(state), source.name.clone());
+            schedule.reconnect = Some(tokio::spawn(async move { reconnect(&state, &name).await }));
+        } else {
+            due.push(source);
+        }
+    }
+    let latencies = join_all(due.iter().map(|source| check_source(state, &source.name))).await;
+    results.extend(due.into_iter().zip(latencies));
+
+    for (source, result) in results {
+        let schedule = schedules.entry(source.name.clone()).or_default();
+        let recorded = match result {
+            Ok(latency) => {
+                schedule.failed_attempts = 0;
+                schedule.next_check = Some(Instant::now() + HEALTH_CHECK_INTERVAL);
+                state.connections.record_health(&source.name, status_for(latency), Some(latency), None).await
+            }
+            Err(e) => {
+                let delay = backoff_delay(schedule.failed_attempts);
+                schedule.failed_attempts = schedule.failed_attempts.saturating_add(1);
+                schedule.next_check = Some(Instant::now() + delay);
+                if source.status != ConnectionStatus::Failed {
+                    warn!("{} is down, reconnecting in {}s: {}", source.name, delay.as_secs(), e);
+                }
+                state.connections.record_health(&source.name, ConnectionStatus::Failed, None, Some(e.to_string())).await
+            }
+        };
+
+        // The source may have been removed by a reload in the meantime
+        if let (Ok(true), Some(info)) = (recorded, state.connections.connection_info(&source.name).await) {
+            let _ = state.tx.send(ServerMessage::SourceStatus { source: info }.to_text());
+        }
+    }
+}
+
+pub fn spawn_supervisor(state: Arc<AppState>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut schedules: HashMap<String, Schedule> = HashMap::new();
+        let mut ticker = tokio::time::interval(SUPERVISOR_TICK);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            ticker.tick().await;
+            supervise_due(&state, &mut schedules).await;
+        }
+    })
+}
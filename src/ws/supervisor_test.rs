@@ -0,0 +1,174 @@
+#[cfg(test)]
+mod tests {
+    use crate::config::config::SourceOptions;
+    use crate::db::connection_manager::{ConnectionManager, ConnectionStatus};
+    use crate::db::db_manager::SourceSpec;
+    use crate::db::query_builder::{DatabaseManager, DatabaseType};
+    use crate::db::{Database, DatabasePool};
+    use crate::utils::errors::DatabaseError;
+    use crate::ws::queries::RunningQueries;
+    use crate::ws::state::AppState;
+    use crate::ws::supervisor::{supervise_due, Schedule};
+    use async_trait::async_trait;
+    use serde_json::Value;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::sync::{broadcast, RwLock};
+
+    /// A pool that records when it is disconnected.
+    struct FakePool {
+        disconnected: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl Database for FakePool {
+        async fn connect(_connection_string: &str) -> Result<Self, DatabaseError> {
+            Err(DatabaseError::ConnectionError("not supported".to_string()))
+        }
+
+        async fn disconnect(&self) -> Result<(), DatabaseError> {
+            self.disconnected.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn execute_query(&self, _query: &str) -> Result<Vec<Value>, DatabaseError> {
+            Err(DatabaseError::ConnectionError("down".to_string()))
+        }
+
+        async fn list_databases(&self) -> Result<Vec<String>, DatabaseError> {
+            Err(DatabaseError::ConnectionError("down".to_string()))
+        }
+
+        async fn list_collections(&self, _database: &str) -> Result<Vec<String>, DatabaseError> {
+            Ok(Vec::new())
+        }
+
+        async fn get_schema(&self, _database: &str, _collection: &str) -> Result<Value, DatabaseError> {
+            Ok(Value::Null)
+        }
+    }
+
+    #[async_trait]
+    impl DatabasePool for FakePool {
+        async fn execute(&self, _query: &str, _params: Vec<Value>) -> Result<Vec<HashMap<String, Value>>, DatabaseError> {
+            Err(DatabaseError::ConnectionError("down".to_string()))
+        }
+    }
+
+    fn spec(name: &str, database_type: DatabaseType, url: Result<&str, &str>) -> SourceSpec {
+        SourceSpec {
+            name: name.to_string(),
+            database_type,
+            url: url.map(String::from).map_err(String::from),
+            options: SourceOptions::default(),
+        }
+    }
+
+    fn search(url: Result<&str, &str>) -> SourceSpec {
+        spec("search", DatabaseType::Elasticsearch, url)
+    }
+
+    /// A state with a failed source of `spec` whose pool is still registered.
+    async fn failed_source(spec: SourceSpec) -> (Arc<AppState>, Arc<AtomicBool>) {
+        let (tx, _) = broadcast::channel(16);
+        let (name, database_type) = (spec.name.clone(), spec.database_type.clone());
+        let state = AppState {
+            tx,
+            client_count: Mutex::new(0),
+            db_manager: RwLock::new(DatabaseManager::new()),
+            connections: ConnectionManager::new(),
+            sources: RwLock::new(vec![spec]),
+            redis: None,
+            pubsub: None,
+            elasticsearch: None,
+            pinned_sources: Vec::new(),
+            queries: RunningQueries::new(),
+        };
+        let disconnected = Arc::new(AtomicBool::new(false));
+        let pool = Arc::new(FakePool { disconnected: Arc::clone(&disconnected) });
+        state.connections.add_connection(&name, database_type, pool).await.unwrap();
+        state.connections.set_status(&name, ConnectionStatus::Failed, Some("down".to_string())).await.unwrap();
+        (Arc::new(state), disconnected)
+    }
+
+    async fn wait_for(flag: &AtomicBool) -> bool {
+        for _ in 0..50 {
+            if flag.load(Ordering::SeqCst) {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        false
+    }
+
+    /// Runs supervisor ticks until the reconnect of `name` has finished and been recorded.
+    async fn supervise_reconnect(state: &Arc<AppState>, schedules: &mut HashMap<String, Schedule>, name: &str) {
+        supervise_due(state, schedules).await;
+        for _ in 0..100 {
+            if schedules[name].reconnect.as_ref().is_none_or(|task| task.is_finished()) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        supervise_due(state, schedules).await;
+    }
+
+    #[tokio::test]
+    async fn test_failed_sources_are_reopened_and_rescheduled() {
+        // Opening an Elasticsearch pool does not connect; the check afterwards fails
+        let (state, disconnected) = failed_source(search(Ok("http://127.0.0.1:1"))).await;
+        let mut schedules = HashMap::new();
+        supervise_reconnect(&state, &mut schedules, "search").await;
+
+        assert!(wait_for(&disconnected).await, "The replaced pool should be disconnected");
+        assert!(state.db_manager.read().await.has_pool(&DatabaseType::Elasticsearch), "The reopened pool should serve queries");
+
+        let schedule: &Schedule = &schedules["search"];
+        assert_eq!(schedule.failed_attempts, 1, "A failed check should back off");
+        assert!(schedule.next_check.is_some());
+        assert_eq!(state.connections.connection_info("search").await.unwrap().status, ConnectionStatus::Failed);
+
+        supervise_due(&state, &mut schedules).await;
+        assert_eq!(schedules["search"].failed_attempts, 1, "Sources should not be checked before they are due");
+        assert!(schedules["search"].reconnect.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_wait_for_reloads() {
+        let (state, disconnected) = failed_source(search(Ok("http://127.0.0.1:1"))).await;
+        let mut schedules = HashMap::new();
+        let mut reload = state.sources.write().await;
+
+        supervise_due(&state, &mut schedules).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        *reload = vec![search(Err("search: either url or host is required"))];
+        drop(reload);
+        supervise_reconnect(&state, &mut schedules, "search").await;
+
+        let info = state.connections.connection_info("search").await.unwrap();
+        assert!(info.last_error.unwrap().contains("url or host"), "The reconnect should use the reloaded settings");
+        assert!(!wait_for(&disconnected).await, "The pool should be kept when the reconnect fails");
+    }
+
+    #[tokio::test]
+    async fn test_ticks_do_not_wait_for_reconnects() {
+        let (state, _) = failed_source(search(Ok("http://127.0.0.1:1"))).await;
+        let other = Arc::new(FakePool { disconnected: Arc::new(AtomicBool::new(false)) });
+        state.connections.add_connection("other", DatabaseType::PostgreSQL, other).await.unwrap();
+        let mut schedules = HashMap::new();
+
+        // The reconnect cannot read the sources until the reload is done
+        let reload = state.sources.write().await;
+        tokio::time::timeout(Duration::from_secs(1), supervise_due(&state, &mut schedules)).await
+            .expect("A tick should not wait for reconnects");
+        assert!(schedules["search"].reconnect.as_ref().is_some_and(|task| !task.is_finished()));
+        assert!(schedules["other"].next_check.is_some(), "Other sources should still be checked");
+
+        drop(reload);
+        supervise_reconnect(&state, &mut schedules, "search").await;
+        assert!(schedules["search"].reconnect.is_none());
+        assert_eq!(schedules["search"].failed_attempts, 1);
+    }
+}